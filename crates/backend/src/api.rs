@@ -0,0 +1,768 @@
+use std::sync::{Mutex, MutexGuard};
+
+use actix::Addr;
+use actix_web::{
+    http::{header, StatusCode},
+    web::{self, Data},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use planpoker_common::{
+    api::{
+        ApiError, ApiToken, CreateRoom, ImportIssues, NewStory, NewWebhook, RoomCreated,
+        StorySummary, TrackerSettings, API_VERSION,
+    },
+    RoomError, RoomId, Story,
+};
+
+use crate::{
+    session::SESSION_COOKIE,
+    trackers,
+    webhooks::{ListWebhooks, Subscribe, Unsubscribe, WebhookDispatcher},
+    AppState, User,
+};
+
+/// Mounts the REST API under `/api/v1`, see [`API_VERSION`].
+///
+/// Requests are authenticated with the session cookie or an API token in the
+/// `Authorization: Bearer` header, and go through the same [`AppState`] operations
+/// as the websocket requests, so connected clients see the changes right away.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    let scope = web::scope(&format!("/api/{}", API_VERSION));
+
+    #[cfg(feature = "schema")]
+    let scope = scope
+        .route("/schema.json", web::get().to(crate::openapi::schema))
+        .route("/openapi.json", web::get().to(crate::openapi::openapi));
+
+    cfg.service(
+        scope
+            .route("/tokens", web::post().to(create_token))
+            .route("/tokens", web::delete().to(revoke_token))
+            .route("/rooms", web::post().to(create_room))
+            .route("/rooms/{id}", web::get().to(room_info))
+            .route("/rooms/{id}", web::delete().to(close_room))
+            .route("/rooms/{id}/stories", web::get().to(stories))
+            .route("/rooms/{id}/stories", web::post().to(add_story))
+            .route("/rooms/{id}/results", web::get().to(results))
+            .route("/rooms/{id}/tracker", web::put().to(set_tracker))
+            .route("/rooms/{id}/tracker", web::delete().to(remove_tracker))
+            .route("/rooms/{id}/tracker/import", web::post().to(import_issues))
+            .route("/rooms/{id}/webhooks", web::get().to(webhooks))
+            .route("/rooms/{id}/webhooks", web::post().to(add_webhook))
+            .route(
+                "/rooms/{id}/webhooks/{index}",
+                web::delete().to(remove_webhook),
+            ),
+    );
+}
+
+/// Issues an API token for the current user, or for a new user if the request is not authenticated.
+async fn create_token(req: HttpRequest, data: Data<Mutex<AppState>>) -> HttpResponse {
+    let mut state = match rate_limited(&req, &data) {
+        Ok(state) => state,
+        Err(res) => return res,
+    };
+
+    let user = match authenticate(&req, &mut state) {
+        Ok(user) => state.add_session(&user),
+        Err(_) => state.create_user(),
+    };
+
+    let (token, session) = state.sessions.issue(user.session_id);
+
+    HttpResponse::Created().json(ApiToken {
+        token,
+        expires_at: session.expires_at,
+    })
+}
+
+/// Revokes the API token the request was made with.
+async fn revoke_token(req: HttpRequest, data: Data<Mutex<AppState>>) -> HttpResponse {
+    let mut state = match rate_limited(&req, &data) {
+        Ok(state) => state,
+        Err(res) => return res,
+    };
+
+    let token = bearer_token(&req).and_then(|token| state.sessions.verify(token).ok());
+
+    match token {
+        Some(token) => {
+            state.sessions.revoke(token.session_id);
+            HttpResponse::NoContent().finish()
+        }
+        None => unauthorized(),
+    }
+}
+
+async fn create_room(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    body: Option<web::Json<CreateRoom>>,
+) -> HttpResponse {
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let deck = body.map(|body| body.deck).unwrap_or_default();
+
+    match state.create_room(user.id, deck) {
+        Ok(id) => HttpResponse::Created().json(RoomCreated { id }),
+        Err(err) => room_error(err),
+    }
+}
+
+async fn room_info(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    let (state, _) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    match state.room(*path) {
+        Some(room) => HttpResponse::Ok().json(room.client_info()),
+        None => room_error(RoomError::NoSuchRoom(*path)),
+    }
+}
+
+async fn close_room(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let res = existing_room(&state, *path).and_then(|_| state.close_room(user.id, *path));
+
+    match res {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => room_error(err),
+    }
+}
+
+async fn stories(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    let (state, _) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    match state.room(*path) {
+        Some(room) => {
+            let stories = room
+                .info
+                .stories
+                .iter()
+                .enumerate()
+                .map(story_summary)
+                .collect::<Vec<_>>();
+
+            HttpResponse::Ok().json(stories)
+        }
+        None => room_error(RoomError::NoSuchRoom(*path)),
+    }
+}
+
+async fn add_story(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+    body: web::Json<NewStory>,
+) -> HttpResponse {
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let title = body.into_inner().title;
+    let res = existing_room(&state, *path).and_then(|_| state.add_story(user.id, *path, title));
+
+    match res {
+        Ok(()) => HttpResponse::Created().finish(),
+        Err(err) => room_error(err),
+    }
+}
+
+/// The stories of the room with the votes of every round and the final estimates.
+/// Votes are only included as far as the voters are known, see [`crate::room::Room::archive_round`].
+async fn results(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    let (state, _) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    match state.room(*path) {
+        Some(room) => HttpResponse::Ok().json(&room.info.stories),
+        None => room_error(RoomError::NoSuchRoom(*path)),
+    }
+}
+
+/// Sets the issue tracker of the room. The settings contain credentials, so they are never sent back.
+async fn set_tracker(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+    body: web::Json<TrackerSettings>,
+) -> HttpResponse {
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let res = existing_room(&state, *path)
+        .and_then(|_| state.set_tracker(user.id, *path, Some(body.into_inner())));
+
+    match res {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => room_error(err),
+    }
+}
+
+async fn remove_tracker(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let res = existing_room(&state, *path).and_then(|_| state.set_tracker(user.id, *path, None));
+
+    match res {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => room_error(err),
+    }
+}
+
+/// Adds the issues matching the query as stories and responds with the added stories.
+async fn import_issues(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    path: web::Path<RoomId>,
+    body: web::Json<ImportIssues>,
+) -> HttpResponse {
+    let tracker = {
+        let (state, user) = match authenticated(&req, &data) {
+            Ok(auth) => auth,
+            Err(res) => return res,
+        };
+
+        match existing_room(&state, *path).and_then(|_| state.tracker(user.id, *path)) {
+            Ok(settings) => trackers::tracker(&settings, state.allowed_hosts.clone()),
+            Err(err) => return room_error(err),
+        }
+    };
+
+    // The state is not locked while waiting for the tracker.
+    let issues = match tracker.import(&body.query).await {
+        Ok(issues) => issues,
+        Err(err) => {
+            return HttpResponse::BadGateway().json(ApiError {
+                error: err.to_string(),
+                room_error: None,
+            })
+        }
+    };
+
+    let (mut state, user) = match authenticated(&req, &data) {
+        Ok(auth) => auth,
+        Err(res) => return res,
+    };
+
+    let count = state.room(*path).map(|room| room.info.stories.len());
+    let res =
+        existing_room(&state, *path).and_then(|_| state.import_stories(user.id, *path, issues));
+
+    match (res, state.room(*path)) {
+        (Ok(()), Some(room)) => {
+            let stories = room
+                .info
+                .stories
+                .iter()
+                .enumerate()
+                .skip(count.unwrap_or_default())
+                .map(story_summary)
+                .collect::<Vec<_>>();
+
+            HttpResponse::Created().json(stories)
+        }
+        (Ok(()), None) => room_error(RoomError::NoSuchRoom(*path)),
+        (Err(err), _) => room_error(err),
+    }
+}
+
+/// The webhooks of the room with their recent deliveries, only visible to the room admin
+/// as the URLs may contain credentials.
+async fn webhooks(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    dispatcher: Data<Addr<WebhookDispatcher>>,
+    path: web::Path<RoomId>,
+) -> HttpResponse {
+    if let Err(res) = room_admin(&req, &data, *path) {
+        return res;
+    }
+
+    match dispatcher.send(ListWebhooks(*path)).await {
+        Ok(webhooks) => HttpResponse::Ok().json(webhooks),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+async fn add_webhook(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    dispatcher: Data<Addr<WebhookDispatcher>>,
+    path: web::Path<RoomId>,
+    body: web::Json<NewWebhook>,
+) -> HttpResponse {
+    if let Err(res) = room_admin(&req, &data, *path) {
+        return res;
+    }
+
+    let msg = Subscribe {
+        room_id: *path,
+        webhook: body.into_inner(),
+    };
+
+    match dispatcher.send(msg).await {
+        Ok(Ok(())) => HttpResponse::Created().finish(),
+        Ok(Err(err)) => room_error(err),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+async fn remove_webhook(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    dispatcher: Data<Addr<WebhookDispatcher>>,
+    path: web::Path<(RoomId, usize)>,
+) -> HttpResponse {
+    let (room_id, index) = path.into_inner();
+    if let Err(res) = room_admin(&req, &data, room_id) {
+        return res;
+    }
+
+    match dispatcher.send(Unsubscribe { room_id, index }).await {
+        Ok(Ok(())) => HttpResponse::NoContent().finish(),
+        Ok(Err(err)) => room_error(err),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+/// Checks that the request is made by the admin of the room, without keeping the state locked.
+fn room_admin(
+    req: &HttpRequest,
+    data: &Data<Mutex<AppState>>,
+    room_id: RoomId,
+) -> Result<(), HttpResponse> {
+    let (state, user) = authenticated(req, data)?;
+    existing_room(&state, room_id).map_err(room_error)?;
+
+    if state.is_room_admin(user.id, room_id) {
+        Ok(())
+    } else {
+        Err(room_error(RoomError::NotAdmin))
+    }
+}
+
+fn story_summary((index, story): (usize, &Story)) -> StorySummary {
+    StorySummary {
+        index,
+        title: story.title.clone(),
+        estimate: story.estimate,
+        rounds: story.rounds.len(),
+    }
+}
+
+fn existing_room(state: &AppState, room_id: RoomId) -> Result<(), RoomError> {
+    match state.room(room_id) {
+        Some(_) => Ok(()),
+        None => Err(RoomError::NoSuchRoom(room_id)),
+    }
+}
+
+/// Locks the state, if the client has not made too many requests.
+fn rate_limited<'a>(
+    req: &HttpRequest,
+    data: &'a Data<Mutex<AppState>>,
+) -> Result<MutexGuard<'a, AppState>, HttpResponse> {
+    let mut state = data.lock().unwrap();
+
+    match req.peer_addr() {
+        Some(addr) if !state.ip_limiter.try_take(addr.ip()) => {
+            Err(room_error(RoomError::RateLimited))
+        }
+        _ => Ok(state),
+    }
+}
+
+fn authenticated<'a>(
+    req: &HttpRequest,
+    data: &'a Data<Mutex<AppState>>,
+) -> Result<(MutexGuard<'a, AppState>, User), HttpResponse> {
+    let mut state = rate_limited(req, data)?;
+    let user = authenticate(req, &mut state).map_err(|_| unauthorized())?;
+
+    Ok((state, user))
+}
+
+/// Finds the user of the API token, or of the session cookie if there is no token.
+fn authenticate(req: &HttpRequest, state: &mut AppState) -> Result<User, ()> {
+    let cookie = req.cookie(SESSION_COOKIE);
+    let token = match bearer_token(req) {
+        Some(token) => token,
+        None => cookie.as_ref().map(|c| c.value()).ok_or(())?,
+    };
+
+    let token = state.sessions.verify(token).map_err(|err| {
+        println!("Rejected API token: {:?}", err);
+    })?;
+
+    state.user_with_session_id(token.session_id).ok_or(())
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(ApiError {
+        error: "missing or invalid session or API token".to_owned(),
+        room_error: None,
+    })
+}
+
+fn room_error(err: RoomError) -> HttpResponse {
+    let status = match err {
+        RoomError::NoSuchRoom(_)
+        | RoomError::NoSuchStory(_)
+        | RoomError::NoSuchCard(_)
+        | RoomError::NoSuchWebhook(_)
+        | RoomError::NoTracker => StatusCode::NOT_FOUND,
+        RoomError::NotAdmin | RoomError::NotInRoom => StatusCode::FORBIDDEN,
+        RoomError::RateLimited | RoomError::TooManyRooms => StatusCode::TOO_MANY_REQUESTS,
+        RoomError::ServerFull => StatusCode::SERVICE_UNAVAILABLE,
+        RoomError::RoomFull(_)
+        | RoomError::TooManyWebhooks
+        | RoomError::AlreadyRevealed
+        | RoomError::NotRevealed
+        | RoomError::RoomClosed => StatusCode::CONFLICT,
+        RoomError::MessageTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        RoomError::InvalidEstimate
+        | RoomError::EmptyChatMessage
+        | RoomError::ChatMessageTooLong(_)
+        | RoomError::InvalidWebhookUrl
+        | RoomError::InvalidTrackerSettings => StatusCode::BAD_REQUEST,
+    };
+
+    HttpResponse::build(status).json(ApiError {
+        error: format!("{:?}", err),
+        room_error: Some(err),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{net::TcpListener, time::Duration};
+
+    use actix::Actor;
+    use actix_http::ws;
+    use actix_web::{
+        client::Client,
+        cookie::Cookie,
+        dev::{Service, ServiceResponse},
+        test::{self, TestRequest},
+        App, Error, HttpServer,
+    };
+    use futures_util::{SinkExt, Stream, StreamExt};
+    use planpoker_common::{RoomInfo, RoomMessage, RoomRequest, RoomState};
+
+    use super::*;
+    use crate::{config::Config, limits::Limits, session::SessionKey, webhooks::WebhookDispatcher};
+
+    fn api(path: &str) -> String {
+        format!("/api/{}{}", API_VERSION, path)
+    }
+
+    pub(crate) fn app_data() -> (Data<Mutex<AppState>>, Data<Config>, Addr<WebhookDispatcher>) {
+        let config = Config {
+            address: "127.0.0.1:0".to_owned(),
+            session_keys: vec![SessionKey::new("test", b"secret".to_vec())],
+            session_ttl: Duration::from_secs(3600),
+            secure_cookies: false,
+            oidc: None,
+            limits: Limits::default(),
+            webhooks: Vec::new(),
+            webhook_allowed_hosts: Vec::new(),
+        };
+        let webhooks = WebhookDispatcher::new(Vec::new(), Vec::new()).start();
+        let state = Data::new(Mutex::new(AppState::new(&config, webhooks.clone())));
+
+        (state, Data::new(config), webhooks)
+    }
+
+    macro_rules! init_app {
+        () => {{
+            let (state, config, webhooks) = app_data();
+            test::init_service(
+                App::new()
+                    .app_data(state)
+                    .app_data(config)
+                    .data(webhooks)
+                    .configure(configure),
+            )
+            .await
+        }};
+    }
+
+    async fn call<S>(app: &mut S, req: TestRequest, token: Option<&str>) -> ServiceResponse
+    where
+        S: Service<Request = actix_http::Request, Response = ServiceResponse, Error = Error>,
+    {
+        let req = match token {
+            Some(token) => req.header(header::AUTHORIZATION, format!("Bearer {}", token)),
+            None => req,
+        };
+
+        test::call_service(app, req.to_request()).await
+    }
+
+    async fn issue_token<S>(app: &mut S, token: Option<&str>) -> String
+    where
+        S: Service<Request = actix_http::Request, Response = ServiceResponse, Error = Error>,
+    {
+        let res = call(app, TestRequest::post().uri(&api("/tokens")), token).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let token: ApiToken = test::read_body_json(res).await;
+        token.token
+    }
+
+    async fn create_room<S>(app: &mut S, token: &str) -> RoomId
+    where
+        S: Service<Request = actix_http::Request, Response = ServiceResponse, Error = Error>,
+    {
+        let res = call(app, TestRequest::post().uri(&api("/rooms")), Some(token)).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+
+        let room: RoomCreated = test::read_body_json(res).await;
+        room.id
+    }
+
+    /// Checks the status and the room error of an error response.
+    async fn assert_error(res: ServiceResponse, status: StatusCode, room_error: Option<RoomError>) {
+        assert_eq!(res.status(), status);
+
+        let err: ApiError = test::read_body_json(res).await;
+        assert_eq!(err.room_error, room_error, "{}", err.error);
+    }
+
+    #[actix_rt::test]
+    async fn tokens_are_issued_and_revoked() {
+        let mut app = init_app!();
+
+        let token = issue_token(&mut app, None).await;
+        let room_id = create_room(&mut app, &token).await;
+
+        // A token issued with a token is for the same user, the admin of the room.
+        let second = issue_token(&mut app, Some(&token)).await;
+        assert_ne!(second, token);
+        let req = TestRequest::get().uri(&api(&format!("/rooms/{}", room_id)));
+        let res = call(&mut app, req, Some(&second)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let room: RoomInfo = test::read_body_json(res).await;
+        assert_eq!(room.id, room_id);
+
+        let res = call(
+            &mut app,
+            TestRequest::delete().uri(&api("/tokens")),
+            Some(&token),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let req = TestRequest::post().uri(&api("/rooms"));
+        assert_error(
+            call(&mut app, req, Some(&token)).await,
+            StatusCode::UNAUTHORIZED,
+            None,
+        )
+        .await;
+        let req = TestRequest::delete().uri(&api("/tokens"));
+        assert_error(
+            call(&mut app, req, Some(&token)).await,
+            StatusCode::UNAUTHORIZED,
+            None,
+        )
+        .await;
+
+        // Revoking one token leaves the other sessions of the user alone.
+        let req = TestRequest::delete().uri(&api(&format!("/rooms/{}", room_id)));
+        let res = call(&mut app, req, Some(&second)).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[actix_rt::test]
+    async fn errors_are_mapped_to_status_codes() {
+        let mut app = init_app!();
+        let admin = issue_token(&mut app, None).await;
+        let other = issue_token(&mut app, None).await;
+        let room_id = create_room(&mut app, &admin).await;
+        let room = api(&format!("/rooms/{}", room_id));
+
+        let req = TestRequest::get().uri(&room);
+        assert_error(
+            call(&mut app, req, None).await,
+            StatusCode::UNAUTHORIZED,
+            None,
+        )
+        .await;
+        let req = TestRequest::get().uri(&room);
+        let res = call(&mut app, req, Some("not a token")).await;
+        assert_error(res, StatusCode::UNAUTHORIZED, None).await;
+
+        let missing = room_id.wrapping_add(1);
+        let req = TestRequest::get().uri(&api(&format!("/rooms/{}", missing)));
+        let res = call(&mut app, req, Some(&other)).await;
+        assert_error(
+            res,
+            StatusCode::NOT_FOUND,
+            Some(RoomError::NoSuchRoom(missing)),
+        )
+        .await;
+
+        let req = TestRequest::delete().uri(&format!("{}/webhooks/3", room));
+        let res = call(&mut app, req, Some(&admin)).await;
+        assert_error(
+            res,
+            StatusCode::NOT_FOUND,
+            Some(RoomError::NoSuchWebhook(3)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri(&format!("{}/stories", room))
+            .set_json(&NewStory {
+                title: "Login page".to_owned(),
+            });
+        let res = call(&mut app, req, Some(&other)).await;
+        assert_error(res, StatusCode::FORBIDDEN, Some(RoomError::NotAdmin)).await;
+
+        let req = TestRequest::delete().uri(&room);
+        let res = call(&mut app, req, Some(&other)).await;
+        assert_error(res, StatusCode::FORBIDDEN, Some(RoomError::NotAdmin)).await;
+
+        let req = TestRequest::delete().uri(&room);
+        assert_eq!(
+            call(&mut app, req, Some(&admin)).await.status(),
+            StatusCode::NO_CONTENT
+        );
+        let req = TestRequest::delete().uri(&room);
+        let res = call(&mut app, req, Some(&admin)).await;
+        assert_error(res, StatusCode::CONFLICT, Some(RoomError::RoomClosed)).await;
+    }
+
+    /// Waits for room info matching the predicate, skipping other messages.
+    async fn room_info<S>(websocket: &mut S, matches: impl Fn(&RoomInfo) -> bool) -> RoomInfo
+    where
+        S: Stream<Item = Result<ws::Frame, ws::ProtocolError>> + Unpin,
+    {
+        let wait = async {
+            while let Some(frame) = websocket.next().await {
+                if let ws::Frame::Text(text) = frame.unwrap() {
+                    match serde_json::from_slice(&text).unwrap() {
+                        RoomMessage::RoomInfo(room) if matches(&room) => return room,
+                        _ => {}
+                    }
+                }
+            }
+            panic!("websocket closed");
+        };
+
+        actix_rt::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("no matching room info received")
+    }
+
+    #[actix_rt::test]
+    async fn websocket_clients_see_the_changes() {
+        let (state, config, webhooks) = app_data();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .app_data(config.clone())
+                .data(webhooks.clone())
+                .route("/ws/", web::get().to(crate::ws_index))
+                .configure(configure)
+        })
+        .listen(listener)
+        .unwrap()
+        .run();
+
+        let client = Client::default();
+        let url = |path: &str| format!("http://{}{}", addr, api(path));
+
+        let mut res = client.post(url("/tokens")).send().await.unwrap();
+        let token = res.json::<ApiToken>().await.unwrap().token;
+        let mut res = client
+            .post(url("/rooms"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        let room_id = res.json::<RoomCreated>().await.unwrap().id;
+
+        let (_, mut websocket) = client
+            .ws(format!("ws://{}/ws/", addr))
+            .cookie(Cookie::new(SESSION_COOKIE, token.clone()))
+            .connect()
+            .await
+            .unwrap();
+        let join = serde_json::to_string(&RoomRequest::JoinRoom(room_id)).unwrap();
+        websocket.send(ws::Message::Text(join)).await.unwrap();
+        room_info(&mut websocket, |room| room.id == room_id).await;
+
+        let res = client
+            .post(url(&format!("/rooms/{}/stories", room_id)))
+            .bearer_auth(&token)
+            .send_json(&NewStory {
+                title: "Login page".to_owned(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+        let room = room_info(&mut websocket, |room| !room.stories.is_empty()).await;
+        assert_eq!(room.stories[0].title, "Login page");
+
+        let res = client
+            .delete(url(&format!("/rooms/{}", room_id)))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        room_info(&mut websocket, |room| room.state == RoomState::Closed).await;
+    }
+}
@@ -0,0 +1,255 @@
+//! A tiny OpenID Connect provider for local development and tests.
+//!
+//! Every authorization request is approved immediately. The logged in subject is
+//! taken from the `login_hint` parameter, falling back to `MOCK_OIDC_SUBJECT`.
+//! ID tokens are unsigned (`alg: none`), which is fine for the backend because it
+//! receives them directly from the token endpoint.
+
+use std::{
+    collections::HashMap,
+    env,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    http::header,
+    middleware,
+    web::{self, Data},
+    App, HttpRequest, HttpResponse, HttpServer,
+};
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+struct Grant {
+    client_id: String,
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    subject: String,
+}
+
+pub struct Provider {
+    issuer: String,
+    default_subject: String,
+    grants: Mutex<HashMap<String, Grant>>,
+    access_tokens: Mutex<HashMap<String, String>>,
+}
+
+impl Provider {
+    pub fn new(issuer: String, default_subject: String) -> Self {
+        Self {
+            issuer,
+            default_subject,
+            grants: Mutex::new(HashMap::new()),
+            access_tokens: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    login_hint: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: String,
+    code_verifier: Option<String>,
+}
+
+async fn discovery(provider: Data<Provider>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "issuer": provider.issuer,
+        "authorization_endpoint": format!("{}/authorize", provider.issuer),
+        "token_endpoint": format!("{}/token", provider.issuer),
+        "userinfo_endpoint": format!("{}/userinfo", provider.issuer),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["none"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+async fn authorize(provider: Data<Provider>, query: web::Query<AuthorizeQuery>) -> HttpResponse {
+    let query = query.into_inner();
+    let code = random_string();
+
+    let mut params = vec![("code", code.as_str())];
+    if let Some(state) = query.state.as_ref() {
+        params.push(("state", state.as_str()));
+    }
+
+    let location = format!(
+        "{}{}{}",
+        query.redirect_uri,
+        if query.redirect_uri.contains('?') {
+            '&'
+        } else {
+            '?'
+        },
+        serde_urlencoded::to_string(&params).unwrap()
+    );
+
+    provider.grants.lock().unwrap().insert(
+        code,
+        Grant {
+            client_id: query.client_id,
+            redirect_uri: query.redirect_uri,
+            nonce: query.nonce,
+            code_challenge: query.code_challenge,
+            subject: query
+                .login_hint
+                .unwrap_or_else(|| provider.default_subject.clone()),
+        },
+    );
+
+    HttpResponse::Found()
+        .header(header::LOCATION, location)
+        .finish()
+}
+
+async fn token(provider: Data<Provider>, form: web::Form<TokenForm>) -> HttpResponse {
+    let grant = provider.grants.lock().unwrap().remove(&form.code);
+
+    let grant = match grant {
+        Some(grant)
+            if form.grant_type == "authorization_code"
+                && grant.client_id == form.client_id
+                && grant.redirect_uri == form.redirect_uri =>
+        {
+            grant
+        }
+        _ => return token_error("invalid_grant"),
+    };
+
+    if let Some(challenge) = grant.code_challenge.as_ref() {
+        let verified = form.code_verifier.as_ref().map_or(false, |verifier| {
+            let digest = Sha256::digest(verifier.as_bytes());
+            &base64::encode_config(digest, base64::URL_SAFE_NO_PAD) == challenge
+        });
+
+        if !verified {
+            return token_error("invalid_grant");
+        }
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let claims = json!({
+        "iss": provider.issuer,
+        "sub": grant.subject,
+        "aud": grant.client_id,
+        "iat": now,
+        "exp": now + 3600,
+        "nonce": grant.nonce,
+        "name": display_name(&grant.subject),
+        "preferred_username": grant.subject,
+    });
+
+    let id_token = format!(
+        "{}.{}.",
+        base64::encode_config(r#"{"alg":"none","typ":"JWT"}"#, base64::URL_SAFE_NO_PAD),
+        base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD)
+    );
+
+    let access_token = random_string();
+    provider
+        .access_tokens
+        .lock()
+        .unwrap()
+        .insert(access_token.clone(), grant.subject);
+
+    HttpResponse::Ok().json(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": 3600,
+        "id_token": id_token,
+    }))
+}
+
+async fn userinfo(provider: Data<Provider>, req: HttpRequest) -> HttpResponse {
+    let subject = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .and_then(|token| provider.access_tokens.lock().unwrap().get(token).cloned());
+
+    match subject {
+        Some(subject) => HttpResponse::Ok().json(json!({
+            "sub": subject,
+            "name": display_name(&subject),
+            "preferred_username": subject,
+        })),
+        None => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+fn token_error(error: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": error }))
+}
+
+fn display_name(subject: &str) -> String {
+    let mut chars = subject.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Routes of the provider, which needs the [`Provider`] as app data.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(
+        "/.well-known/openid-configuration",
+        web::get().to(discovery),
+    )
+    .route("/authorize", web::get().to(authorize))
+    .route("/token", web::post().to(token))
+    .route("/userinfo", web::get().to(userinfo));
+}
+
+fn random_string() -> String {
+    let bytes = rand::thread_rng().gen::<[u8; 16]>();
+
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
+    env_logger::init();
+
+    let address = env::var("MOCK_OIDC_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8090".to_owned());
+
+    let provider = Data::new(Provider::new(
+        format!("http://{}", address),
+        env::var("MOCK_OIDC_SUBJECT").unwrap_or_else(|_| "alice".to_owned()),
+    ));
+
+    println!("Mock OpenID Connect provider at {}", provider.issuer);
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(provider.clone())
+            .wrap(middleware::Logger::default())
+            .configure(configure)
+    })
+    .bind(address)?
+    .run()
+    .await
+}
@@ -0,0 +1,424 @@
+//! Result posts for the incoming webhooks of team chats
+
+use planpoker_common::{api::WebhookEvent, CardValue, RoomInfo, RoomState, Vote};
+use serde_json::{json, Value};
+
+/// Longest text Slack accepts in a header block
+const SLACK_HEADER_LENGTH: usize = 150;
+
+/// Readable summary of a room event, shared by the chat formats
+struct Summary {
+    title: String,
+    /// `(name, card)` of every vote, or `(card, count)` in anonymous mode
+    votes: Vec<(String, String)>,
+    /// `(label, value)` pairs, such as the average
+    stats: Vec<(&'static str, String)>,
+    room: String,
+}
+
+impl Summary {
+    fn new(event: WebhookEvent, room: &RoomInfo) -> Self {
+        let story = room
+            .current_story()
+            .map(|story| story.title.clone())
+            .unwrap_or_else(|| format!("Room {}", room.id));
+
+        let title = match event {
+            WebhookEvent::RoundRevealed => format!("Votes revealed: {}", story),
+            WebhookEvent::EstimateFinalized => format!("Estimate set: {}", story),
+            WebhookEvent::RoomClosed => format!("Room {} closed", room.id),
+        };
+
+        let (votes, stats) = if room.state.is_revealed() {
+            (votes(room), stats(room))
+        } else {
+            (Vec::new(), Vec::new())
+        };
+
+        Self {
+            title,
+            votes,
+            stats,
+            room: format!("Planning poker room {}", room.id),
+        }
+    }
+
+    fn votes_text(&self, bold: &str, escape: fn(&str) -> String) -> String {
+        if self.votes.is_empty() {
+            return String::new();
+        }
+
+        self.votes
+            .iter()
+            .map(|(who, card)| format!("• {1}{0}{1}: {2}", escape(who), bold, escape(card)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Slack message with blocks, the `text` is shown in notifications.
+pub fn slack(event: WebhookEvent, room: &RoomInfo) -> Value {
+    let summary = Summary::new(event, room);
+
+    let mut blocks = vec![json!({
+        "type": "header",
+        "text": { "type": "plain_text", "text": truncate(&summary.title, SLACK_HEADER_LENGTH) },
+    })];
+
+    let votes = summary.votes_text("*", slack_escape);
+    if !votes.is_empty() {
+        blocks.push(json!({
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": votes },
+        }));
+    }
+
+    if !summary.stats.is_empty() {
+        let fields = summary
+            .stats
+            .iter()
+            .map(|(label, value)| {
+                json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", label, value) })
+            })
+            .collect::<Vec<_>>();
+
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+
+    blocks.push(json!({
+        "type": "context",
+        "elements": [{ "type": "mrkdwn", "text": summary.room }],
+    }));
+
+    json!({
+        "text": slack_escape(&summary.title),
+        "blocks": blocks,
+    })
+}
+
+/// Mattermost message with an attachment, Mattermost does not support blocks.
+pub fn mattermost(event: WebhookEvent, room: &RoomInfo) -> Value {
+    let summary = Summary::new(event, room);
+
+    let fields = summary
+        .stats
+        .iter()
+        .map(|(label, value)| json!({ "short": true, "title": label, "value": value }))
+        .collect::<Vec<_>>();
+
+    json!({
+        "text": "",
+        "attachments": [{
+            "fallback": summary.title,
+            "title": summary.title,
+            "text": summary.votes_text("**", |text| text.to_owned()),
+            "fields": fields,
+            "footer": summary.room,
+        }],
+    })
+}
+
+fn votes(room: &RoomInfo) -> Vec<(String, String)> {
+    let label = |card: u32| {
+        room.cards
+            .get(card as usize)
+            .map(|card| card.as_str().to_owned())
+            .unwrap_or_else(|| "?".to_owned())
+    };
+
+    match room.distribution.as_ref() {
+        Some(distribution) => distribution
+            .iter()
+            .map(|c| match c.count {
+                1 => (label(c.card), "1 vote".to_owned()),
+                count => (label(c.card), format!("{} votes", count)),
+            })
+            .collect(),
+        None => room
+            .users
+            .iter()
+            .enumerate()
+            .filter_map(|(i, user)| match user.vote {
+                Vote::Revealed(card) => {
+                    // Same fallback as the frontend for users without a name
+                    let name = user.name.clone().unwrap_or_else(|| format!("User {}", i));
+                    Some((name, label(card)))
+                }
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+fn stats(room: &RoomInfo) -> Vec<(&'static str, String)> {
+    let values = room
+        .revealed_cards()
+        .into_iter()
+        .filter_map(|card| room.cards.get(card as usize)?.value())
+        .map(CardValue::as_f64)
+        .collect::<Vec<_>>();
+
+    let mut stats = Vec::new();
+
+    if !values.is_empty() {
+        let average = values.iter().sum::<f64>() / values.len() as f64;
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        stats.push(("Average", format!("{:.1}", average)));
+        stats.push((
+            "Range",
+            format!("{} – {}", CardValue::from(min), CardValue::from(max)),
+        ));
+    }
+
+    if let Some(suggested) = room.suggested_estimate() {
+        stats.push(("Suggested", suggested.to_string()));
+    }
+
+    let estimate = room.current_story().and_then(|story| story.estimate);
+    if let (RoomState::Finalized, Some(estimate)) = (room.state, estimate) {
+        stats.push(("Final estimate", estimate.to_string()));
+    }
+
+    stats
+}
+
+/// Shortens the text to at most `max` characters, ending it with an ellipsis if it was cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+
+    let mut truncated = text.chars().take(max - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
+
+/// Escapes the characters Slack uses for links and mentions.
+fn slack_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use planpoker_common::{
+        api::{NewWebhook, WebhookFormat},
+        Card, CardCount, RoomUser, Story,
+    };
+
+    use actix::Actor;
+
+    use super::*;
+    use crate::webhooks::{tests::Receiver, Publish, Subscribe, WebhookDispatcher};
+
+    fn card(display: &'static str, value: Option<u32>) -> Card {
+        Card::new(display, value.map(CardValue::from))
+    }
+
+    fn user(user_id: u128, name: Option<&str>, vote: Vote) -> RoomUser {
+        RoomUser {
+            user_id,
+            name: name.map(str::to_owned),
+            vote,
+        }
+    }
+
+    /// Room with revealed votes of 1 and 5, a `?` and a user who did not vote
+    fn revealed_room(story: &str) -> RoomInfo {
+        let mut room = RoomInfo::new(1, 10);
+        room.cards = vec![
+            card("1", Some(1)),
+            card("3", Some(3)),
+            card("5", Some(5)),
+            card("?", None),
+        ];
+        room.users = vec![
+            user(10, Some("Alice"), Vote::Revealed(0)),
+            user(11, Some("Bob <admin>"), Vote::Revealed(2)),
+            user(12, None, Vote::Revealed(3)),
+            user(13, Some("Carol"), Vote::None),
+        ];
+        room.state = RoomState::Revealed;
+        room.stories = vec![Story::new(story)];
+        room.current_story = Some(0);
+        room
+    }
+
+    /// Posts the event to a stub chat endpoint and returns the body it received.
+    async fn post(format: WebhookFormat, event: WebhookEvent, room: RoomInfo) -> Value {
+        let receiver = Receiver::start();
+        let dispatcher = WebhookDispatcher::new(Vec::new(), vec!["127.0.0.1".to_owned()]).start();
+        let webhook = NewWebhook {
+            url: format!("{}/hooks/abc", receiver.url),
+            secret: None,
+            events: Vec::new(),
+            format,
+        };
+        dispatcher
+            .send(Subscribe {
+                room_id: room.id,
+                webhook,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        dispatcher.do_send(Publish { event, room });
+
+        let received = receiver.wait_for(1).await;
+        assert_eq!(received[0].path, "/hooks/abc");
+        assert_eq!(
+            received[0].headers.get("content-type").unwrap(),
+            "application/json"
+        );
+        serde_json::from_str(&received[0].body).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn slack_round_revealed() {
+        let room = revealed_room("Login page");
+        let body = post(WebhookFormat::Slack, WebhookEvent::RoundRevealed, room).await;
+
+        assert_eq!(
+            body,
+            json!({
+                "text": "Votes revealed: Login page",
+                "blocks": [
+                    {
+                        "type": "header",
+                        "text": { "type": "plain_text", "text": "Votes revealed: Login page" },
+                    },
+                    {
+                        "type": "section",
+                        "text": {
+                            "type": "mrkdwn",
+                            "text": "• *Alice*: 1\n• *Bob &lt;admin&gt;*: 5\n• *User 2*: ?",
+                        },
+                    },
+                    {
+                        "type": "section",
+                        "fields": [
+                            { "type": "mrkdwn", "text": "*Average*\n3.0" },
+                            { "type": "mrkdwn", "text": "*Range*\n1 – 5" },
+                            { "type": "mrkdwn", "text": "*Suggested*\n3" },
+                        ],
+                    },
+                    {
+                        "type": "context",
+                        "elements": [{ "type": "mrkdwn", "text": "Planning poker room 1" }],
+                    },
+                ],
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn slack_room_closed() {
+        let mut room = revealed_room("Login page");
+        room.state = RoomState::Closed;
+        let body = post(WebhookFormat::Slack, WebhookEvent::RoomClosed, room).await;
+
+        assert_eq!(
+            body,
+            json!({
+                "text": "Room 1 closed",
+                "blocks": [
+                    {
+                        "type": "header",
+                        "text": { "type": "plain_text", "text": "Room 1 closed" },
+                    },
+                    {
+                        "type": "context",
+                        "elements": [{ "type": "mrkdwn", "text": "Planning poker room 1" }],
+                    },
+                ],
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn slack_header_is_truncated() {
+        let story = "é".repeat(200);
+        let body = post(
+            WebhookFormat::Slack,
+            WebhookEvent::RoundRevealed,
+            revealed_room(&story),
+        )
+        .await;
+
+        let title = format!("Votes revealed: {}", story);
+        let header = body["blocks"][0]["text"]["text"].as_str().unwrap();
+        assert_eq!(header.chars().count(), SLACK_HEADER_LENGTH);
+        assert!(header.ends_with('…'));
+        assert!(title.starts_with(header.trim_end_matches('…')));
+
+        // The notification text is not limited
+        assert_eq!(body["text"], title.as_str());
+    }
+
+    #[actix_rt::test]
+    async fn mattermost_estimate_finalized() {
+        let mut room = revealed_room("Login page");
+        room.state = RoomState::Finalized;
+        room.stories[0].estimate = Some(CardValue::from(5));
+        let body = post(
+            WebhookFormat::Mattermost,
+            WebhookEvent::EstimateFinalized,
+            room,
+        )
+        .await;
+
+        assert_eq!(
+            body,
+            json!({
+                "text": "",
+                "attachments": [{
+                    "fallback": "Estimate set: Login page",
+                    "title": "Estimate set: Login page",
+                    "text": "• **Alice**: 1\n• **Bob <admin>**: 5\n• **User 2**: ?",
+                    "fields": [
+                        { "short": true, "title": "Average", "value": "3.0" },
+                        { "short": true, "title": "Range", "value": "1 – 5" },
+                        { "short": true, "title": "Suggested", "value": "3" },
+                        { "short": true, "title": "Final estimate", "value": "5" },
+                    ],
+                    "footer": "Planning poker room 1",
+                }],
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn mattermost_anonymous_round() {
+        let mut room = revealed_room("Login page");
+        for user in room.users.iter_mut() {
+            user.vote = Vote::None;
+        }
+        room.distribution = Some(vec![
+            CardCount { card: 0, count: 1 },
+            CardCount { card: 2, count: 2 },
+        ]);
+        let body = post(WebhookFormat::Mattermost, WebhookEvent::RoundRevealed, room).await;
+
+        assert_eq!(
+            body,
+            json!({
+                "text": "",
+                "attachments": [{
+                    "fallback": "Votes revealed: Login page",
+                    "title": "Votes revealed: Login page",
+                    "text": "• **1**: 1 vote\n• **5**: 2 votes",
+                    "fields": [
+                        { "short": true, "title": "Average", "value": "3.7" },
+                        { "short": true, "title": "Range", "value": "1 – 5" },
+                        { "short": true, "title": "Suggested", "value": "5" },
+                    ],
+                    "footer": "Planning poker room 1",
+                }],
+            })
+        );
+    }
+}
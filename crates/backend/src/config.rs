@@ -0,0 +1,126 @@
+use std::{env, time::Duration};
+
+use rand::Rng;
+
+use crate::{
+    limits::Limits,
+    session::SessionKey,
+    webhooks::{self, Webhook},
+};
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8082";
+/// Default lifetime of a session token
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+pub struct Config {
+    /// Address the server listens on
+    pub address: String,
+    /// Keys used for signing session tokens. The first key signs new tokens,
+    /// the rest are only accepted for verification (key rotation).
+    pub session_keys: Vec<SessionKey>,
+    pub session_ttl: Duration,
+    /// Always mark cookies as Secure, even when the request scheme is plain http.
+    /// Useful when TLS is terminated by a proxy that does not set forwarding headers.
+    pub secure_cookies: bool,
+    /// OpenID Connect login, disabled unless an issuer and a client id are configured.
+    pub oidc: Option<OidcConfig>,
+    pub limits: Limits,
+    /// Server-wide webhooks, receiving the events of every room
+    pub webhooks: Vec<Webhook>,
+    /// Internal hosts room webhooks may be sent to and issue trackers may be reached at
+    pub webhook_allowed_hosts: Vec<String>,
+}
+
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// Callback URL registered at the provider, pointing to `/auth/callback`
+    pub redirect_url: String,
+    /// Where the browser is sent after a successful login
+    pub post_login_url: String,
+}
+
+impl Config {
+    /// Reads the configuration from environment variables:
+    ///
+    /// - `PLANPOKER_ADDRESS`: address to listen on, `127.0.0.1:8082` by default.
+    /// - `PLANPOKER_SESSION_KEYS`: comma separated list of `key_id:secret` pairs.
+    ///   If not set, a random key is generated and sessions do not survive restarts.
+    /// - `PLANPOKER_SESSION_TTL`: session token lifetime in seconds.
+    /// - `PLANPOKER_SECURE_COOKIES`: set to `1` or `true` to always use Secure cookies.
+    /// - `PLANPOKER_OIDC_ISSUER`, `PLANPOKER_OIDC_CLIENT_ID`, `PLANPOKER_OIDC_CLIENT_SECRET`,
+    ///   `PLANPOKER_OIDC_REDIRECT_URL` and `PLANPOKER_OIDC_POST_LOGIN_URL`: OpenID Connect login.
+    /// - Rate limits and other abuse protection, see [`Limits::from_env`].
+    /// - Server-wide webhooks, see [`Webhook::from_env`], and the internal hosts room webhooks
+    ///   and issue trackers may use, see [`webhooks::allowed_hosts_from_env`].
+    pub fn from_env() -> Self {
+        let session_keys = env::var("PLANPOKER_SESSION_KEYS")
+            .ok()
+            .map(|keys| parse_session_keys(&keys))
+            .filter(|keys| !keys.is_empty())
+            .unwrap_or_else(|| {
+                println!("PLANPOKER_SESSION_KEYS not set, using a random session key");
+
+                let secret = rand::thread_rng().gen::<[u8; 32]>();
+                vec![SessionKey::new("default", secret.to_vec())]
+            });
+
+        let session_ttl = env::var("PLANPOKER_SESSION_TTL")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SESSION_TTL);
+
+        Self {
+            address: env::var("PLANPOKER_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_owned()),
+            session_keys,
+            session_ttl,
+            secure_cookies: env_flag("PLANPOKER_SECURE_COOKIES"),
+            oidc: OidcConfig::from_env(),
+            limits: Limits::from_env(),
+            webhooks: Webhook::from_env(),
+            webhook_allowed_hosts: webhooks::allowed_hosts_from_env(),
+        }
+    }
+}
+
+impl OidcConfig {
+    fn from_env() -> Option<Self> {
+        let issuer = env::var("PLANPOKER_OIDC_ISSUER").ok()?;
+        let client_id = env::var("PLANPOKER_OIDC_CLIENT_ID").ok()?;
+
+        Some(Self {
+            issuer: issuer.trim_end_matches('/').to_owned(),
+            client_id,
+            client_secret: env::var("PLANPOKER_OIDC_CLIENT_SECRET").ok(),
+            redirect_url: env::var("PLANPOKER_OIDC_REDIRECT_URL")
+                .unwrap_or_else(|_| "http://localhost:8082/auth/callback".to_owned()),
+            post_login_url: env::var("PLANPOKER_OIDC_POST_LOGIN_URL")
+                .unwrap_or_else(|_| "http://localhost:8080/".to_owned()),
+        })
+    }
+}
+
+fn parse_session_keys(keys: &str) -> Vec<SessionKey> {
+    keys.split(',')
+        .filter_map(|key| {
+            let mut parts = key.trim().splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(id), Some(secret)) if !id.is_empty() && !id.contains('.') => {
+                    Some(SessionKey::new(id, secret.as_bytes().to_vec()))
+                }
+                _ => {
+                    println!("Ignoring invalid session key entry");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
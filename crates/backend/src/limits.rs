@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+/// Buckets that have been idle this long are full again and can be dropped.
+const IDLE_BUCKET_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Copy, Clone)]
+pub struct RateLimit {
+    /// How many requests can be made in a burst
+    pub burst: u32,
+    /// How many requests per second are allowed on average
+    pub per_second: f64,
+}
+
+#[derive(Copy, Clone)]
+pub struct Limits {
+    pub per_connection: RateLimit,
+    pub per_ip: RateLimit,
+    /// Maximum number of rooms a single user can own, not counting closed or abandoned ones
+    pub max_rooms_per_user: usize,
+    pub max_room_members: usize,
+    /// Maximum number of rooms on the server
+    pub max_rooms: usize,
+    /// Maximum size of a single websocket frame in bytes
+    pub max_frame_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            per_connection: RateLimit {
+                burst: 20,
+                per_second: 5.0,
+            },
+            per_ip: RateLimit {
+                burst: 100,
+                per_second: 25.0,
+            },
+            max_rooms_per_user: 10,
+            max_room_members: 100,
+            max_rooms: 10_000,
+            max_frame_size: 16 * 1024,
+        }
+    }
+}
+
+impl Limits {
+    /// Reads the limits from environment variables, using defaults for unset values:
+    ///
+    /// - `PLANPOKER_RATE_LIMIT`: requests per second for a single connection
+    /// - `PLANPOKER_IP_RATE_LIMIT`: requests per second from a single IP address
+    /// - `PLANPOKER_MAX_ROOMS_PER_USER`, `PLANPOKER_MAX_ROOM_MEMBERS`, `PLANPOKER_MAX_ROOMS`
+    /// - `PLANPOKER_MAX_FRAME_SIZE`: in bytes
+    pub fn from_env() -> Self {
+        let mut limits = Self::default();
+
+        if let Some(limit) = env_rate_limit("PLANPOKER_RATE_LIMIT") {
+            limits.per_connection = limit;
+        }
+        if let Some(limit) = env_rate_limit("PLANPOKER_IP_RATE_LIMIT") {
+            limits.per_ip = limit;
+        }
+        if let Some(max) = env_value("PLANPOKER_MAX_ROOMS_PER_USER") {
+            limits.max_rooms_per_user = max;
+        }
+        if let Some(max) = env_value("PLANPOKER_MAX_ROOM_MEMBERS") {
+            limits.max_room_members = max;
+        }
+        if let Some(max) = env_value("PLANPOKER_MAX_ROOMS") {
+            limits.max_rooms = max;
+        }
+        if let Some(max) = env_value("PLANPOKER_MAX_FRAME_SIZE") {
+            limits.max_frame_size = max;
+        }
+
+        limits
+    }
+}
+
+impl RateLimit {
+    /// Rate limit with a burst of four seconds worth of requests,
+    /// `None` unless the rate is positive.
+    fn with_rate(per_second: f64) -> Option<Self> {
+        if !per_second.is_finite() || per_second <= 0.0 {
+            return None;
+        }
+
+        Some(Self {
+            burst: (per_second * 4.0).ceil().max(1.0) as u32,
+            per_second,
+        })
+    }
+}
+
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token from the bucket. Returns false if the bucket is empty.
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.limit.per_second).min(self.limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn idle_for(&self, duration: Duration) -> bool {
+        self.last_refill.elapsed() >= duration
+    }
+}
+
+/// Token buckets shared by all connections from the same IP address
+pub struct IpRateLimiter {
+    limit: RateLimit,
+    buckets: HashMap<IpAddr, TokenBucket>,
+}
+
+impl IpRateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    pub fn try_take(&mut self, ip: IpAddr) -> bool {
+        if self.buckets.len() > 1024 {
+            self.buckets
+                .retain(|_, bucket| !bucket.idle_for(IDLE_BUCKET_TIMEOUT));
+        }
+
+        let limit = self.limit;
+
+        self.buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(limit))
+            .try_take()
+    }
+}
+
+fn env_rate_limit(name: &str) -> Option<RateLimit> {
+    let per_second = env_value(name)?;
+    let limit = RateLimit::with_rate(per_second);
+
+    if limit.is_none() {
+        println!("Ignoring {}, the rate has to be positive", name);
+    }
+
+    limit
+}
+
+fn env_value<T: std::str::FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(burst: u32, per_second: f64) -> RateLimit {
+        RateLimit { burst, per_second }
+    }
+
+    /// Pretends the bucket was last refilled the duration ago.
+    fn wait(bucket: &mut TokenBucket, duration: Duration) {
+        bucket.last_refill -= duration;
+    }
+
+    fn take_all(bucket: &mut TokenBucket) -> usize {
+        (0..).take_while(|_| bucket.try_take()).count()
+    }
+
+    #[test]
+    fn bucket_allows_a_full_burst() {
+        let mut bucket = TokenBucket::new(limit(10, 1.0));
+
+        assert_eq!(take_all(&mut bucket), 10);
+        assert!(!bucket.try_take());
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let mut bucket = TokenBucket::new(limit(10, 4.0));
+        take_all(&mut bucket);
+
+        wait(&mut bucket, Duration::from_millis(500));
+        assert_eq!(take_all(&mut bucket), 2);
+
+        wait(&mut bucket, Duration::from_millis(100));
+        assert!(!bucket.try_take());
+        wait(&mut bucket, Duration::from_millis(200));
+        assert!(bucket.try_take());
+    }
+
+    #[test]
+    fn bucket_does_not_refill_beyond_the_burst() {
+        let mut bucket = TokenBucket::new(limit(10, 4.0));
+        take_all(&mut bucket);
+
+        wait(&mut bucket, Duration::from_secs(60));
+        assert_eq!(take_all(&mut bucket), 10);
+    }
+
+    #[test]
+    fn ips_have_separate_buckets() {
+        let mut limiter = IpRateLimiter::new(limit(3, 1.0));
+        let a: IpAddr = "192.0.2.1".parse().unwrap();
+        let b: IpAddr = "2001:db8::1".parse().unwrap();
+
+        assert!((0..3).all(|_| limiter.try_take(a)));
+        assert!(!limiter.try_take(a));
+
+        assert!((0..3).all(|_| limiter.try_take(b)));
+        assert!(!limiter.try_take(b));
+    }
+
+    #[test]
+    fn rate_has_to_be_positive() {
+        assert!(RateLimit::with_rate(0.0).is_none());
+        assert!(RateLimit::with_rate(-5.0).is_none());
+        assert!(RateLimit::with_rate(f64::NAN).is_none());
+        assert!(RateLimit::with_rate(f64::INFINITY).is_none());
+
+        let limit = RateLimit::with_rate(2.5).unwrap();
+        assert_eq!(limit.burst, 10);
+        assert_eq!(limit.per_second, 2.5);
+
+        assert_eq!(RateLimit::with_rate(0.1).unwrap().burst, 1);
+    }
+}
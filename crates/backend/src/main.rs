@@ -1,6 +1,20 @@
+mod api;
+mod chatops;
+mod config;
+mod limits;
+mod oidc;
+#[cfg(feature = "schema")]
+mod openapi;
+mod room;
+mod session;
+mod ticker;
+mod trackers;
+mod webhooks;
+
 use std::{
+    collections::HashMap,
+    net::IpAddr,
     ops::Deref,
-    str::FromStr,
     sync::Mutex,
     time::{Duration, Instant},
 };
@@ -15,11 +29,21 @@ use actix_web::{
     App, Error, HttpMessage, HttpRequest, HttpResponse, HttpServer,
 };
 use actix_web_actors::ws::{self, WebsocketContext};
+use config::Config;
+use limits::{IpRateLimiter, Limits, TokenBucket};
+use oidc::{Identity, PendingLogin};
 use planpoker_common::{
-    deserialize_binary, deserialize_json, serialize_json, Card, RoomId, RoomInfo, RoomMessage,
-    RoomRequest, SessionId, UserId, Vote,
+    api::{TrackerSettings, WebhookEvent},
+    deserialize_binary, deserialize_json, serialize_json, CardValue, ChatMessage, Deck, Reaction,
+    RoomError, RoomId, RoomInfo, RoomMessage, RoomRequest, RoomSettings, RoomState, SessionId,
+    Story, UserId, Vote,
 };
+use room::{sanitize_chat, Room, MAX_AUTO_REVEAL_DELAY};
+use session::{unix_now, Sessions, SESSION_COOKIE};
+use ticker::RoomTicker;
+use trackers::ImportedIssue;
 use uuid::Uuid;
+use webhooks::{Publish, RoomsRemoved, WebhookDispatcher};
 
 /// How often heartbeat pings are sent
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
@@ -27,11 +51,15 @@ const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
 const MAX_ROOM_ID: RoomId = 999999;
+/// How many random room ids are tried before giving up
+const ROOM_ID_ATTEMPTS: usize = 100;
+const MAX_STORY_TITLE_LENGTH: usize = 200;
 
 #[derive(Clone)]
 pub struct User {
     pub id: UserId,
     pub session_id: SessionId,
+    pub name: Option<String>,
 }
 struct RoomNotification(RoomMessage);
 
@@ -48,17 +76,40 @@ impl Deref for RoomNotification {
 }
 
 struct AppState {
-    clients: Vec<(UserId, Addr<WebSocket>)>,
-    rooms: Vec<RoomInfo>,
+    clients: Vec<(UserId, Recipient<RoomNotification>)>,
+    rooms: Vec<Room>,
     users: Vec<User>,
+    sessions: Sessions,
+    pending_logins: HashMap<String, PendingLogin>,
+    identities: HashMap<Identity, UserId>,
+    limits: Limits,
+    ip_limiter: IpRateLimiter,
+    webhooks: Addr<WebhookDispatcher>,
+    /// Internal hosts issue trackers may be reached at
+    allowed_hosts: Vec<String>,
 }
 
 impl AppState {
-    fn add_client(&mut self, user_id: UserId, client: Addr<WebSocket>) {
+    fn new(config: &Config, webhooks: Addr<WebhookDispatcher>) -> Self {
+        Self {
+            clients: Vec::new(),
+            rooms: Vec::new(),
+            users: Vec::new(),
+            sessions: Sessions::new(config.session_keys.clone(), config.session_ttl),
+            pending_logins: HashMap::new(),
+            identities: HashMap::new(),
+            limits: config.limits,
+            ip_limiter: IpRateLimiter::new(config.limits.per_ip),
+            webhooks,
+            allowed_hosts: config.webhook_allowed_hosts.clone(),
+        }
+    }
+
+    fn add_client(&mut self, user_id: UserId, client: Recipient<RoomNotification>) {
         self.clients.push((user_id, client));
     }
 
-    fn remove_client(&mut self, client: Addr<WebSocket>) {
+    fn remove_client(&mut self, client: Recipient<RoomNotification>) {
         let client = self
             .clients
             .iter()
@@ -70,7 +121,7 @@ impl AppState {
             // There may be multiple clients with same user id (multiple tabs open in the browser)
             // Do not remove the user from rooms in that case.
             if self.clients.iter().filter(|c| c.0 == client.0).count() == 1 {
-                let room_ids = self.rooms.iter().map(|r| r.id).collect::<Vec<_>>();
+                let room_ids = self.rooms.iter().map(|r| r.id()).collect::<Vec<_>>();
 
                 for room_id in room_ids {
                     self.remove_user_from_room(client.0, room_id);
@@ -92,120 +143,669 @@ impl AppState {
         let user = User {
             id: user_id,
             session_id,
+            name: None,
         };
 
         self.users.push(user.clone());
         user
     }
 
-    fn create_room(&mut self, owner_id: UserId) -> RoomId {
-        let id = loop {
-            let id = rand::thread_rng().gen_range(0, MAX_ROOM_ID);
+    /// Starts a new session for the user mapped to the identity,
+    /// creating the user on first login.
+    fn login_user(&mut self, identity: Identity, name: Option<String>) -> User {
+        let user_id = *self
+            .identities
+            .entry(identity)
+            .or_insert_with(|| Uuid::new_v4().as_u128());
 
-            if !self.has_room_with_id(id) {
-                break id;
-            }
+        for user in self.users.iter_mut().filter(|u| u.id == user_id) {
+            user.name = name.clone();
+        }
+
+        let user = User {
+            id: user_id,
+            session_id: Uuid::new_v4().as_u128(),
+            name,
         };
 
-        let mut cards = (1..10)
-            .map(|i| Card::valued(i.to_string(), i))
-            .collect::<Vec<_>>();
-        cards.push(Card::valueless("?"));
+        self.users.push(user.clone());
+        user
+    }
+
+    /// Starts an additional session for the user, such as for an API token.
+    fn add_session(&mut self, user: &User) -> User {
+        let user = User {
+            session_id: Uuid::new_v4().as_u128(),
+            ..user.clone()
+        };
+
+        self.users.push(user.clone());
+        user
+    }
+
+    fn create_room(&mut self, owner_id: UserId, deck: Deck) -> Result<RoomId, RoomError> {
+        // Closed and abandoned rooms stay around until the server needs the space,
+        // but do not count against the owner.
+        let owned_rooms = self
+            .rooms
+            .iter()
+            .filter(|r| r.info.admin == owner_id && r.in_use())
+            .count();
+        if owned_rooms >= self.limits.max_rooms_per_user {
+            return Err(RoomError::TooManyRooms);
+        }
+
+        if self.rooms.len() >= self.limits.max_rooms {
+            // Make space by dropping closed rooms and rooms everyone has left,
+            // but not the new ones that are yet to be joined.
+            let (rooms, removed): (Vec<Room>, Vec<Room>) =
+                self.rooms.drain(..).partition(Room::in_use);
+            self.rooms = rooms;
+            self.webhooks
+                .do_send(RoomsRemoved(removed.iter().map(Room::id).collect()));
+
+            if self.rooms.len() >= self.limits.max_rooms {
+                return Err(RoomError::ServerFull);
+            }
+        }
+
+        // The number of rooms is capped well below the id space,
+        // so a free id is practically always found on the first attempts.
+        let mut rng = rand::thread_rng();
+        let id = (0..ROOM_ID_ATTEMPTS)
+            .map(|_| rng.gen_range(0, MAX_ROOM_ID))
+            .find(|id| !self.has_room_with_id(*id))
+            .ok_or(RoomError::ServerFull)?;
 
         let mut room = RoomInfo::new(id, owner_id);
-        room.cards = cards;
+        room.cards = deck.cards();
 
-        self.rooms.push(room);
+        self.rooms.push(Room::new(room));
 
-        id
+        Ok(id)
     }
 
     fn has_room_with_id(&self, id: RoomId) -> bool {
-        self.rooms.iter().any(|r| r.id == id)
+        self.rooms.iter().any(|r| r.id() == id)
     }
 
-    fn add_user_to_room(&mut self, user_id: UserId, room_id: RoomId) {
+    fn add_user_to_room(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        let name = self
+            .users
+            .iter()
+            .find(|u| u.id == user_id)
+            .and_then(|u| u.name.clone());
+        let max_members = self.limits.max_room_members;
+
         if let Some(room) = self.room_mut(room_id) {
-            room.add_user(user_id);
+            let is_member = room.info.users.iter().any(|u| u.user_id == user_id);
+            if !is_member && room.info.users.len() >= max_members {
+                return Err(RoomError::RoomFull(room_id));
+            }
 
-            let room = room.clone();
+            room.info.add_user(user_id, name);
+
+            let room = room.client_info();
             self.send_to_room_users(&room, RoomMessage::UserJoined(user_id));
             self.send_room_info(&room);
+            self.check_auto_reveal(room_id);
         }
+
+        Ok(())
     }
 
     fn remove_user_from_room(&mut self, user_id: UserId, room_id: RoomId) {
         if let Some(room) = self.room_mut(room_id) {
-            room.users.retain(|u| u.user_id != user_id);
+            room.info.users.retain(|u| u.user_id != user_id);
 
-            let room = room.clone();
+            let room = room.client_info();
             self.send_to_room_users(&room, RoomMessage::UserLeft(user_id));
             self.send_room_info(&room);
+            self.check_auto_reveal(room_id);
         }
     }
 
-    fn vote(&mut self, user_id: UserId, room_id: RoomId, vote: u32) {
+    fn vote(&mut self, user_id: UserId, room_id: RoomId, vote: u32) -> Result<(), RoomError> {
         if let Some(room) = self.room_mut(room_id) {
-            if room.revealed {
-                // Room must be reset before voting is allowed.
-                return;
+            let state = room.info.state.vote()?;
+
+            if vote as usize >= room.info.cards.len() {
+                return Err(RoomError::NoSuchCard(vote));
             }
 
-            if let Some(user) = room.users.iter_mut().find(|u| u.user_id == user_id) {
-                let new_vote = match user.vote {
-                    Vote::Hidden(v) if v == vote => Vote::None,
-                    _ => Vote::Hidden(vote),
+            if let Some(user) = room.info.users.iter_mut().find(|u| u.user_id == user_id) {
+                let (new_vote, changed) = match user.vote {
+                    Vote::Hidden(v) if v == vote => (Vote::None, false),
+                    Vote::Hidden(_) => (Vote::Hidden(vote), true),
+                    _ => (Vote::Hidden(vote), false),
                 };
 
                 user.vote = new_vote;
+                room.info.state = state;
 
-                let room = room.clone();
+                // A changed vote restarts the delay of a pending reveal, so that everyone sees it.
+                let restart_reveal = changed && room.cancel_reveal();
+
+                let room = room.client_info();
                 self.send_to_room_users(&room, RoomMessage::UserVoted(user_id));
                 self.send_room_info(&room);
+                if restart_reveal {
+                    self.send_to_room_users(&room, RoomMessage::RevealCancelled);
+                }
+                self.check_auto_reveal(room_id);
             }
         }
+
+        Ok(())
+    }
+
+    fn reveal_votes(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        self.reveal(room_id)
     }
 
-    fn reveal_votes(&mut self, user_id: UserId, room_id: RoomId) {
+    fn reveal(&mut self, room_id: RoomId) -> Result<(), RoomError> {
         if let Some(room) = self.room_mut(room_id) {
-            if room.admin == user_id {
-                for user in room.users.iter_mut() {
-                    if let Vote::Hidden(vote) = user.vote {
-                        user.vote = Vote::Revealed(vote);
-                    }
+            room.info.state = room.info.state.reveal()?;
+
+            for user in room.info.users.iter_mut() {
+                if let Vote::Hidden(vote) = user.vote {
+                    user.vote = Vote::Revealed(vote);
                 }
             }
 
-            room.revealed = true;
+            room.stop_timer();
+            room.cancel_reveal();
 
-            let room = room.clone();
+            let room = room.client_info();
             self.send_to_room_users(&room, RoomMessage::CardsRevealed);
             self.send_room_info(&room);
+            self.publish(WebhookEvent::RoundRevealed, room);
+        }
+
+        Ok(())
+    }
+
+    fn reset_votes(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            room.info.state = room.info.state.reset()?;
+            room.clear_votes();
+
+            let room = room.client_info();
+            self.send_to_room_users(&room, RoomMessage::Reset);
+            self.send_room_info(&room);
+        }
+
+        Ok(())
+    }
+
+    fn revote(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            room.info.state = room.info.state.revote()?;
+            room.archive_round();
+            room.clear_votes();
+
+            let room = room.client_info();
+            self.send_to_room_users(&room, RoomMessage::Revote);
+            self.send_room_info(&room);
+        }
+
+        Ok(())
+    }
+
+    fn set_estimate(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        estimate: CardValue,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if !estimate.is_valid() {
+            return Err(RoomError::InvalidEstimate);
+        }
+
+        let allowed_hosts = self.allowed_hosts.clone();
+        if let Some(room) = self.room_mut(room_id) {
+            room.info.state = room.info.state.finalize()?;
+            room.set_estimate(estimate);
+
+            let issue = room.info.current_story().and_then(|s| s.issue.clone());
+            if let (Some(tracker), Some(issue)) = (room.tracker.as_ref(), issue) {
+                trackers::write_back(room_id, tracker, allowed_hosts, issue, estimate);
+            }
+
+            let room = room.client_info();
+            self.send_to_room_users(&room, RoomMessage::EstimateSet(estimate));
+            self.send_room_info(&room);
+            self.publish(WebhookEvent::EstimateFinalized, room);
+        }
+
+        Ok(())
+    }
+
+    fn send_chat(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        text: String,
+        story: Option<usize>,
+    ) -> Result<(), RoomError> {
+        let text = sanitize_chat(&text)?;
+        let name = self
+            .users
+            .iter()
+            .find(|u| u.id == user_id)
+            .and_then(|u| u.name.clone());
+
+        if let Some(room) = self.room_mut(room_id) {
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            if let Some(index) = story {
+                if index >= room.info.stories.len() {
+                    return Err(RoomError::NoSuchStory(index));
+                }
+            }
+
+            let message = ChatMessage {
+                user_id,
+                name,
+                text,
+                story,
+                sent_at: unix_now(),
+            };
+            room.push_chat(message.clone());
+
+            let room = room.client_info();
+            self.send_to_room_users(&room, RoomMessage::Chat(message));
+        }
+
+        Ok(())
+    }
+
+    fn react(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        reaction: Reaction,
+    ) -> Result<(), RoomError> {
+        if let Some(room) = self.room(room_id) {
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            let room = room.client_info();
+            self.send_to_room_users(&room, RoomMessage::Reaction { user_id, reaction });
+        }
+
+        Ok(())
+    }
+
+    /// Sends a nudge only to the users who have not voted yet, other than the admin.
+    fn nudge(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room(room_id) {
+            room.info.state.vote()?;
+
+            let pending = room
+                .info
+                .users
+                .iter()
+                .filter(|u| u.vote == Vote::None && u.user_id != user_id)
+                .map(|u| u.user_id)
+                .collect::<Vec<_>>();
+
+            for pending_user_id in pending {
+                self.send_to_user(pending_user_id, RoomMessage::Nudge);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_story(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        title: String,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        let title = title
+            .trim()
+            .chars()
+            .take(MAX_STORY_TITLE_LENGTH)
+            .collect::<String>();
+
+        if let Some(room) = self.room_mut(room_id) {
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            room.info.stories.push(Story::new(title));
+
+            if room.info.current_story.is_none() {
+                room.info.current_story = Some(room.info.stories.len() - 1);
+            }
+
+            let room = room.client_info();
+            self.send_room_info(&room);
         }
+
+        Ok(())
     }
 
-    fn reset_votes(&mut self, user_id: UserId, room_id: RoomId) {
+    /// Adds the issues as stories, skipping the ones that are already in the room.
+    fn import_stories(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        issues: Vec<ImportedIssue>,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
         if let Some(room) = self.room_mut(room_id) {
-            if room.admin == user_id {
-                for user in room.users.iter_mut() {
-                    user.vote = Vote::None;
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            for imported in issues {
+                let exists = room
+                    .info
+                    .stories
+                    .iter()
+                    .any(|s| s.issue.as_ref() == Some(&imported.issue));
+                if exists {
+                    continue;
                 }
+
+                let title = imported
+                    .title
+                    .trim()
+                    .chars()
+                    .take(MAX_STORY_TITLE_LENGTH)
+                    .collect::<String>();
+                let mut story = Story::new(title);
+                story.issue = Some(imported.issue);
+                room.info.stories.push(story);
+            }
+
+            if room.info.current_story.is_none() && !room.info.stories.is_empty() {
+                room.info.current_story = Some(0);
+            }
+
+            let room = room.client_info();
+            self.send_room_info(&room);
+        }
+
+        Ok(())
+    }
+
+    fn set_tracker(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        tracker: Option<TrackerSettings>,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(tracker) = tracker.as_ref() {
+            trackers::validate(tracker)?;
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            room.tracker = tracker;
+        }
+
+        Ok(())
+    }
+
+    fn tracker(&self, user_id: UserId, room_id: RoomId) -> Result<TrackerSettings, RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        self.room(room_id)
+            .and_then(|room| room.tracker.clone())
+            .ok_or(RoomError::NoTracker)
+    }
+
+    /// Closes the room for good. It can still be viewed, but nothing can be changed anymore.
+    fn close_room(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            room.info.state = room.info.state.close()?;
+            room.stop_timer();
+            room.cancel_reveal();
+
+            let room = room.client_info();
+            self.send_room_info(&room);
+            self.publish(WebhookEvent::RoomClosed, room);
+        }
+
+        Ok(())
+    }
+
+    fn select_story(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        index: usize,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            if index >= room.info.stories.len() {
+                return Err(RoomError::NoSuchStory(index));
+            }
+
+            let revealed = room.info.state.is_revealed();
+            room.info.state = room.info.state.reset()?;
+
+            // Keep the revealed votes in the history of the story they were cast for.
+            if revealed {
+                room.archive_round();
             }
 
-            room.revealed = false;
+            room.info.current_story = Some(index);
+            room.clear_votes();
 
-            let room = room.clone();
+            let room = room.client_info();
             self.send_to_room_users(&room, RoomMessage::Reset);
             self.send_room_info(&room);
         }
+
+        Ok(())
+    }
+
+    fn start_timer(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        seconds: u32,
+        auto_reveal: bool,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            room.start_timer(Duration::from_secs(seconds as u64), auto_reveal);
+
+            let room = room.client_info();
+            if let Some(timer) = room.timer.clone() {
+                self.send_to_room_users(&room, RoomMessage::TimerStarted(timer));
+            }
+            self.send_room_info(&room);
+        }
+
+        Ok(())
+    }
+
+    fn stop_timer(&mut self, user_id: UserId, room_id: RoomId) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        if let Some(room) = self.room_mut(room_id) {
+            if room.stop_timer() {
+                let room = room.client_info();
+                self.send_to_room_users(&room, RoomMessage::TimerStopped);
+                self.send_room_info(&room);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_settings(
+        &mut self,
+        user_id: UserId,
+        room_id: RoomId,
+        mut settings: RoomSettings,
+    ) -> Result<(), RoomError> {
+        if !self.is_room_admin(user_id, room_id) {
+            return Err(RoomError::NotAdmin);
+        }
+
+        settings.auto_reveal_delay = settings.auto_reveal_delay.min(MAX_AUTO_REVEAL_DELAY);
+
+        if let Some(room) = self.room_mut(room_id) {
+            if room.info.state == RoomState::Closed {
+                return Err(RoomError::RoomClosed);
+            }
+
+            // Switching off anonymous mode would expose the revealed votes.
+            if room.info.state.is_revealed() && room.info.settings.anonymous != settings.anonymous {
+                return Err(RoomError::AlreadyRevealed);
+            }
+
+            room.info.settings = settings;
+
+            let room = room.client_info();
+            self.send_room_info(&room);
+            self.check_auto_reveal(room_id);
+        }
+
+        Ok(())
+    }
+
+    /// Reveals the votes, or schedules them to be revealed, if automatic reveal is enabled
+    /// and everyone has voted. Cancels a pending reveal if that is no longer the case.
+    fn check_auto_reveal(&mut self, room_id: RoomId) {
+        let msg = match self.room_mut(room_id) {
+            Some(room) => {
+                let settings = &room.info.settings;
+
+                if settings.auto_reveal && room.info.state.accepts_votes() && room.info.all_voted()
+                {
+                    let delay = settings.auto_reveal_delay;
+
+                    if delay == 0 {
+                        let _ = self.reveal(room_id);
+                        None
+                    } else if room.schedule_reveal(Duration::from_secs(delay as u64)) {
+                        Some(RoomMessage::RevealPending(delay))
+                    } else {
+                        None
+                    }
+                } else if room.cancel_reveal() {
+                    Some(RoomMessage::RevealCancelled)
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+
+        if let Some(msg) = msg {
+            if let Some(room) = self.room(room_id).map(Room::client_info) {
+                self.send_to_room_users(&room, msg);
+            }
+        }
     }
 
-    fn room(&mut self, room_id: RoomId) -> Option<&RoomInfo> {
-        self.rooms.iter().find(|r| r.id == room_id)
+    /// Handles time based events of all rooms. Called periodically by [`RoomTicker`].
+    fn tick(&mut self, now: Instant) {
+        let expired = self
+            .rooms
+            .iter_mut()
+            .filter_map(|room| room.expire_timer(now).map(|reveal| (room.id(), reveal)))
+            .collect::<Vec<_>>();
+
+        for (room_id, auto_reveal) in expired {
+            if let Some(room) = self.room(room_id).map(Room::client_info) {
+                self.send_to_room_users(&room, RoomMessage::TimerExpired);
+
+                if !auto_reveal || self.reveal(room_id).is_err() {
+                    self.send_room_info(&room);
+                }
+            }
+        }
+
+        let due_reveals = self
+            .rooms
+            .iter_mut()
+            .filter_map(|room| {
+                if room.take_due_reveal(now) {
+                    Some(room.id())
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for room_id in due_reveals {
+            // The room can only have been closed since, in which case there is nothing to reveal.
+            let _ = self.reveal(room_id);
+        }
     }
 
-    fn room_mut(&mut self, room_id: RoomId) -> Option<&mut RoomInfo> {
-        self.rooms.iter_mut().find(|r| r.id == room_id)
+    fn is_room_admin(&self, user_id: UserId, room_id: RoomId) -> bool {
+        self.rooms
+            .iter()
+            .any(|r| r.id() == room_id && r.info.admin == user_id)
+    }
+
+    fn room(&self, room_id: RoomId) -> Option<&Room> {
+        self.rooms.iter().find(|r| r.id() == room_id)
+    }
+
+    fn room_mut(&mut self, room_id: RoomId) -> Option<&mut Room> {
+        self.rooms.iter_mut().find(|r| r.id() == room_id)
     }
 
     fn send_room_info(&self, room: &RoomInfo) {
@@ -220,8 +820,22 @@ impl AppState {
         }
     }
 
-    fn send_to_client(&self, client: &Addr<WebSocket>, msg: RoomMessage) {
-        client.do_send(RoomNotification(msg));
+    /// Sends the message to all clients of the user, such as multiple open tabs.
+    fn send_to_user(&self, user_id: UserId, msg: RoomMessage) {
+        for (client_user_id, client) in self.clients.iter() {
+            if *client_user_id == user_id {
+                self.send_to_client(client, msg.clone());
+            }
+        }
+    }
+
+    fn send_to_client(&self, client: &Recipient<RoomNotification>, msg: RoomMessage) {
+        // Fails only if the client has stopped, it is removed then.
+        let _ = client.do_send(RoomNotification(msg));
+    }
+
+    fn publish(&self, event: WebhookEvent, room: RoomInfo) {
+        self.webhooks.do_send(Publish { event, room });
     }
 }
 
@@ -232,6 +846,9 @@ struct WebSocket {
 
     user: User,
     room: Option<RoomId>,
+
+    ip: Option<IpAddr>,
+    rate_limit: TokenBucket,
 }
 
 impl Actor for WebSocket {
@@ -242,13 +859,13 @@ impl Actor for WebSocket {
         self.data
             .lock()
             .unwrap()
-            .add_client(self.user.id, ctx.address());
+            .add_client(self.user.id, ctx.address().recipient());
 
         self.heartbeat(ctx);
     }
 
     fn stopped(&mut self, ctx: &mut Self::Context) {
-        self.data().remove_client(ctx.address());
+        self.data().remove_client(ctx.address().recipient());
     }
 }
 
@@ -273,6 +890,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocket {
             Ok(ws::Message::Text(msg)) => {
                 println!("Text received: {:?}", msg);
 
+                if !self.accept_request(ctx, msg.len()) {
+                    return;
+                }
+
                 if let Ok(msg) = deserialize_json::<RoomRequest>(&msg) {
                     self.handle_request(ctx, msg)
                 }
@@ -280,6 +901,10 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocket {
             Ok(ws::Message::Binary(bin)) => {
                 println!("Binary received: {:?}", &bin);
 
+                if !self.accept_request(ctx, bin.len()) {
+                    return;
+                }
+
                 if let Ok(msg) = deserialize_binary::<RoomRequest>(&bin) {
                     self.handle_request(ctx, msg)
                 }
@@ -288,18 +913,25 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocket {
                 ctx.close(reason);
                 ctx.stop();
             }
+            Err(ws::ProtocolError::Overflow) => {
+                self.respond(ctx, RoomMessage::Error(RoomError::MessageTooLarge));
+                ctx.close(Some(ws::CloseCode::Size.into()));
+                ctx.stop();
+            }
             _ => ctx.stop(),
         }
     }
 }
 
 impl WebSocket {
-    fn new(user: User, data: Data<Mutex<AppState>>) -> Self {
+    fn new(user: User, ip: Option<IpAddr>, limits: &Limits, data: Data<Mutex<AppState>>) -> Self {
         Self {
             heartbeat: Instant::now(),
             user,
             data,
             room: None,
+            ip,
+            rate_limit: TokenBucket::new(limits.per_connection),
         }
     }
 
@@ -307,12 +939,38 @@ impl WebSocket {
         self.data.lock().unwrap()
     }
 
+    /// Checks the size and rate limits for an incoming request.
+    /// Responds with an error if the request should be dropped.
+    fn accept_request(&mut self, ctx: &mut <Self as Actor>::Context, size: usize) -> bool {
+        let error = {
+            let mut data = self.data.lock().unwrap();
+
+            if size > data.limits.max_frame_size {
+                Some(RoomError::MessageTooLarge)
+            } else if !self.rate_limit.try_take()
+                || !self.ip.map_or(true, |ip| data.ip_limiter.try_take(ip))
+            {
+                Some(RoomError::RateLimited)
+            } else {
+                None
+            }
+        };
+
+        match error {
+            Some(error) => {
+                self.respond(ctx, RoomMessage::Error(error));
+                false
+            }
+            None => true,
+        }
+    }
+
     fn handle_request(&mut self, ctx: &mut ws::WebsocketContext<Self>, msg: RoomRequest) {
         match msg {
             RoomRequest::UserInfo => self.respond(ctx, RoomMessage::UserInfo(self.user.id)),
             RoomRequest::RoomInfo => {
                 if let Some(room_id) = self.room {
-                    if let Some(room) = self.data().room(room_id).cloned() {
+                    if let Some(room) = self.data().room(room_id).map(Room::client_info) {
                         self.respond(ctx, RoomMessage::RoomInfo(room));
                     }
                 }
@@ -320,9 +978,72 @@ impl WebSocket {
             RoomRequest::CreateRoom => self.create_room(ctx),
             RoomRequest::JoinRoom(room_id) => self.join_room(room_id, ctx),
             RoomRequest::LeaveRoom => self.leave_room(),
-            RoomRequest::Vote(card_index) => self.vote(card_index),
-            RoomRequest::Reveal => self.reveal_votes(),
-            RoomRequest::Reset => self.reset_votes(),
+            RoomRequest::Vote(card_index) => {
+                let res = self.vote(card_index);
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::Reveal => {
+                let res = self.reveal_votes();
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::Reset => {
+                let res = self.reset_votes();
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::StartTimer {
+                seconds,
+                auto_reveal,
+            } => {
+                let res = self.in_room(|data, user_id, room_id| {
+                    data.start_timer(user_id, room_id, seconds, auto_reveal)
+                });
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::UpdateSettings(settings) => {
+                let res = self.in_room(|data, user_id, room_id| {
+                    data.update_settings(user_id, room_id, settings)
+                });
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::AddStory(title) => {
+                let res =
+                    self.in_room(|data, user_id, room_id| data.add_story(user_id, room_id, title));
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::SelectStory(index) => {
+                let res = self
+                    .in_room(|data, user_id, room_id| data.select_story(user_id, room_id, index));
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::SetEstimate(estimate) => {
+                let res = self.in_room(|data, user_id, room_id| {
+                    data.set_estimate(user_id, room_id, estimate)
+                });
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::SendChat { text, story } => {
+                let res = self.in_room(|data, user_id, room_id| {
+                    data.send_chat(user_id, room_id, text, story)
+                });
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::React(reaction) => {
+                let res =
+                    self.in_room(|data, user_id, room_id| data.react(user_id, room_id, reaction));
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::Nudge => {
+                let res = self.in_room(|data, user_id, room_id| data.nudge(user_id, room_id));
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::Revote => {
+                let res = self.in_room(|data, user_id, room_id| data.revote(user_id, room_id));
+                self.respond_error(ctx, res);
+            }
+            RoomRequest::StopTimer => {
+                let res = self.in_room(|data, user_id, room_id| data.stop_timer(user_id, room_id));
+                self.respond_error(ctx, res);
+            }
             msg => {
                 println!("unhandled req: {:?}", msg);
             }
@@ -330,18 +1051,32 @@ impl WebSocket {
     }
 
     fn create_room(&mut self, ctx: &mut <Self as Actor>::Context) {
-        let room_id = self.data().create_room(self.user.id);
+        let res = self.data().create_room(self.user.id, Deck::default());
 
-        self.respond(ctx, RoomMessage::RoomCreated(room_id));
+        match res {
+            Ok(room_id) => self.respond(ctx, RoomMessage::RoomCreated(room_id)),
+            Err(err) => self.respond(ctx, RoomMessage::Error(err)),
+        }
     }
 
     fn join_room(&mut self, room_id: RoomId, ctx: &mut <Self as Actor>::Context) {
         let room_exists = self.data().has_room_with_id(room_id);
 
         if room_exists {
-            self.room = Some(room_id);
+            let res = self.data().add_user_to_room(self.user.id.clone(), room_id);
+
+            match res {
+                Ok(()) => {
+                    self.room = Some(room_id);
 
-            self.data().add_user_to_room(self.user.id.clone(), room_id);
+                    // Only for the joining connection, the other tabs of the user already have it.
+                    let history = self.data().room(room_id).map(Room::chat_history);
+                    if let Some(history) = history {
+                        self.respond(ctx, RoomMessage::ChatHistory(history));
+                    }
+                }
+                Err(err) => self.respond(ctx, RoomMessage::Error(err)),
+            }
         } else {
             self.respond(ctx, RoomMessage::NoSuchRoom(room_id));
         }
@@ -354,21 +1089,32 @@ impl WebSocket {
         }
     }
 
-    fn vote(&mut self, vote: u32) {
-        if let Some(room_id) = self.room {
-            self.data().vote(self.user.id, room_id, vote);
-        }
+    fn vote(&mut self, vote: u32) -> Result<(), RoomError> {
+        self.in_room(|data, user_id, room_id| data.vote(user_id, room_id, vote))
+    }
+
+    fn reveal_votes(&mut self) -> Result<(), RoomError> {
+        self.in_room(|data, user_id, room_id| data.reveal_votes(user_id, room_id))
     }
 
-    fn reveal_votes(&mut self) {
-        if let Some(room_id) = self.room {
-            self.data().reveal_votes(self.user.id, room_id);
+    fn reset_votes(&mut self) -> Result<(), RoomError> {
+        self.in_room(|data, user_id, room_id| data.reset_votes(user_id, room_id))
+    }
+
+    /// Runs an action in the room the user is in.
+    fn in_room<F>(&self, action: F) -> Result<(), RoomError>
+    where
+        F: FnOnce(&mut AppState, UserId, RoomId) -> Result<(), RoomError>,
+    {
+        match self.room {
+            Some(room_id) => action(&mut self.data(), self.user.id, room_id),
+            None => Err(RoomError::NotInRoom),
         }
     }
 
-    fn reset_votes(&mut self) {
-        if let Some(room_id) = self.room {
-            self.data().reset_votes(self.user.id, room_id);
+    fn respond_error(&self, ctx: &mut <Self as Actor>::Context, res: Result<(), RoomError>) {
+        if let Err(err) = res {
+            self.respond(ctx, RoomMessage::Error(err));
         }
     }
 
@@ -392,36 +1138,99 @@ async fn ws_index(
     req: HttpRequest,
     stream: web::Payload,
     data: Data<Mutex<AppState>>,
+    config: Data<Config>,
 ) -> Result<HttpResponse, Error> {
     let mut res = ws::handshake(&req)?;
 
-    let (user, is_new) = req
-        .cookie("session_id")
-        .and_then(|cookie| Uuid::from_str(cookie.value()).ok())
-        .and_then(|uuid| data.lock().unwrap().user_with_session_id(uuid.as_u128()))
-        .map_or_else(
-            /* no such user. create new */
-            || (data.lock().unwrap().create_user(), true),
-            /* existing user */
-            |u| (u, false),
-        );
+    let token = req.cookie(SESSION_COOKIE).map(|c| c.value().to_owned());
 
-    if is_new {
-        println!(
-            "User created with user id {} and session id {}",
-            &user.id, &user.session_id
-        );
+    let mut state = data.lock().unwrap();
 
-        res.cookie(
-            Cookie::build("session_id", &Uuid::from_u128(user.session_id).to_string())
-                .http_only(true)
-                .secure(false)
-                .same_site(SameSite::Strict)
-                .finish(),
-        );
+    let user = token
+        .as_deref()
+        .and_then(|token| match state.sessions.verify(token) {
+            Ok(token) => Some(token),
+            Err(err) => {
+                println!("Rejected session token: {:?}", err);
+                None
+            }
+        })
+        .and_then(|token| state.user_with_session_id(token.session_id));
+
+    let (user, refresh_token) = match user {
+        Some(user) => {
+            let refresh = token
+                .as_deref()
+                .map_or(true, |token| state.sessions.needs_refresh(token));
+
+            (user, refresh)
+        }
+        None => {
+            let user = state.create_user();
+
+            println!(
+                "User created with user id {} and session id {}",
+                &user.id, &user.session_id
+            );
+
+            (user, true)
+        }
+    };
+
+    if refresh_token {
+        let (token, _) = state.sessions.issue(user.session_id);
+        res.cookie(session_cookie(&req, &config, token, state.sessions.ttl()));
+    }
+
+    let limits = state.limits;
+    drop(state);
+
+    let ip = req.peer_addr().map(|addr| addr.ip());
+    let codec = actix_http::ws::Codec::new().max_size(limits.max_frame_size);
+    let ws = WebSocket::new(user, ip, &limits, data);
+
+    Ok(res.streaming(WebsocketContext::with_codec(ws, stream, codec)))
+}
+
+/// Revokes the current session and clears the session cookie.
+async fn logout(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    config: Data<Config>,
+) -> HttpResponse {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        let mut state = data.lock().unwrap();
+
+        if let Ok(token) = state.sessions.verify(cookie.value()) {
+            state.sessions.revoke(token.session_id);
+        }
     }
 
-    Ok(res.streaming(WebsocketContext::create(WebSocket::new(user, data), stream)))
+    HttpResponse::Ok()
+        .cookie(session_cookie(
+            &req,
+            &config,
+            String::new(),
+            Duration::from_secs(0),
+        ))
+        .finish()
+}
+
+fn session_cookie(
+    req: &HttpRequest,
+    config: &Config,
+    token: String,
+    max_age: Duration,
+) -> Cookie<'static> {
+    let secure = config.secure_cookies || req.connection_info().scheme() == "https";
+
+    Cookie::build(SESSION_COOKIE, token)
+        .path("/")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Strict)
+        .max_age(time::Duration::seconds(max_age.as_secs() as i64))
+        .finish()
 }
 
 #[actix_web::main]
@@ -429,19 +1238,513 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
     env_logger::init();
 
-    let state = Data::new(Mutex::new(AppState {
-        clients: Vec::new(),
-        rooms: Vec::new(),
-        users: Vec::new(),
-    }));
+    let config = Data::new(Config::from_env());
+    let address = config.address.clone();
+    let webhooks = WebhookDispatcher::new(
+        config.webhooks.clone(),
+        config.webhook_allowed_hosts.clone(),
+    )
+    .start();
+    let state = Data::new(Mutex::new(AppState::new(&config, webhooks.clone())));
+
+    RoomTicker::new(state.clone()).start();
 
     HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            .app_data(config.clone())
+            .data(webhooks.clone())
             .wrap(middleware::Logger::default())
             .route("/ws/", web::get().to(ws_index))
+            .route("/logout", web::post().to(logout))
+            .route("/auth/login", web::get().to(oidc::login))
+            .route("/auth/callback", web::get().to(oidc::callback))
+            .configure(api::configure)
     })
-    .bind("127.0.0.1:8082")?
+    .bind(address)?
     .run()
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const ADMIN: UserId = 1;
+    const USERS: [UserId; 3] = [ADMIN, 2, 3];
+
+    /// What happens to the room next, requests as they arrive over the websocket
+    #[derive(Clone, Debug)]
+    enum Step {
+        Request(UserId, RoomRequest),
+        /// Closing the room through the REST API
+        Close(UserId),
+        Tick,
+    }
+
+    fn request(cards: u32) -> impl Strategy<Value = RoomRequest> {
+        prop_oneof![
+            Just(RoomRequest::JoinRoom(0)),
+            Just(RoomRequest::LeaveRoom),
+            // Including cards beyond the deck
+            (0..cards + 3).prop_map(RoomRequest::Vote),
+            (0..cards + 3).prop_map(RoomRequest::Vote),
+            (0..cards + 3).prop_map(RoomRequest::Vote),
+            Just(RoomRequest::Reveal),
+            Just(RoomRequest::Reset),
+            Just(RoomRequest::Revote),
+            (0..21u32).prop_map(|value| RoomRequest::SetEstimate(value.into())),
+            Just(RoomRequest::AddStory("Story".to_owned())),
+            (0..3usize).prop_map(RoomRequest::SelectStory),
+            (1..60u32, any::<bool>()).prop_map(|(seconds, auto_reveal)| {
+                RoomRequest::StartTimer {
+                    seconds,
+                    auto_reveal,
+                }
+            }),
+            Just(RoomRequest::StopTimer),
+            (
+                any::<bool>(),
+                prop_oneof![Just(0u32), Just(5)],
+                any::<bool>()
+            )
+                .prop_map(|(auto_reveal, auto_reveal_delay, anonymous)| {
+                    RoomRequest::UpdateSettings(RoomSettings {
+                        auto_reveal,
+                        auto_reveal_delay,
+                        anonymous,
+                        ..RoomSettings::default()
+                    })
+                }),
+        ]
+    }
+
+    fn steps() -> impl Strategy<Value = (Deck, Vec<Step>)> {
+        proptest::sample::select(Deck::ALL.to_vec()).prop_flat_map(|deck| {
+            let cards = deck.cards().len() as u32;
+            let step = prop_oneof![
+                20 => (proptest::sample::select(USERS.to_vec()), request(cards))
+                    .prop_map(|(user, request)| Step::Request(user, request)),
+                1 => proptest::sample::select(USERS.to_vec()).prop_map(Step::Close),
+                1 => Just(Step::Tick),
+            ];
+
+            (Just(deck), proptest::collection::vec(step, 1..60))
+        })
+    }
+
+    /// Handles the request like [`WebSocket::handle_request`] does for a user in the room.
+    fn handle(
+        state: &mut AppState,
+        room_id: RoomId,
+        members: &mut Vec<UserId>,
+        user_id: UserId,
+        request: RoomRequest,
+    ) -> Result<(), RoomError> {
+        match request {
+            RoomRequest::JoinRoom(_) => {
+                state.add_user_to_room(user_id, room_id)?;
+                if !members.contains(&user_id) {
+                    members.push(user_id);
+                }
+                return Ok(());
+            }
+            _ if !members.contains(&user_id) => return Err(RoomError::NotInRoom),
+            _ => {}
+        }
+
+        match request {
+            RoomRequest::LeaveRoom => {
+                members.retain(|&u| u != user_id);
+                state.remove_user_from_room(user_id, room_id);
+                Ok(())
+            }
+            RoomRequest::Vote(card) => state.vote(user_id, room_id, card),
+            RoomRequest::Reveal => state.reveal_votes(user_id, room_id),
+            RoomRequest::Reset => state.reset_votes(user_id, room_id),
+            RoomRequest::Revote => state.revote(user_id, room_id),
+            RoomRequest::SetEstimate(value) => state.set_estimate(user_id, room_id, value),
+            RoomRequest::AddStory(title) => state.add_story(user_id, room_id, title),
+            RoomRequest::SelectStory(index) => state.select_story(user_id, room_id, index),
+            RoomRequest::StartTimer {
+                seconds,
+                auto_reveal,
+            } => state.start_timer(user_id, room_id, seconds, auto_reveal),
+            RoomRequest::StopTimer => state.stop_timer(user_id, room_id),
+            RoomRequest::UpdateSettings(settings) => {
+                state.update_settings(user_id, room_id, settings)
+            }
+            request => unreachable!("not generated: {:?}", request),
+        }
+    }
+
+    fn check_invariants(room: &RoomInfo) {
+        for user in room.users.iter() {
+            match user.vote {
+                Vote::None | Vote::Unknown => {}
+                Vote::Hidden(card) | Vote::Revealed(card) => {
+                    assert!((card as usize) < room.cards.len(), "{:?}", user.vote);
+                }
+            }
+
+            match (user.vote, room.state) {
+                (Vote::None, _) => {}
+                (Vote::Hidden(_), RoomState::Voting | RoomState::Closed) => {}
+                (Vote::Revealed(_), state) if state.is_revealed() => {}
+                (Vote::Revealed(_), RoomState::Closed) => {}
+                (vote, state) => panic!("{:?} in state {:?}", vote, state),
+            }
+        }
+    }
+
+    fn config() -> Config {
+        Config {
+            address: "127.0.0.1:0".to_owned(),
+            session_keys: vec![session::SessionKey::new("test", b"secret".to_vec())],
+            session_ttl: Duration::from_secs(3600),
+            secure_cookies: false,
+            oidc: None,
+            limits: Limits::default(),
+            webhooks: Vec::new(),
+            webhook_allowed_hosts: Vec::new(),
+        }
+    }
+
+    fn app_state(config: &Config) -> AppState {
+        AppState::new(
+            config,
+            WebhookDispatcher::new(Vec::new(), Vec::new()).start(),
+        )
+    }
+
+    type Inbox = Arc<Mutex<Vec<RoomMessage>>>;
+
+    /// Websocket client stand-in, collecting the messages sent to it
+    struct Client(Inbox);
+
+    impl Actor for Client {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<RoomNotification> for Client {
+        type Result = ();
+
+        fn handle(&mut self, msg: RoomNotification, _: &mut Self::Context) {
+            self.0.lock().unwrap().push(msg.0);
+        }
+    }
+
+    /// Connects a client of the user and joins the room with it.
+    fn join(state: &mut AppState, user_id: UserId, room_id: RoomId) -> Inbox {
+        let inbox = Inbox::default();
+        state.add_client(user_id, Client(inbox.clone()).start().recipient());
+        state.add_user_to_room(user_id, room_id).unwrap();
+        inbox
+    }
+
+    /// Takes the messages the client has received so far.
+    async fn received(inbox: &Inbox) -> Vec<RoomMessage> {
+        actix_rt::time::delay_for(Duration::from_millis(20)).await;
+        std::mem::take(&mut *inbox.lock().unwrap())
+    }
+
+    fn room_info(messages: &[RoomMessage]) -> &RoomInfo {
+        messages
+            .iter()
+            .rev()
+            .find_map(|msg| match msg {
+                RoomMessage::RoomInfo(room) => Some(room),
+                _ => None,
+            })
+            .expect("no room info received")
+    }
+
+    /// Names of the messages other than room info, which is sent along with most of them
+    fn events(messages: &[RoomMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .filter(|msg| !matches!(msg, RoomMessage::RoomInfo(_)))
+            .map(|msg| format!("{:?}", msg))
+            .collect()
+    }
+
+    /// Room of the admin with a second member, and the inbox of the member
+    async fn room_with_member(state: &mut AppState) -> (RoomId, Inbox) {
+        let room_id = state.create_room(ADMIN, Deck::default()).unwrap();
+        join(state, ADMIN, room_id);
+        let member = join(state, 2, room_id);
+        received(&member).await;
+
+        (room_id, member)
+    }
+
+    #[actix_rt::test]
+    async fn expired_timer_stops_without_revealing() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        state.vote(2, room_id, 1).unwrap();
+        state.start_timer(ADMIN, room_id, 60, false).unwrap();
+        received(&member).await;
+
+        state.tick(Instant::now() + Duration::from_secs(59));
+        assert!(received(&member).await.is_empty());
+
+        state.tick(Instant::now() + Duration::from_secs(61));
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["TimerExpired"]);
+        let room = room_info(&messages);
+        assert!(room.timer.is_none());
+        assert_eq!(room.state, RoomState::Voting);
+        assert_eq!(room.users[1].vote, Vote::Hidden(1));
+
+        // The timer only expires once
+        state.tick(Instant::now() + Duration::from_secs(120));
+        assert!(received(&member).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn expired_timer_reveals_the_votes() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        state.vote(2, room_id, 1).unwrap();
+        state.start_timer(ADMIN, room_id, 60, true).unwrap();
+        received(&member).await;
+
+        state.tick(Instant::now() + Duration::from_secs(61));
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["TimerExpired", "CardsRevealed"]);
+        let room = room_info(&messages);
+        assert!(room.timer.is_none());
+        assert_eq!(room.state, RoomState::Revealed);
+        assert_eq!(room.users[1].vote, Vote::Revealed(1));
+    }
+
+    #[actix_rt::test]
+    async fn late_joiner_sees_the_remaining_time() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        state.start_timer(ADMIN, room_id, 60, true).unwrap();
+
+        let messages = received(&member).await;
+        match messages.first() {
+            Some(RoomMessage::TimerStarted(timer)) => {
+                assert_eq!(timer.duration, 60);
+                assert!(timer.remaining_ms > 59_000);
+            }
+            msg => panic!("{:?}", msg),
+        }
+
+        actix_rt::time::delay_for(Duration::from_millis(300)).await;
+        let late = join(&mut state, 3, room_id);
+
+        let messages = received(&late).await;
+        let timer = room_info(&messages).timer.clone().unwrap();
+        assert_eq!(timer.duration, 60);
+        assert!(timer.auto_reveal);
+        assert!(timer.remaining_ms <= 59_700, "{}", timer.remaining_ms);
+        assert!(timer.remaining_ms > 50_000, "{}", timer.remaining_ms);
+    }
+
+    fn auto_reveal(state: &mut AppState, room_id: RoomId, delay: u32) {
+        let settings = RoomSettings {
+            auto_reveal: true,
+            auto_reveal_delay: delay,
+            ..RoomSettings::default()
+        };
+        state.update_settings(ADMIN, room_id, settings).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn votes_are_revealed_once_everyone_has_voted() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        auto_reveal(&mut state, room_id, 0);
+
+        state.vote(2, room_id, 1).unwrap();
+        assert_eq!(events(&received(&member).await), ["UserVoted(2)"]);
+
+        state.vote(ADMIN, room_id, 2).unwrap();
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["UserVoted(1)", "CardsRevealed"]);
+        assert_eq!(room_info(&messages).state, RoomState::Revealed);
+    }
+
+    #[actix_rt::test]
+    async fn delayed_reveal_is_scheduled() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        auto_reveal(&mut state, room_id, 5);
+
+        state.vote(2, room_id, 1).unwrap();
+        state.vote(ADMIN, room_id, 2).unwrap();
+        let messages = received(&member).await;
+        assert_eq!(
+            events(&messages),
+            ["UserVoted(2)", "UserVoted(1)", "RevealPending(5)"]
+        );
+        assert_eq!(room_info(&messages).state, RoomState::Voting);
+
+        state.tick(Instant::now() + Duration::from_secs(4));
+        assert!(received(&member).await.is_empty());
+
+        state.tick(Instant::now() + Duration::from_secs(6));
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["CardsRevealed"]);
+        assert_eq!(room_info(&messages).users[1].vote, Vote::Revealed(1));
+    }
+
+    #[actix_rt::test]
+    async fn pending_reveal_is_cancelled_when_a_vote_is_withdrawn() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        auto_reveal(&mut state, room_id, 5);
+        state.vote(2, room_id, 1).unwrap();
+        state.vote(ADMIN, room_id, 2).unwrap();
+        received(&member).await;
+
+        // Voting for the same card again withdraws the vote.
+        state.vote(2, room_id, 1).unwrap();
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["UserVoted(2)", "RevealCancelled"]);
+
+        state.tick(Instant::now() + Duration::from_secs(6));
+        assert!(received(&member).await.is_empty());
+        let room = &state.room(room_id).unwrap().info;
+        assert_eq!(room.state, RoomState::Voting);
+        assert_eq!(room.users[1].vote, Vote::None);
+    }
+
+    #[actix_rt::test]
+    async fn pending_reveal_restarts_when_a_vote_is_changed() {
+        let mut state = app_state(&config());
+        let (room_id, member) = room_with_member(&mut state).await;
+        auto_reveal(&mut state, room_id, 5);
+        state.vote(2, room_id, 1).unwrap();
+        state.vote(ADMIN, room_id, 2).unwrap();
+        received(&member).await;
+
+        actix_rt::time::delay_for(Duration::from_millis(500)).await;
+        state.vote(2, room_id, 3).unwrap();
+        let messages = received(&member).await;
+        assert_eq!(
+            events(&messages),
+            ["UserVoted(2)", "RevealCancelled", "RevealPending(5)"]
+        );
+
+        // The delay of the first reveal has run out, but not the one of the restarted reveal.
+        state.tick(Instant::now() + Duration::from_millis(4_800));
+        assert!(received(&member).await.is_empty());
+
+        state.tick(Instant::now() + Duration::from_secs(6));
+        let messages = received(&member).await;
+        assert_eq!(events(&messages), ["CardsRevealed"]);
+        assert_eq!(room_info(&messages).users[1].vote, Vote::Revealed(3));
+    }
+
+    #[actix_rt::test]
+    async fn nudge_reaches_only_the_users_yet_to_vote() {
+        let mut state = app_state(&config());
+        let (room_id, voted) = room_with_member(&mut state).await;
+        let admin = Inbox::default();
+        state.add_client(ADMIN, Client(admin.clone()).start().recipient());
+        let pending = join(&mut state, 3, room_id);
+        // A second tab of the same user
+        let pending_tab = Inbox::default();
+        state.add_client(3, Client(pending_tab.clone()).start().recipient());
+        state.vote(2, room_id, 1).unwrap();
+        for inbox in [&admin, &voted, &pending, &pending_tab].iter() {
+            received(inbox).await;
+        }
+
+        assert_eq!(state.nudge(2, room_id), Err(RoomError::NotAdmin));
+        assert_eq!(state.nudge(3, room_id), Err(RoomError::NotAdmin));
+        assert!(received(&pending).await.is_empty());
+
+        state.nudge(ADMIN, room_id).unwrap();
+        assert_eq!(events(&received(&pending).await), ["Nudge"]);
+        assert_eq!(events(&received(&pending_tab).await), ["Nudge"]);
+        assert!(received(&voted).await.is_empty());
+        assert!(received(&admin).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn full_server_evicts_only_rooms_not_in_use() {
+        let mut config = config();
+        config.limits.max_rooms = 2;
+        let mut state = app_state(&config);
+
+        let unjoined = state.create_room(ADMIN, Deck::default()).unwrap();
+        let closed = state.create_room(2, Deck::default()).unwrap();
+        state.close_room(2, closed).unwrap();
+
+        let new = state.create_room(3, Deck::default()).unwrap();
+        assert!(state.room(unjoined).is_some());
+        assert!(state.room(closed).is_none());
+        assert!(state.room(new).is_some());
+
+        // Nobody has joined the rooms yet, but they were only just created.
+        assert_eq!(
+            state.create_room(3, Deck::default()),
+            Err(RoomError::ServerFull)
+        );
+        assert!(state.room(unjoined).is_some());
+        assert!(state.room(new).is_some());
+    }
+
+    proptest! {
+        #[test]
+        fn random_requests_keep_the_room_consistent((deck, steps) in steps()) {
+            System::new("test").block_on(async move {
+                let mut state = app_state(&config());
+                let room_id = state.create_room(ADMIN, deck).unwrap();
+                let mut members = Vec::new();
+
+                for step in steps {
+                    let before = state.room(room_id).unwrap().info.clone();
+
+                    match step {
+                        Step::Request(user_id, request) => {
+                            let vote = match request {
+                                RoomRequest::Vote(card) if members.contains(&user_id) => Some(card),
+                                _ => None,
+                            };
+                            let res = handle(&mut state, room_id, &mut members, user_id, request);
+
+                            if let Some(card) = vote {
+                                let allowed = before.state.accepts_votes()
+                                    && (card as usize) < before.cards.len();
+                                assert_eq!(res.is_ok(), allowed, "{:?} in {:?}", res, before.state);
+                            }
+                        }
+                        Step::Close(user_id) => {
+                            let _ = state.close_room(user_id, room_id);
+                        }
+                        Step::Tick => state.tick(Instant::now()),
+                    }
+
+                    let room = &state.room(room_id).unwrap().info;
+                    check_invariants(room);
+
+                    if before.state == RoomState::Closed {
+                        assert_eq!(room.state, RoomState::Closed);
+                    }
+
+                    // No votes are cast while the votes are revealed or the room is closed.
+                    if !before.state.accepts_votes() {
+                        for user in room.users.iter() {
+                            let previous = before.users.iter().find(|u| u.user_id == user.user_id);
+
+                            if let (Vote::Hidden(_), Some(previous)) = (user.vote, previous) {
+                                assert_eq!(user.vote, previous.vote, "in state {:?}", before.state);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    }
+}
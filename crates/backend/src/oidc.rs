@@ -0,0 +1,588 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    client::Client,
+    cookie::{Cookie, SameSite},
+    error::{ErrorBadGateway, ErrorBadRequest, ErrorNotFound},
+    http::header,
+    web::{self, Data},
+    Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{Config, OidcConfig},
+    session::{unix_now, SESSION_COOKIE},
+    session_cookie, AppState,
+};
+
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "bin/mock-oidc.rs"]
+mod mock_oidc;
+
+/// How long the user has to complete the login at the provider
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Name of the cookie binding a pending login to the browser that started it
+const LOGIN_COOKIE: &str = "login_state";
+
+/// A login that has been started but not yet completed.
+pub struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    started: Instant,
+}
+
+/// Identity of a user logged in through the OpenID Connect provider
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+}
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: u64,
+    nonce: Option<String>,
+    name: Option<String>,
+    preferred_username: Option<String>,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    fn contains(&self, client_id: &str) -> bool {
+        match self {
+            Audience::Single(aud) => aud == client_id,
+            Audience::Multiple(auds) => auds.iter().any(|aud| aud == client_id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Starts the authorization code flow with PKCE by redirecting to the provider.
+pub async fn login(
+    req: HttpRequest,
+    data: Data<Mutex<AppState>>,
+    config: Data<Config>,
+) -> Result<HttpResponse, Error> {
+    let oidc = config
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ErrorNotFound("login disabled"))?;
+    let metadata = discover(oidc).await?;
+
+    let state = random_string();
+    let nonce = random_string();
+    let code_verifier = random_string();
+    let code_challenge = base64::encode_config(
+        Sha256::digest(code_verifier.as_bytes()),
+        base64::URL_SAFE_NO_PAD,
+    );
+
+    let url = format!(
+        "{}?{}",
+        metadata.authorization_endpoint,
+        serde_urlencoded::to_string(&[
+            ("response_type", "code"),
+            ("scope", "openid profile email"),
+            ("client_id", &oidc.client_id),
+            ("redirect_uri", &oidc.redirect_url),
+            ("state", &state),
+            ("nonce", &nonce),
+            ("code_challenge", &code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(ErrorBadRequest)?
+    );
+
+    let mut app_state = data.lock().unwrap();
+    let cookie = login_cookie(
+        &req,
+        &config,
+        app_state.sessions.sign_value(&login_cookie_value(&state)),
+        LOGIN_TIMEOUT,
+    );
+
+    app_state
+        .pending_logins
+        .retain(|_, login| login.started.elapsed() < LOGIN_TIMEOUT);
+    app_state.pending_logins.insert(
+        state,
+        PendingLogin {
+            code_verifier,
+            nonce,
+            started: Instant::now(),
+        },
+    );
+
+    Ok(HttpResponse::Found()
+        .header(header::LOCATION, url)
+        .cookie(cookie)
+        .finish())
+}
+
+/// Completes the login, maps the identity to a user and starts a session for it.
+pub async fn callback(
+    req: HttpRequest,
+    query: web::Query<CallbackQuery>,
+    data: Data<Mutex<AppState>>,
+    config: Data<Config>,
+) -> Result<HttpResponse, Error> {
+    let oidc = config
+        .oidc
+        .as_ref()
+        .ok_or_else(|| ErrorNotFound("login disabled"))?;
+
+    if let Some(error) = query.error.as_ref() {
+        return Err(ErrorBadRequest(format!("login failed: {}", error)));
+    }
+
+    let (code, state) = match (query.code.as_ref(), query.state.as_ref()) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(ErrorBadRequest("missing code or state")),
+    };
+
+    let pending = {
+        let mut app_state = data.lock().unwrap();
+
+        // Without this, an attacker could have the browser complete a login started by
+        // the attacker, logging the victim in as the attacker (login CSRF).
+        let started_here = req
+            .cookie(LOGIN_COOKIE)
+            .and_then(|cookie| app_state.sessions.verify_value(cookie.value()).ok())
+            == Some(login_cookie_value(state));
+        if !started_here {
+            return Err(ErrorBadRequest("login was not started by this browser"));
+        }
+
+        app_state
+            .pending_logins
+            .remove(state)
+            .filter(|login| login.started.elapsed() < LOGIN_TIMEOUT)
+            .ok_or_else(|| ErrorBadRequest("unknown or expired login"))?
+    };
+
+    let metadata = discover(oidc).await?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", oidc.redirect_url.as_str()),
+        ("client_id", oidc.client_id.as_str()),
+        ("code_verifier", pending.code_verifier.as_str()),
+    ];
+    if let Some(secret) = oidc.client_secret.as_ref() {
+        form.push(("client_secret", secret.as_str()));
+    }
+
+    let token: TokenResponse = Client::default()
+        .post(&metadata.token_endpoint)
+        .send_form(&form)
+        .await
+        .map_err(ErrorBadGateway)?
+        .json()
+        .await
+        .map_err(ErrorBadGateway)?;
+
+    // The token is received directly from the token endpoint, so the provider
+    // has already been authenticated and the claims only need to be checked.
+    let claims =
+        decode_claims(&token.id_token).ok_or_else(|| ErrorBadGateway("invalid id token"))?;
+
+    if claims.iss.trim_end_matches('/') != oidc.issuer
+        || !claims.aud.contains(&oidc.client_id)
+        || claims.exp <= unix_now()
+        || claims.nonce.as_deref() != Some(pending.nonce.as_str())
+    {
+        return Err(ErrorBadGateway("id token validation failed"));
+    }
+
+    let identity = Identity {
+        issuer: oidc.issuer.clone(),
+        subject: claims.sub.clone(),
+    };
+    let name = claims.name.or(claims.preferred_username).or(claims.email);
+
+    let mut app_state = data.lock().unwrap();
+
+    // The anonymous session is replaced by the identity's session.
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        if let Ok(token) = app_state.sessions.verify(cookie.value()) {
+            app_state.sessions.revoke(token.session_id);
+        }
+    }
+
+    let user = app_state.login_user(identity, name);
+    let (token, _) = app_state.sessions.issue(user.session_id);
+    let cookie = session_cookie(&req, &config, token, app_state.sessions.ttl());
+
+    println!(
+        "User {} logged in with session id {}",
+        user.id, user.session_id
+    );
+
+    Ok(HttpResponse::Found()
+        .header(header::LOCATION, oidc.post_login_url.as_str())
+        .cookie(cookie)
+        .cookie(login_cookie(
+            &req,
+            &config,
+            String::new(),
+            Duration::from_secs(0),
+        ))
+        .finish())
+}
+
+fn login_cookie_value(state: &str) -> String {
+    format!("login:{}", state)
+}
+
+fn login_cookie(
+    req: &HttpRequest,
+    config: &Config,
+    value: String,
+    max_age: Duration,
+) -> Cookie<'static> {
+    let secure = config.secure_cookies || req.connection_info().scheme() == "https";
+
+    // Lax, since the browser comes back from the provider with a cross-site redirect.
+    Cookie::build(LOGIN_COOKIE, value)
+        .path("/auth")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(max_age.as_secs() as i64))
+        .finish()
+}
+
+async fn discover(oidc: &OidcConfig) -> Result<ProviderMetadata, Error> {
+    Client::default()
+        .get(format!("{}/.well-known/openid-configuration", oidc.issuer))
+        .send()
+        .await
+        .map_err(ErrorBadGateway)?
+        .json()
+        .await
+        .map_err(ErrorBadGateway)
+}
+
+fn decode_claims(id_token: &str) -> Option<IdTokenClaims> {
+    let payload = id_token.split('.').nth(1)?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+
+    serde_json::from_slice(&payload).ok()
+}
+
+fn random_string() -> String {
+    let bytes = rand::thread_rng().gen::<[u8; 32]>();
+
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use actix::Actor;
+    use actix_web::{
+        dev::{Service, ServiceResponse},
+        http::StatusCode,
+        test::{self, TestRequest},
+        App, HttpServer,
+    };
+
+    use super::*;
+    use crate::{
+        limits::Limits,
+        session::{SessionKey, Sessions},
+        webhooks::WebhookDispatcher,
+    };
+
+    const CLIENT_ID: &str = "planpoker";
+    const POST_LOGIN_URL: &str = "http://localhost:8080/";
+
+    /// Starts the mock provider on a free port, returning its issuer.
+    fn start_provider() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let provider = Data::new(mock_oidc::Provider::new(issuer.clone(), "alice".to_owned()));
+
+        HttpServer::new(move || {
+            App::new()
+                .app_data(provider.clone())
+                .configure(mock_oidc::configure)
+        })
+        .listen(listener)
+        .unwrap()
+        .run();
+
+        issuer
+    }
+
+    fn config(issuer: String) -> Config {
+        Config {
+            address: "127.0.0.1:0".to_owned(),
+            session_keys: vec![SessionKey::new("test", b"secret".to_vec())],
+            session_ttl: Duration::from_secs(3600),
+            secure_cookies: false,
+            oidc: Some(OidcConfig {
+                issuer,
+                client_id: CLIENT_ID.to_owned(),
+                client_secret: None,
+                redirect_url: "http://localhost:8082/auth/callback".to_owned(),
+                post_login_url: POST_LOGIN_URL.to_owned(),
+            }),
+            limits: Limits::default(),
+            webhooks: Vec::new(),
+            webhook_allowed_hosts: Vec::new(),
+        }
+    }
+
+    struct Login {
+        state: String,
+        cookie: Cookie<'static>,
+        /// Query of the redirect back from the provider
+        callback_query: String,
+    }
+
+    /// Starts a login and has the provider approve it, without completing it at the callback.
+    async fn start_login<S>(app: &mut S) -> Login
+    where
+        S: Service<Request = actix_http::Request, Response = ServiceResponse, Error = Error>,
+    {
+        let res = test::call_service(app, TestRequest::get().uri("/auth/login").to_request()).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+
+        let cookie = res
+            .response()
+            .cookies()
+            .find(|c| c.name() == LOGIN_COOKIE)
+            .expect("login cookie")
+            .into_owned();
+        let authorize_url = location(&res);
+
+        let res = Client::default().get(&authorize_url).send().await.unwrap();
+        assert_eq!(res.status(), StatusCode::FOUND);
+
+        let redirect = res
+            .headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let callback_query = redirect.split_once('?').unwrap().1.to_owned();
+        let state = serde_urlencoded::from_str::<Vec<(String, String)>>(&callback_query)
+            .unwrap()
+            .into_iter()
+            .find(|(name, _)| name == "state")
+            .map(|(_, state)| state)
+            .unwrap();
+
+        Login {
+            state,
+            cookie,
+            callback_query,
+        }
+    }
+
+    fn location(res: &ServiceResponse) -> String {
+        res.headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    macro_rules! init_app {
+        ($data:expr, $config:expr) => {
+            test::init_service(
+                App::new()
+                    .app_data($data.clone())
+                    .app_data($config.clone())
+                    .route("/auth/login", web::get().to(login))
+                    .route("/auth/callback", web::get().to(callback)),
+            )
+            .await
+        };
+    }
+
+    fn app_data(config: &Config) -> Data<Mutex<AppState>> {
+        let webhooks = WebhookDispatcher::new(Vec::new(), Vec::new()).start();
+
+        Data::new(Mutex::new(AppState::new(config, webhooks)))
+    }
+
+    #[actix_rt::test]
+    async fn login_round_trip() {
+        let config = Data::new(config(start_provider()));
+        let data = app_data(&config);
+        let mut app = init_app!(data, config);
+
+        let login = start_login(&mut app).await;
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", login.callback_query))
+            .cookie(login.cookie)
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(location(&res), POST_LOGIN_URL);
+
+        let session = res
+            .response()
+            .cookies()
+            .find(|c| c.name() == SESSION_COOKIE)
+            .expect("session cookie")
+            .into_owned();
+        let cleared = res
+            .response()
+            .cookies()
+            .find(|c| c.name() == LOGIN_COOKIE)
+            .expect("cleared login cookie")
+            .into_owned();
+        assert_eq!(cleared.value(), "");
+
+        let state = data.lock().unwrap();
+        let token = state.sessions.verify(session.value()).unwrap();
+        let user = state
+            .users
+            .iter()
+            .find(|u| u.session_id == token.session_id)
+            .unwrap();
+        assert_eq!(user.name.as_deref(), Some("Alice"));
+        assert!(state.pending_logins.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn callback_with_other_state_is_rejected() {
+        let config = Data::new(config(start_provider()));
+        let data = app_data(&config);
+        let mut app = init_app!(data, config);
+
+        // The attacker's login, completed in the browser of the victim
+        let attacker = start_login(&mut app).await;
+        let victim = start_login(&mut app).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", attacker.callback_query))
+            .cookie(victim.cookie)
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", attacker.callback_query))
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        // A rejected callback does not discard the login.
+        assert!(data
+            .lock()
+            .unwrap()
+            .pending_logins
+            .contains_key(&attacker.state));
+    }
+
+    #[actix_rt::test]
+    async fn callback_with_forged_cookie_is_rejected() {
+        let config = Data::new(config(start_provider()));
+        let data = app_data(&config);
+        let mut app = init_app!(data, config);
+
+        let login = start_login(&mut app).await;
+        let forged = Sessions::new(
+            vec![SessionKey::new("test", b"other secret".to_vec())],
+            Duration::from_secs(3600),
+        )
+        .sign_value(&login_cookie_value(&login.state));
+
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", login.callback_query))
+            .cookie(Cookie::new(LOGIN_COOKIE, forged))
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn wrong_code_verifier_is_rejected_by_the_provider() {
+        let config = Data::new(config(start_provider()));
+        let data = app_data(&config);
+        let mut app = init_app!(data, config);
+
+        let login = start_login(&mut app).await;
+        data.lock()
+            .unwrap()
+            .pending_logins
+            .get_mut(&login.state)
+            .unwrap()
+            .code_verifier = random_string();
+
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", login.callback_query))
+            .cookie(login.cookie)
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert!(res.response().cookies().all(|c| c.name() != SESSION_COOKIE));
+    }
+
+    #[actix_rt::test]
+    async fn expired_login_is_rejected() {
+        let config = Data::new(config(start_provider()));
+        let data = app_data(&config);
+        let mut app = init_app!(data, config);
+
+        let login = start_login(&mut app).await;
+        data.lock()
+            .unwrap()
+            .pending_logins
+            .get_mut(&login.state)
+            .unwrap()
+            .started = Instant::now()
+            .checked_sub(LOGIN_TIMEOUT)
+            .expect("the clock started long enough ago");
+
+        let req = TestRequest::get()
+            .uri(&format!("/auth/callback?{}", login.callback_query))
+            .cookie(login.cookie)
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(data.lock().unwrap().pending_logins.is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use actix_web::HttpResponse;
+use planpoker_common::{
+    api::API_VERSION,
+    schema::{definitions, protocol_schema},
+    PROTOCOL_VERSION,
+};
+use schemars::gen::SchemaSettings;
+use serde_json::{json, Value};
+
+/// JSON Schema of the websocket protocol types
+pub async fn schema() -> HttpResponse {
+    HttpResponse::Ok().json(protocol_schema())
+}
+
+/// OpenAPI document of the REST API
+pub async fn openapi() -> HttpResponse {
+    HttpResponse::Ok().json(document())
+}
+
+fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Planning poker",
+            "version": format!("{}.0.0", PROTOCOL_VERSION),
+        },
+        "servers": [{ "url": format!("/api/{}", API_VERSION) }],
+        "security": [{ "bearer": [] }, { "session": [] }],
+        "paths": {
+            "/tokens": {
+                "post": operation(
+                    "Issue an API token for the current session, or for a new user",
+                    None,
+                    ("201", Some("ApiToken")),
+                ),
+                "delete": operation("Revoke the API token of the request", None, ("204", None)),
+            },
+            "/rooms": {
+                "post": operation("Create a room", Some("CreateRoom"), ("201", Some("RoomCreated"))),
+            },
+            "/rooms/{id}": {
+                "parameters": [room_id()],
+                "get": operation("Room info", None, ("200", Some("RoomInfo"))),
+                "delete": operation("Close the room", None, ("204", None)),
+            },
+            "/rooms/{id}/stories": {
+                "parameters": [room_id()],
+                "get": array_operation("List the stories", "StorySummary"),
+                "post": operation("Add a story", Some("NewStory"), ("201", None)),
+            },
+            "/rooms/{id}/results": {
+                "parameters": [room_id()],
+                "get": array_operation("Stories with the votes of every round", "Story"),
+            },
+            "/rooms/{id}/tracker": {
+                "parameters": [room_id()],
+                "put": operation("Set the issue tracker", Some("TrackerSettings"), ("204", None)),
+                "delete": operation("Remove the issue tracker", None, ("204", None)),
+            },
+            "/rooms/{id}/tracker/import": {
+                "parameters": [room_id()],
+                "post": {
+                    "summary": "Import issues as stories",
+                    "requestBody": {
+                        "content": { "application/json": { "schema": schema_ref("ImportIssues") } },
+                    },
+                    "responses": {
+                        "201": response_body(Some(json!({
+                            "type": "array",
+                            "items": schema_ref("StorySummary"),
+                        }))),
+                        "default": response_body(Some(schema_ref("ApiError"))),
+                    },
+                },
+            },
+            "/rooms/{id}/webhooks": {
+                "parameters": [room_id()],
+                "get": array_operation("Webhooks with their recent deliveries", "WebhookSummary"),
+                "post": operation("Add a webhook", Some("NewWebhook"), ("201", None)),
+            },
+            "/rooms/{id}/webhooks/{index}": {
+                "parameters": [room_id(), index()],
+                "delete": operation("Remove a webhook", None, ("204", None)),
+            },
+        },
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "session": { "type": "apiKey", "in": "cookie", "name": "session" },
+            },
+            "schemas": definitions(SchemaSettings::openapi3()),
+        },
+    })
+}
+
+fn operation(summary: &str, request: Option<&str>, response: (&str, Option<&str>)) -> Value {
+    let (status, body) = response;
+    let mut operation = json!({
+        "summary": summary,
+        "responses": {
+            status: response_body(body.map(schema_ref)),
+            "default": response_body(Some(schema_ref("ApiError"))),
+        },
+    });
+
+    if let Some(request) = request {
+        operation["requestBody"] = json!({
+            "content": { "application/json": { "schema": schema_ref(request) } },
+        });
+    }
+
+    operation
+}
+
+fn array_operation(summary: &str, item: &str) -> Value {
+    let mut operation = operation(summary, None, ("200", None));
+    operation["responses"]["200"] = response_body(Some(json!({
+        "type": "array",
+        "items": schema_ref(item),
+    })));
+
+    operation
+}
+
+fn response_body(schema: Option<Value>) -> Value {
+    match schema {
+        Some(schema) => json!({
+            "description": "",
+            "content": { "application/json": { "schema": schema } },
+        }),
+        None => json!({ "description": "" }),
+    }
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn room_id() -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "uint32" },
+    })
+}
+
+fn index() -> Value {
+    json!({
+        "name": "index",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "uint" },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{Method, StatusCode},
+        test::{self, TestRequest},
+        App,
+    };
+
+    use super::*;
+    use crate::api::{self, tests::app_data};
+
+    #[actix_rt::test]
+    async fn documented_operations_match_the_routes() {
+        let (state, config, webhooks) = app_data();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(config)
+                .data(webhooks)
+                .configure(api::configure),
+        )
+        .await;
+
+        let document = document();
+        let prefix = format!("/api/{}", API_VERSION);
+        assert_eq!(document["servers"][0]["url"], prefix.as_str());
+
+        let methods = [
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::PATCH,
+        ];
+
+        for (path, item) in document["paths"].as_object().unwrap() {
+            let uri = format!(
+                "{}{}",
+                prefix,
+                path.replace("{id}", "1").replace("{index}", "0")
+            );
+
+            for method in methods.iter() {
+                let req = TestRequest::default()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .to_request();
+                let res = test::call_service(&mut app, req).await;
+                let status = res.status();
+                let body = test::read_body(res).await;
+
+                // Requests no handler is registered for get an empty 404 or 405,
+                // the handlers respond with an error body.
+                let unrouted =
+                    status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED;
+                let routed = !(unrouted && body.is_empty());
+                let documented = item.get(method.as_str().to_lowercase()).is_some();
+
+                assert_eq!(routed, documented, "{} {}: {}", method, path, status);
+            }
+        }
+    }
+}
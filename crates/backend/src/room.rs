@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use planpoker_common::{
+    api::TrackerSettings, CardCount, CardValue, ChatMessage, RoomError, RoomId, RoomInfo,
+    RoomState, Round, RoundTimer, RoundVote, Story, Vote,
+};
+
+/// Longest allowed round timer
+pub const MAX_TIMER_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Longest allowed delay before revealing the votes automatically
+pub const MAX_AUTO_REVEAL_DELAY: u32 = 60;
+/// Longest allowed chat message, in characters
+pub const MAX_CHAT_LENGTH: usize = 1000;
+/// How many chat messages are kept for users joining later
+const CHAT_HISTORY_LENGTH: usize = 200;
+/// How long a room nobody has joined yet still counts as in use
+const UNJOINED_ROOM_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+struct Timer {
+    duration: Duration,
+    deadline: Instant,
+    auto_reveal: bool,
+}
+
+/// Server side state of a room.
+///
+/// `info` is the state shared with the clients, the rest is only known to the server.
+pub struct Room {
+    pub info: RoomInfo,
+    timer: Option<Timer>,
+    /// When the votes will be revealed automatically
+    pending_reveal: Option<Instant>,
+    /// Whether the revealed votes are already in the story history
+    round_archived: bool,
+    chat: VecDeque<ChatMessage>,
+    /// Issue tracker the stories are imported from
+    pub tracker: Option<TrackerSettings>,
+    created: Instant,
+}
+
+impl Room {
+    pub fn new(info: RoomInfo) -> Self {
+        Self {
+            info,
+            created: Instant::now(),
+            timer: None,
+            pending_reveal: None,
+            round_archived: false,
+            chat: VecDeque::new(),
+            tracker: None,
+        }
+    }
+
+    pub fn id(&self) -> RoomId {
+        self.info.id
+    }
+
+    /// Whether the room is still in use: not closed, and either has members
+    /// or was created only recently and is yet to be joined.
+    pub fn in_use(&self) -> bool {
+        self.info.state != RoomState::Closed
+            && (!self.info.users.is_empty() || self.created.elapsed() < UNJOINED_ROOM_TIMEOUT)
+    }
+
+    /// Room info to be sent to the clients, with the remaining time of the timer updated.
+    ///
+    /// In anonymous mode the revealed votes are replaced with their distribution,
+    /// so that who voted what never leaves the server.
+    pub fn client_info(&self) -> RoomInfo {
+        let mut info = self.info.clone();
+
+        if info.settings.anonymous && info.state.is_revealed() {
+            let mut counts = BTreeMap::new();
+
+            for user in info.users.iter_mut() {
+                if let Vote::Revealed(card) = user.vote {
+                    *counts.entry(card).or_insert(0) += 1;
+                    user.vote = Vote::Unknown;
+                }
+            }
+
+            info.distribution = Some(
+                counts
+                    .into_iter()
+                    .map(|(card, count)| CardCount { card, count })
+                    .collect(),
+            );
+        }
+
+        info.timer = self.timer.as_ref().map(|timer| RoundTimer {
+            duration: timer.duration.as_secs() as u32,
+            remaining_ms: timer
+                .deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64,
+            auto_reveal: timer.auto_reveal,
+        });
+
+        info
+    }
+
+    pub fn start_timer(&mut self, duration: Duration, auto_reveal: bool) {
+        let duration = duration.min(MAX_TIMER_DURATION);
+
+        self.timer = Some(Timer {
+            duration,
+            deadline: Instant::now() + duration,
+            auto_reveal,
+        });
+    }
+
+    /// Stops the timer. Returns false if there was no timer running.
+    pub fn stop_timer(&mut self) -> bool {
+        self.timer.take().is_some()
+    }
+
+    /// Removes the timer if it has run out. Returns whether the votes
+    /// should be revealed automatically, or `None` if the timer has not expired.
+    pub fn expire_timer(&mut self, now: Instant) -> Option<bool> {
+        match self.timer.as_ref() {
+            Some(timer) if timer.deadline <= now => self.timer.take().map(|t| t.auto_reveal),
+            _ => None,
+        }
+    }
+
+    /// Clears the votes and stops everything related to the current round.
+    /// The state of the room is left to the caller.
+    pub fn clear_votes(&mut self) {
+        for user in self.info.users.iter_mut() {
+            user.vote = Vote::None;
+        }
+
+        self.round_archived = false;
+        self.stop_timer();
+        self.cancel_reveal();
+    }
+
+    /// Stores the revealed votes in the history of the current story,
+    /// adding an untitled story if there is none. Returns the stored round,
+    /// which is only added once per round of voting.
+    pub fn archive_round(&mut self) -> Option<&mut Round> {
+        let anonymous = self.info.settings.anonymous;
+        let votes = self
+            .info
+            .users
+            .iter()
+            .filter_map(|u| match u.vote {
+                Vote::Revealed(card) => Some(RoundVote {
+                    user_id: if anonymous { None } else { Some(u.user_id) },
+                    card,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        if self.info.current_story().is_none() {
+            let title = format!("Story {}", self.info.stories.len() + 1);
+            self.info.stories.push(Story::new(title));
+            self.info.current_story = Some(self.info.stories.len() - 1);
+        }
+
+        let archived = std::mem::replace(&mut self.round_archived, true);
+        let story = self.info.current_story_mut()?;
+
+        if !archived {
+            story.rounds.push(Round {
+                votes,
+                estimate: None,
+            });
+        }
+
+        story.rounds.last_mut()
+    }
+
+    /// Sets the final estimate of the current story, archiving the revealed round.
+    pub fn set_estimate(&mut self, estimate: CardValue) {
+        if let Some(round) = self.archive_round() {
+            round.estimate = Some(estimate);
+        }
+
+        if let Some(story) = self.info.current_story_mut() {
+            story.estimate = Some(estimate);
+        }
+    }
+
+    pub fn chat_history(&self) -> Vec<ChatMessage> {
+        self.chat.iter().cloned().collect()
+    }
+
+    /// Adds a message to the chat history, dropping the oldest one if the history is full.
+    pub fn push_chat(&mut self, message: ChatMessage) {
+        if self.chat.len() >= CHAT_HISTORY_LENGTH {
+            self.chat.pop_front();
+        }
+
+        self.chat.push_back(message);
+    }
+
+    /// Schedules the votes to be revealed after the delay.
+    /// Returns false if a reveal was already pending.
+    pub fn schedule_reveal(&mut self, delay: Duration) -> bool {
+        if self.pending_reveal.is_some() {
+            return false;
+        }
+
+        self.pending_reveal = Some(Instant::now() + delay);
+        true
+    }
+
+    /// Cancels a pending reveal. Returns false if there was none.
+    pub fn cancel_reveal(&mut self) -> bool {
+        self.pending_reveal.take().is_some()
+    }
+
+    /// Returns true, and clears the pending reveal, if it is time to reveal the votes.
+    pub fn take_due_reveal(&mut self, now: Instant) -> bool {
+        match self.pending_reveal {
+            Some(at) if at <= now => {
+                self.pending_reveal = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Trims the message and removes control characters other than line breaks.
+pub fn sanitize_chat(text: &str) -> Result<String, RoomError> {
+    let text = text
+        .trim()
+        .chars()
+        .filter(|c| *c == '\n' || !c.is_control())
+        .collect::<String>();
+
+    if text.is_empty() {
+        Err(RoomError::EmptyChatMessage)
+    } else if text.chars().count() > MAX_CHAT_LENGTH {
+        Err(RoomError::ChatMessageTooLong(MAX_CHAT_LENGTH))
+    } else {
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use planpoker_common::RoomUser;
+
+    use super::*;
+
+    fn user() -> RoomUser {
+        RoomUser {
+            user_id: 2,
+            name: None,
+            vote: Vote::None,
+        }
+    }
+
+    #[test]
+    fn new_room_is_in_use_until_abandoned() {
+        let mut room = Room::new(RoomInfo::new(1, 2));
+        assert!(room.in_use());
+
+        room.created -= UNJOINED_ROOM_TIMEOUT;
+        assert!(!room.in_use());
+
+        room.info.users.push(user());
+        assert!(room.in_use());
+    }
+
+    #[test]
+    fn closed_room_is_not_in_use() {
+        let mut room = Room::new(RoomInfo::new(1, 2));
+        room.info.users.push(user());
+        room.info.state = RoomState::Closed;
+
+        assert!(!room.in_use());
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac, NewMac};
+use planpoker_common::SessionId;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Name of the cookie holding the signed session token
+pub const SESSION_COOKIE: &str = "session";
+
+#[derive(Clone)]
+pub struct SessionKey {
+    id: String,
+    secret: Vec<u8>,
+}
+
+impl SessionKey {
+    pub fn new<T: Into<String>>(id: T, secret: Vec<u8>) -> Self {
+        Self {
+            id: id.into(),
+            secret,
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_varkey(&self.secret).expect("HMAC accepts keys of any length")
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionToken {
+    pub session_id: SessionId,
+    /// Unix timestamp (seconds) of when the token was issued
+    pub issued_at: u64,
+    /// Unix timestamp (seconds) after which the token is no longer accepted
+    pub expires_at: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Malformed,
+    UnknownKey,
+    BadSignature,
+    Expired,
+    Revoked,
+}
+
+/// Issues and verifies HMAC signed session tokens.
+///
+/// A token has the form `key_id.payload.signature` where payload and signature
+/// are base64url encoded. The payload contains the session id together with
+/// the issue and expiry times.
+pub struct Sessions {
+    /// The first key is used for signing, all keys are accepted when verifying.
+    keys: Vec<SessionKey>,
+    ttl: Duration,
+    /// Revoked session ids, mapped to the time after which every token
+    /// of the session has expired anyway and the entry can be dropped.
+    revoked: HashMap<SessionId, u64>,
+}
+
+impl Sessions {
+    pub fn new(keys: Vec<SessionKey>, ttl: Duration) -> Self {
+        assert!(!keys.is_empty(), "at least one session key is required");
+
+        Self {
+            keys,
+            ttl,
+            revoked: HashMap::new(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn issue(&self, session_id: SessionId) -> (String, SessionToken) {
+        let issued_at = unix_now();
+        let token = SessionToken {
+            session_id,
+            issued_at,
+            expires_at: issued_at + self.ttl.as_secs(),
+        };
+
+        (self.sign(&token), token)
+    }
+
+    pub fn verify(&self, token: &str) -> Result<SessionToken, TokenError> {
+        let (_, token) = self.verify_signature(token)?;
+
+        if token.expires_at <= unix_now() {
+            return Err(TokenError::Expired);
+        }
+
+        if self.revoked.contains_key(&token.session_id) {
+            return Err(TokenError::Revoked);
+        }
+
+        Ok(token)
+    }
+
+    /// Returns true if a valid token should be replaced with a fresh one, either
+    /// because it was signed with a rotated-out key or because it is past half of its lifetime.
+    pub fn needs_refresh(&self, token: &str) -> bool {
+        match self.verify_signature(token) {
+            Ok((key, token)) => {
+                let half_life = (token.expires_at - token.issued_at) / 2;
+
+                key.id != self.keys[0].id || unix_now() >= token.issued_at + half_life
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Revokes every token issued for the session.
+    pub fn revoke(&mut self, session_id: SessionId) {
+        let now = unix_now();
+
+        self.revoked.retain(|_, until| *until > now);
+        self.revoked.insert(session_id, now + self.ttl.as_secs());
+    }
+
+    /// Signs a value with the current key, for other short-lived cookies.
+    /// The value has to tell itself apart from session tokens and other signed values.
+    pub fn sign_value(&self, value: &str) -> String {
+        let key = &self.keys[0];
+        let payload = base64::encode_config(value, base64::URL_SAFE_NO_PAD);
+        let signed = format!("{}.{}", key.id, payload);
+
+        let mut mac = key.mac();
+        mac.update(signed.as_bytes());
+        let signature = base64::encode_config(mac.finalize().into_bytes(), base64::URL_SAFE_NO_PAD);
+
+        format!("{}.{}", signed, signature)
+    }
+
+    /// Returns the value signed with [`Sessions::sign_value`] if the signature is valid.
+    pub fn verify_value(&self, signed: &str) -> Result<String, TokenError> {
+        self.verify_value_with_key(signed).map(|(_, value)| value)
+    }
+
+    fn sign(&self, token: &SessionToken) -> String {
+        self.sign_value(&format!(
+            "{:032x}:{}:{}",
+            token.session_id, token.issued_at, token.expires_at
+        ))
+    }
+
+    fn verify_value_with_key(&self, token: &str) -> Result<(&SessionKey, String), TokenError> {
+        let mut parts = token.rsplitn(2, '.');
+        let signature = parts.next().ok_or(TokenError::Malformed)?;
+        let signed = parts.next().ok_or(TokenError::Malformed)?;
+
+        let mut parts = signed.splitn(2, '.');
+        let key_id = parts.next().ok_or(TokenError::Malformed)?;
+        let payload = parts.next().ok_or(TokenError::Malformed)?;
+
+        let key = self
+            .keys
+            .iter()
+            .find(|k| k.id == key_id)
+            .ok_or(TokenError::UnknownKey)?;
+
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| TokenError::Malformed)?;
+
+        let mut mac = key.mac();
+        mac.update(signed.as_bytes());
+        mac.verify(&signature)
+            .map_err(|_| TokenError::BadSignature)?;
+
+        let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|p| String::from_utf8(p).ok())
+            .ok_or(TokenError::Malformed)?;
+
+        Ok((key, payload))
+    }
+
+    fn verify_signature(&self, token: &str) -> Result<(&SessionKey, SessionToken), TokenError> {
+        let (key, payload) = self.verify_value_with_key(token)?;
+
+        let mut fields = payload.split(':');
+        let mut next_field = || fields.next().ok_or(TokenError::Malformed);
+
+        let session_id =
+            SessionId::from_str_radix(next_field()?, 16).map_err(|_| TokenError::Malformed)?;
+        let issued_at = next_field()?.parse().map_err(|_| TokenError::Malformed)?;
+        let expires_at = next_field()?.parse().map_err(|_| TokenError::Malformed)?;
+
+        Ok((
+            key,
+            SessionToken {
+                session_id,
+                issued_at,
+                expires_at,
+            },
+        ))
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(3600);
+
+    fn key(id: &str) -> SessionKey {
+        SessionKey::new(id, format!("secret of {}", id).into_bytes())
+    }
+
+    /// Replaces one of the dot separated parts of the token.
+    fn replace_part(token: &str, index: usize, part: &str) -> String {
+        let mut parts = token.split('.').collect::<Vec<_>>();
+        parts[index] = part;
+        parts.join(".")
+    }
+
+    #[test]
+    fn issued_token_verifies() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let (token, issued) = sessions.issue(42);
+
+        assert_eq!(sessions.verify(&token), Ok(issued));
+        assert!(!sessions.needs_refresh(&token));
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let (token, _) = sessions.issue(42);
+
+        let other = Sessions::new(vec![SessionKey::new("a", b"other".to_vec())], TTL);
+        let (forged, _) = other.issue(42);
+        let signature = forged.rsplit('.').next().unwrap();
+
+        assert_eq!(
+            sessions.verify(&replace_part(&token, 2, signature)),
+            Err(TokenError::BadSignature)
+        );
+        assert_eq!(
+            sessions.verify(&replace_part(&token, 2, "not base64!")),
+            Err(TokenError::Malformed)
+        );
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let (token, issued) = sessions.issue(42);
+
+        let payload = base64::encode_config(
+            format!("{:032x}:{}:{}", 43, issued.issued_at, issued.expires_at),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        assert_eq!(
+            sessions.verify(&replace_part(&token, 1, &payload)),
+            Err(TokenError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let now = unix_now();
+        let token = sessions.sign(&SessionToken {
+            session_id: 42,
+            issued_at: now - 7200,
+            expires_at: now - 3600,
+        });
+
+        assert_eq!(sessions.verify(&token), Err(TokenError::Expired));
+        assert!(sessions.needs_refresh(&token));
+    }
+
+    #[test]
+    fn token_past_half_of_its_lifetime_needs_refresh() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let now = unix_now();
+        let token = sessions.sign(&SessionToken {
+            session_id: 42,
+            issued_at: now - 2000,
+            expires_at: now + 1600,
+        });
+
+        assert!(sessions.verify(&token).is_ok());
+        assert!(sessions.needs_refresh(&token));
+    }
+
+    #[test]
+    fn token_of_unknown_key_is_rejected() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+        let (token, _) = Sessions::new(vec![key("b")], TTL).issue(42);
+
+        assert_eq!(sessions.verify(&token), Err(TokenError::UnknownKey));
+        assert!(sessions.needs_refresh(&token));
+    }
+
+    #[test]
+    fn token_of_rotated_out_key_verifies_and_needs_refresh() {
+        let (token, issued) = Sessions::new(vec![key("old")], TTL).issue(42);
+        let sessions = Sessions::new(vec![key("new"), key("old")], TTL);
+
+        assert_eq!(sessions.verify(&token), Ok(issued));
+        assert!(sessions.needs_refresh(&token));
+
+        let (fresh, _) = sessions.issue(42);
+        assert!(fresh.starts_with("new."));
+        assert!(!sessions.needs_refresh(&fresh));
+    }
+
+    #[test]
+    fn revoked_session_is_rejected() {
+        let mut sessions = Sessions::new(vec![key("a")], TTL);
+        let (token, _) = sessions.issue(42);
+        let (other, _) = sessions.issue(43);
+
+        sessions.revoke(42);
+
+        assert_eq!(sessions.verify(&token), Err(TokenError::Revoked));
+        assert!(sessions.verify(&other).is_ok());
+    }
+
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        let sessions = Sessions::new(vec![key("a")], TTL);
+
+        for token in &["", "a", "a.b", "a..", "a.!!!.!!!"] {
+            assert!(sessions.verify(token).is_err(), "{:?}", token);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix::prelude::*;
+use actix_web::web::Data;
+
+use crate::AppState;
+
+/// How often room timers are checked
+const TICK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Drives time based room events, such as round timers, independently of the clients.
+pub struct RoomTicker {
+    data: Data<Mutex<AppState>>,
+}
+
+impl RoomTicker {
+    pub fn new(data: Data<Mutex<AppState>>) -> Self {
+        Self { data }
+    }
+}
+
+impl Actor for RoomTicker {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(TICK_INTERVAL, |act, _| {
+            act.data.lock().unwrap().tick(Instant::now());
+        });
+    }
+}
@@ -0,0 +1,882 @@
+//! Issue tracker integrations, for importing stories and writing the final estimates back
+
+use std::{fmt, future::Future, pin::Pin, time::Duration};
+
+use actix_web::{
+    client::{Client, ClientRequest},
+    http::{header, Uri},
+};
+use planpoker_common::{
+    api::{GitHubSettings, JiraSettings, TrackerSettings},
+    CardValue, Issue, RoomError, RoomId,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::webhooks::checked_client;
+
+/// Largest response accepted from a tracker, search results can be long
+const MAX_RESPONSE_SIZE: usize = 4 * 1024 * 1024;
+/// How many issues are imported at most
+const MAX_IMPORTED_ISSUES: usize = 100;
+const GITHUB_API_URL: &str = "https://api.github.com";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type TrackerFuture<T> = Pin<Box<dyn Future<Output = Result<T, TrackerError>>>>;
+
+#[derive(Debug)]
+pub struct TrackerError(String);
+
+impl fmt::Display for TrackerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn error(err: impl fmt::Display) -> TrackerError {
+    TrackerError(err.to_string())
+}
+
+pub struct ImportedIssue {
+    pub issue: Issue,
+    pub title: String,
+}
+
+impl ImportedIssue {
+    /// `None` unless the URL of the issue is a web page, as clients link to it
+    fn new(key: String, url: String, title: String) -> Option<Self> {
+        let issue = Issue { key, url };
+        if !issue.has_web_url() {
+            return None;
+        }
+
+        Some(Self { issue, title })
+    }
+}
+
+pub trait Tracker {
+    /// Issues matching the query, in the order of the tracker
+    fn import(&self, query: &str) -> TrackerFuture<Vec<ImportedIssue>>;
+
+    /// Writes the final estimate to the issue, if writing back is configured.
+    fn write_estimate(&self, issue: &Issue, estimate: CardValue) -> TrackerFuture<()>;
+}
+
+/// Checks the settings before they are stored: the URLs must be `http` or `https`
+/// and the GitHub repository has to be `owner/name`, as it becomes part of the paths.
+pub fn validate(settings: &TrackerSettings) -> Result<(), RoomError> {
+    let valid = match settings {
+        TrackerSettings::Jira(settings) => is_web_url(&settings.url),
+        TrackerSettings::GitHub(settings) => {
+            let api_url = match settings.api_url.as_deref() {
+                Some(url) => is_web_url(url),
+                None => true,
+            };
+            api_url && is_repo(&settings.repo)
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(RoomError::InvalidTrackerSettings)
+    }
+}
+
+fn is_web_url(url: &str) -> bool {
+    match url.parse::<Uri>() {
+        Ok(uri) => {
+            matches!(uri.scheme_str(), Some("http") | Some("https"))
+                && matches!(uri.host(), Some(host) if !host.is_empty())
+        }
+        Err(_) => false,
+    }
+}
+
+fn is_repo(repo: &str) -> bool {
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name != "."
+            && name != ".."
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    };
+
+    match repo.split_once('/') {
+        Some((owner, name)) => is_name(owner) && is_name(name),
+        None => false,
+    }
+}
+
+/// The tracker of the settings. Its requests are only sent to public addresses or
+/// `allowed_hosts`, as the URLs are chosen by room admins (see [`checked_client`]).
+pub fn tracker(settings: &TrackerSettings, allowed_hosts: Vec<String>) -> Box<dyn Tracker> {
+    let client = checked_client(allowed_hosts, REQUEST_TIMEOUT);
+
+    match settings {
+        TrackerSettings::Jira(settings) => Box::new(Jira(settings.clone(), client)),
+        TrackerSettings::GitHub(settings) => Box::new(GitHub(settings.clone(), client)),
+    }
+}
+
+/// Writes the estimate back in the background, failures are only logged.
+pub fn write_back(
+    room_id: RoomId,
+    settings: &TrackerSettings,
+    allowed_hosts: Vec<String>,
+    issue: Issue,
+    estimate: CardValue,
+) {
+    let write = tracker(settings, allowed_hosts).write_estimate(&issue, estimate);
+
+    actix::spawn(async move {
+        if let Err(err) = write.await {
+            println!(
+                "Failed to write the estimate of {} in room {} back: {}",
+                issue.key, room_id, err
+            );
+        }
+    });
+}
+
+struct Jira(JiraSettings, Client);
+
+#[derive(Deserialize)]
+struct JiraSearch {
+    issues: Vec<JiraIssue>,
+    /// Number of issues matching the query, over all pages
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: JiraFields,
+}
+
+#[derive(Deserialize)]
+struct JiraFields {
+    summary: String,
+}
+
+impl Jira {
+    fn request(settings: &JiraSettings, request: ClientRequest) -> ClientRequest {
+        match settings.user.as_ref() {
+            Some(user) => request.basic_auth(user, Some(&settings.token)),
+            None => request.bearer_auth(&settings.token),
+        }
+    }
+}
+
+impl Tracker for Jira {
+    fn import(&self, query: &str) -> TrackerFuture<Vec<ImportedIssue>> {
+        let settings = self.0.clone();
+        let client = self.1.clone();
+        let url = format!("{}/rest/api/2/search", settings.url.trim_end_matches('/'));
+        let query = query.to_owned();
+
+        Box::pin(async move {
+            // Jira may return fewer issues than asked for, the rest is fetched page by page.
+            let mut issues = Vec::new();
+            loop {
+                let start_at = issues.len().to_string();
+                let max_results = (MAX_IMPORTED_ISSUES - issues.len()).to_string();
+                let request = Jira::request(&settings, client.get(&url))
+                    .query(&[
+                        ("jql", query.as_str()),
+                        ("fields", "summary"),
+                        ("startAt", &start_at),
+                        ("maxResults", &max_results),
+                    ])
+                    .map_err(error)?;
+
+                let search: JiraSearch = fetch(request).await?;
+                let last_page = search.issues.is_empty();
+                issues.extend(search.issues);
+
+                if last_page || issues.len() >= search.total.min(MAX_IMPORTED_ISSUES) {
+                    break;
+                }
+            }
+            issues.truncate(MAX_IMPORTED_ISSUES);
+
+            let browse_url = format!("{}/browse", settings.url.trim_end_matches('/'));
+
+            Ok(issues
+                .into_iter()
+                .filter_map(|issue| {
+                    let url = format!("{}/{}", browse_url, issue.key);
+                    ImportedIssue::new(issue.key, url, issue.fields.summary)
+                })
+                .collect())
+        })
+    }
+
+    fn write_estimate(&self, issue: &Issue, estimate: CardValue) -> TrackerFuture<()> {
+        let field = match self.0.estimate_field.as_ref() {
+            Some(field) => field,
+            None => return Box::pin(async { Ok(()) }),
+        };
+
+        let url = format!(
+            "{}/rest/api/2/issue/{}",
+            self.0.url.trim_end_matches('/'),
+            issue.key
+        );
+        let request = Jira::request(&self.0, self.1.put(url));
+        let body = json!({ "fields": { field: estimate.as_f64() } });
+
+        Box::pin(async move { send(request, &body).await })
+    }
+}
+
+struct GitHub(GitHubSettings, Client);
+
+#[derive(Deserialize)]
+struct GitHubSearch {
+    items: Vec<GitHubIssue>,
+}
+
+#[derive(Deserialize)]
+struct GitHubIssue {
+    number: u64,
+    title: String,
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct GitHubLabel {
+    name: String,
+}
+
+impl GitHub {
+    fn api_url(&self) -> &str {
+        self.0
+            .api_url
+            .as_deref()
+            .unwrap_or(GITHUB_API_URL)
+            .trim_end_matches('/')
+    }
+
+    fn request(&self, request: ClientRequest) -> ClientRequest {
+        request
+            .bearer_auth(&self.0.token)
+            .header(header::ACCEPT, "application/vnd.github+json")
+            // Required by the GitHub API
+            .header(header::USER_AGENT, "planpoker")
+    }
+}
+
+impl Tracker for GitHub {
+    fn import(&self, query: &str) -> TrackerFuture<Vec<ImportedIssue>> {
+        let url = format!("{}/search/issues", self.api_url());
+        let query = format!("{} repo:{} is:issue", query, self.0.repo);
+        let per_page = MAX_IMPORTED_ISSUES.to_string();
+        let request = self
+            .request(self.1.get(url))
+            .query(&[("q", query.as_str()), ("per_page", &per_page)]);
+
+        Box::pin(async move {
+            let search: GitHubSearch = fetch(request.map_err(error)?).await?;
+
+            Ok(search
+                .items
+                .into_iter()
+                .filter_map(|issue| {
+                    let key = format!("#{}", issue.number);
+                    ImportedIssue::new(key, issue.html_url, issue.title)
+                })
+                .collect())
+        })
+    }
+
+    fn write_estimate(&self, issue: &Issue, estimate: CardValue) -> TrackerFuture<()> {
+        let prefix = match self.0.label_prefix.clone() {
+            Some(prefix) => prefix,
+            None => return Box::pin(async { Ok(()) }),
+        };
+
+        let number = match issue.key.trim_start_matches('#').parse::<u64>() {
+            Ok(number) => number,
+            Err(_) => {
+                let err = TrackerError(format!("invalid GitHub issue key {}", issue.key));
+                return Box::pin(async { Err(err) });
+            }
+        };
+
+        let url = format!(
+            "{}/repos/{}/issues/{}/labels",
+            self.api_url(),
+            self.0.repo,
+            number
+        );
+        let get_labels = self.request(self.1.get(&url));
+        let set_labels = self.request(self.1.put(&url));
+
+        Box::pin(async move {
+            // The labels are replaced as a whole, keeping everything but earlier estimates.
+            let labels: Vec<GitHubLabel> = fetch(get_labels).await?;
+            let mut labels = labels
+                .into_iter()
+                .map(|label| label.name)
+                .filter(|name| !name.starts_with(&prefix))
+                .collect::<Vec<_>>();
+            labels.push(format!("{}{}", prefix, estimate));
+
+            send(set_labels, &json!({ "labels": labels })).await
+        })
+    }
+}
+
+async fn fetch<T: DeserializeOwned>(request: ClientRequest) -> Result<T, TrackerError> {
+    let mut res = request.send().await.map_err(error)?;
+
+    if !res.status().is_success() {
+        return Err(TrackerError(format!(
+            "tracker responded with {}",
+            res.status()
+        )));
+    }
+
+    res.json().limit(MAX_RESPONSE_SIZE).await.map_err(error)
+}
+
+async fn send(request: ClientRequest, body: &serde_json::Value) -> Result<(), TrackerError> {
+    let res = request.send_json(body).await.map_err(error)?;
+
+    if !res.status().is_success() {
+        return Err(TrackerError(format!(
+            "tracker responded with {}",
+            res.status()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use actix_web::{
+        http::{HeaderMap, StatusCode},
+        web::{self, Bytes, Data},
+        App, HttpRequest, HttpResponse, HttpServer,
+    };
+    use serde_json::Value;
+
+    use super::*;
+
+    struct Recorded {
+        method: String,
+        path: String,
+        query: HashMap<String, String>,
+        headers: HeaderMap,
+        /// `Null` for requests without a body
+        body: Value,
+    }
+
+    impl Recorded {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.get(name).map(|v| v.to_str().unwrap())
+        }
+    }
+
+    /// Local tracker API answering every request with `respond`
+    #[derive(Clone)]
+    struct Mock {
+        url: String,
+        requests: Arc<Mutex<Vec<Recorded>>>,
+        respond: fn(&Recorded) -> (u16, Value),
+    }
+
+    impl Mock {
+        fn start(respond: fn(&Recorded) -> (u16, Value)) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let mock = Mock {
+                url: format!("http://{}", listener.local_addr().unwrap()),
+                requests: Arc::default(),
+                respond,
+            };
+
+            let data = Data::new(mock.clone());
+            HttpServer::new(move || {
+                App::new()
+                    .app_data(data.clone())
+                    .default_service(web::route().to(handle))
+            })
+            .listen(listener)
+            .unwrap()
+            .run();
+
+            mock
+        }
+
+        fn requests(&self) -> std::sync::MutexGuard<'_, Vec<Recorded>> {
+            self.requests.lock().unwrap()
+        }
+    }
+
+    async fn handle(mock: Data<Mock>, req: HttpRequest, body: Bytes) -> HttpResponse {
+        let recorded = Recorded {
+            method: req.method().to_string(),
+            path: req.path().to_owned(),
+            query: serde_urlencoded::from_str(req.query_string()).unwrap(),
+            headers: req.headers().clone(),
+            body: match body.is_empty() {
+                true => Value::Null,
+                false => serde_json::from_slice(&body).unwrap(),
+            },
+        };
+
+        let (status, body) = (mock.respond)(&recorded);
+        mock.requests().push(recorded);
+
+        HttpResponse::build(StatusCode::from_u16(status).unwrap()).json(body)
+    }
+
+    fn unauthorized(_: &Recorded) -> (u16, Value) {
+        (401, json!({ "message": "Bad credentials" }))
+    }
+
+    fn jira(mock: &Mock, user: Option<&str>, estimate_field: Option<&str>) -> Box<dyn Tracker> {
+        let settings = TrackerSettings::Jira(JiraSettings {
+            // The trailing slash is ignored
+            url: format!("{}/", mock.url),
+            user: user.map(str::to_owned),
+            token: "jira-token".to_owned(),
+            estimate_field: estimate_field.map(str::to_owned),
+        });
+        tracker(&settings, vec!["127.0.0.1".to_owned()])
+    }
+
+    fn github(mock: &Mock, label_prefix: Option<&str>) -> Box<dyn Tracker> {
+        tracker(
+            &github_settings(Some(format!("{}/", mock.url)), label_prefix),
+            vec!["127.0.0.1".to_owned()],
+        )
+    }
+
+    fn github_settings(api_url: Option<String>, label_prefix: Option<&str>) -> TrackerSettings {
+        TrackerSettings::GitHub(GitHubSettings {
+            repo: "acme/app".to_owned(),
+            token: "github-token".to_owned(),
+            label_prefix: label_prefix.map(str::to_owned),
+            api_url,
+        })
+    }
+
+    /// A page of a Jira search over `total` issues, of at most `page_size` issues
+    fn jira_page(request: &Recorded, total: usize, page_size: usize) -> (u16, Value) {
+        let start_at: usize = request.query["startAt"].parse().unwrap();
+        let max_results: usize = request.query["maxResults"].parse().unwrap();
+        let end = total.min(start_at + page_size.min(max_results));
+
+        let issues = (start_at..end)
+            .map(|i| {
+                json!({
+                    "key": format!("PP-{}", i + 1),
+                    "fields": { "summary": format!("Story {}", i + 1) },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let page = json!({
+            "startAt": start_at,
+            "maxResults": page_size,
+            "total": total,
+            "issues": issues,
+        });
+        (200, page)
+    }
+
+    fn keys(issues: &[ImportedIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.issue.key.as_str()).collect()
+    }
+
+    #[actix_rt::test]
+    async fn jira_import_searches_with_jql() {
+        let mock = Mock::start(|request| jira_page(request, 2, 50));
+        let jira = jira(&mock, Some("me@example.com"), None);
+
+        let issues = jira
+            .import("project = PP AND sprint in openSprints()")
+            .await
+            .unwrap();
+
+        assert_eq!(keys(&issues), ["PP-1", "PP-2"]);
+        assert_eq!(issues[1].title, "Story 2");
+        assert_eq!(issues[1].issue.url, format!("{}/browse/PP-2", mock.url));
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/rest/api/2/search");
+        assert_eq!(
+            request.query["jql"],
+            "project = PP AND sprint in openSprints()"
+        );
+        assert_eq!(request.query["fields"], "summary");
+        assert_eq!(request.query["startAt"], "0");
+        assert_eq!(request.query["maxResults"], "100");
+
+        let credentials = base64::encode("me@example.com:jira-token");
+        assert_eq!(
+            request.header("authorization"),
+            Some(format!("Basic {}", credentials).as_str())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn jira_import_follows_the_pages() {
+        let mock = Mock::start(|request| jira_page(request, 5, 2));
+        let issues = jira(&mock, None, None)
+            .import("project = PP")
+            .await
+            .unwrap();
+
+        assert_eq!(keys(&issues), ["PP-1", "PP-2", "PP-3", "PP-4", "PP-5"]);
+
+        let pages = mock
+            .requests()
+            .iter()
+            .map(|r| (r.query["startAt"].clone(), r.query["maxResults"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pages,
+            [
+                ("0".to_owned(), "100".to_owned()),
+                ("2".to_owned(), "98".to_owned()),
+                ("4".to_owned(), "96".to_owned()),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn jira_import_stops_at_the_limit() {
+        let mock = Mock::start(|request| jira_page(request, 250, 40));
+        let issues = jira(&mock, None, None)
+            .import("project = PP")
+            .await
+            .unwrap();
+
+        assert_eq!(issues.len(), MAX_IMPORTED_ISSUES);
+        assert_eq!(issues[99].issue.key, "PP-100");
+
+        let starts = mock
+            .requests()
+            .iter()
+            .map(|r| r.query["startAt"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, ["0", "40", "80"]);
+    }
+
+    #[actix_rt::test]
+    async fn jira_estimate_is_written_to_the_field() {
+        let mock = Mock::start(|_| (204, Value::Null));
+        let issue = Issue {
+            key: "PP-7".to_owned(),
+            url: format!("{}/browse/PP-7", mock.url),
+        };
+
+        jira(&mock, None, Some("customfield_10016"))
+            .write_estimate(&issue, CardValue::from(0.5))
+            .await
+            .unwrap();
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].path, "/rest/api/2/issue/PP-7");
+        assert_eq!(
+            requests[0].body,
+            json!({ "fields": { "customfield_10016": 0.5 } })
+        );
+        // Without a user the token is a bearer token
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some("Bearer jira-token")
+        );
+    }
+
+    #[actix_rt::test]
+    async fn jira_estimate_is_not_written_without_a_field() {
+        let mock = Mock::start(|_| (204, Value::Null));
+        let issue = Issue {
+            key: "PP-7".to_owned(),
+            url: format!("{}/browse/PP-7", mock.url),
+        };
+
+        jira(&mock, None, None)
+            .write_estimate(&issue, CardValue::from(3))
+            .await
+            .unwrap();
+
+        assert!(mock.requests().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn jira_auth_failure() {
+        let mock = Mock::start(unauthorized);
+        let jira = jira(&mock, Some("me@example.com"), Some("customfield_10016"));
+        let issue = Issue {
+            key: "PP-7".to_owned(),
+            url: format!("{}/browse/PP-7", mock.url),
+        };
+
+        let err = jira.import("project = PP").await.err().unwrap();
+        assert!(err.to_string().contains("401"), "{}", err);
+
+        let err = jira
+            .write_estimate(&issue, CardValue::from(3))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401"), "{}", err);
+    }
+
+    #[actix_rt::test]
+    async fn github_import_searches_the_repository() {
+        let mock = Mock::start(|_| {
+            let search = json!({
+                "total_count": 2,
+                "items": [
+                    {
+                        "number": 12,
+                        "title": "Login page",
+                        "html_url": "https://github.com/acme/app/issues/12",
+                    },
+                    {
+                        "number": 15,
+                        "title": "Logout",
+                        "html_url": "https://github.com/acme/app/issues/15",
+                    },
+                ],
+            });
+            (200, search)
+        });
+
+        let issues = github(&mock, None).import("label:ready").await.unwrap();
+
+        assert_eq!(keys(&issues), ["#12", "#15"]);
+        assert_eq!(issues[0].title, "Login page");
+        assert_eq!(issues[0].issue.url, "https://github.com/acme/app/issues/12");
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/search/issues");
+        assert_eq!(request.query["q"], "label:ready repo:acme/app is:issue");
+        assert_eq!(request.query["per_page"], "100");
+        assert_eq!(request.header("authorization"), Some("Bearer github-token"));
+        assert_eq!(
+            request.header("accept"),
+            Some("application/vnd.github+json")
+        );
+        assert_eq!(request.header("user-agent"), Some("planpoker"));
+    }
+
+    #[actix_rt::test]
+    async fn github_estimate_replaces_the_label() {
+        let mock = Mock::start(|request| match request.method.as_str() {
+            "GET" => {
+                let labels = json!([
+                    { "name": "bug" },
+                    { "name": "estimate: 3" },
+                    { "name": "estimate: 5" },
+                ]);
+                (200, labels)
+            }
+            _ => (200, request.body["labels"].clone()),
+        });
+        let issue = Issue {
+            key: "#12".to_owned(),
+            url: "https://github.com/acme/app/issues/12".to_owned(),
+        };
+
+        github(&mock, Some("estimate: "))
+            .write_estimate(&issue, CardValue::from(8))
+            .await
+            .unwrap();
+
+        let requests = mock.requests();
+        let calls = requests
+            .iter()
+            .map(|r| (r.method.as_str(), r.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                ("GET", "/repos/acme/app/issues/12/labels"),
+                ("PUT", "/repos/acme/app/issues/12/labels"),
+            ]
+        );
+        assert_eq!(
+            requests[1].body,
+            json!({ "labels": ["bug", "estimate: 8"] })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn github_estimate_is_not_written_without_a_prefix() {
+        let mock = Mock::start(|_| (200, json!([])));
+        let issue = Issue {
+            key: "#12".to_owned(),
+            url: "https://github.com/acme/app/issues/12".to_owned(),
+        };
+
+        github(&mock, None)
+            .write_estimate(&issue, CardValue::from(8))
+            .await
+            .unwrap();
+
+        assert!(mock.requests().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn github_auth_failure() {
+        let mock = Mock::start(unauthorized);
+        let github = github(&mock, Some("estimate: "));
+        let issue = Issue {
+            key: "#12".to_owned(),
+            url: "https://github.com/acme/app/issues/12".to_owned(),
+        };
+
+        let err = github.import("label:ready").await.err().unwrap();
+        assert!(err.to_string().contains("401"), "{}", err);
+
+        let err = github
+            .write_estimate(&issue, CardValue::from(8))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401"), "{}", err);
+
+        // The labels are not replaced if they could not be read
+        let methods = mock
+            .requests()
+            .iter()
+            .map(|r| r.method.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, ["GET", "GET"]);
+    }
+
+    #[test]
+    fn settings_are_validated() {
+        let jira = |url: &str| {
+            TrackerSettings::Jira(JiraSettings {
+                url: url.to_owned(),
+                user: None,
+                token: "jira-token".to_owned(),
+                estimate_field: None,
+            })
+        };
+        let github = |repo: &str, api_url: Option<&str>| {
+            TrackerSettings::GitHub(GitHubSettings {
+                repo: repo.to_owned(),
+                token: "github-token".to_owned(),
+                label_prefix: None,
+                api_url: api_url.map(str::to_owned),
+            })
+        };
+
+        for valid in [
+            jira("https://example.atlassian.net"),
+            jira("http://jira.example.com:8080/jira/"),
+            github("acme/app", None),
+            github(
+                "acme-corp/app.rs",
+                Some("https://github.example.com/api/v3"),
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(validate(valid), Ok(()), "{:?}", valid);
+        }
+
+        for invalid in [
+            jira("javascript:alert(1)"),
+            jira("file:///etc/passwd"),
+            jira("ftp://jira.example.com"),
+            jira("jira.example.com"),
+            jira(""),
+            github("acme/app", Some("gopher://github.example.com")),
+            github("acme", None),
+            github("acme/app/issues", None),
+            github("../app", None),
+            github("acme/..", None),
+            github("acme/app?per_page=1", None),
+            github("/app", None),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                validate(invalid),
+                Err(RoomError::InvalidTrackerSettings),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn requests_to_internal_hosts_are_refused() {
+        let mock = Mock::start(|_| (200, json!({ "items": [] })));
+        let tracker = tracker(
+            &github_settings(Some(mock.url.clone()), Some("estimate: ")),
+            Vec::new(),
+        );
+        let issue = Issue {
+            key: "#12".to_owned(),
+            url: "https://github.com/acme/app/issues/12".to_owned(),
+        };
+
+        let err = tracker.import("label:ready").await.err().unwrap();
+        assert!(err.to_string().contains("internal address"), "{}", err);
+
+        let err = tracker
+            .write_estimate(&issue, CardValue::from(3))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("internal address"), "{}", err);
+
+        assert!(mock.requests().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn issues_without_web_urls_are_not_imported() {
+        let mock = Mock::start(|_| {
+            let search = json!({
+                "total_count": 3,
+                "items": [
+                    {
+                        "number": 12,
+                        "title": "Login page",
+                        "html_url": "javascript:alert(document.cookie)",
+                    },
+                    {
+                        "number": 15,
+                        "title": "Logout",
+                        "html_url": "https://github.com/acme/app/issues/15",
+                    },
+                    {
+                        "number": 16,
+                        "title": "Sign up",
+                        "html_url": "data:text/html,<script>alert(1)</script>",
+                    },
+                ],
+            });
+            (200, search)
+        });
+
+        let issues = github(&mock, None).import("label:ready").await.unwrap();
+
+        assert_eq!(keys(&issues), ["#15"]);
+    }
+}
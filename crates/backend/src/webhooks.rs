@@ -0,0 +1,1027 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs},
+    pin::Pin,
+    task::{self, Poll},
+    time::Duration,
+};
+
+use actix::prelude::*;
+use actix_connect::{Connect, ConnectError, Resolver, TcpConnector};
+use actix_service::Service;
+use actix_web::{
+    client::{Client, Connector},
+    http::{header, Uri},
+    web,
+};
+use hmac::{Hmac, Mac, NewMac};
+use planpoker_common::{
+    api::{
+        NewWebhook, WebhookDelivery, WebhookEvent, WebhookFormat, WebhookPayload, WebhookSummary,
+    },
+    RoomError, RoomId, RoomInfo,
+};
+use sha2::Sha256;
+
+use crate::{chatops, session::unix_now};
+
+/// Maximum number of webhooks a single room can have
+const MAX_ROOM_WEBHOOKS: usize = 10;
+/// How many delivery attempts are kept per webhook
+const DELIVERY_LOG_LENGTH: usize = 50;
+/// Failed deliveries are retried until this many attempts have been made
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled for every further one
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const SIGNATURE_HEADER: &str = "X-Planpoker-Signature";
+pub const EVENT_HEADER: &str = "X-Planpoker-Event";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Clone)]
+pub struct Webhook {
+    pub url: String,
+    pub secret: Option<String>,
+    /// All events if empty
+    pub events: Vec<WebhookEvent>,
+    pub format: WebhookFormat,
+}
+
+impl Webhook {
+    fn new(webhook: NewWebhook) -> Result<Self, RoomError> {
+        let url = webhook.url.trim();
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(RoomError::InvalidWebhookUrl);
+        }
+
+        Ok(Self {
+            url: url.to_owned(),
+            secret: webhook.secret.filter(|secret| !secret.is_empty()),
+            events: webhook.events,
+            format: webhook.format,
+        })
+    }
+
+    /// Server-wide webhooks receiving the events of every room:
+    ///
+    /// - `PLANPOKER_WEBHOOKS`: comma separated list of URLs, which may point to internal hosts
+    /// - `PLANPOKER_WEBHOOK_SECRET`: key for signing the requests
+    pub fn from_env() -> Vec<Self> {
+        let secret = env::var("PLANPOKER_WEBHOOK_SECRET").ok();
+
+        env::var("PLANPOKER_WEBHOOKS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|url| !url.trim().is_empty())
+            .filter_map(|url| {
+                let webhook = Webhook::new(NewWebhook {
+                    url: url.to_owned(),
+                    secret: secret.clone(),
+                    events: Vec::new(),
+                    format: WebhookFormat::Json,
+                });
+                if webhook.is_err() {
+                    println!("Ignoring invalid webhook URL {}", url);
+                }
+                webhook.ok()
+            })
+            .collect()
+    }
+
+    fn subscribed(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    /// Value of the signature header for the body, if the webhook has a secret
+    fn signature(&self, body: &str) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac =
+            HmacSha256::new_varkey(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(body.as_bytes());
+
+        let hex = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        Some(format!("sha256={}", hex))
+    }
+}
+
+/// Hosts room webhooks may be sent to and issue trackers may be reached at, even though
+/// they are internal:
+///
+/// - `PLANPOKER_WEBHOOK_ALLOWED_HOSTS`: comma separated list of host names or addresses,
+///   such as a team chat or a Jira server on the internal network
+pub fn allowed_hosts_from_env() -> Vec<String> {
+    env::var("PLANPOKER_WEBHOOK_ALLOWED_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// Checks that the URL of a room webhook does not point into the network of the server,
+/// as anyone can add one to their room (server-side request forgery).
+///
+/// This rejects bad URLs up front, the deliveries themselves go through [`checked_client`].
+pub(crate) async fn check_destination(
+    url: String,
+    allowed_hosts: Vec<String>,
+) -> Result<(), String> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|err| format!("invalid URL: {}", err))?;
+    let host = normalize_host(uri.host().ok_or("URL without a host")?);
+
+    if allowed_hosts.contains(&host) {
+        return Ok(());
+    }
+
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+
+    let addresses = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            let name = host.clone();
+            web::block(move || {
+                (name.as_str(), port)
+                    .to_socket_addrs()
+                    .map(|addrs| addrs.map(|addr| addr.ip()).collect::<Vec<_>>())
+            })
+            .await
+            .map_err(|err| format!("could not resolve {}: {}", host, err))?
+        }
+    };
+
+    match addresses.iter().find(|ip| is_internal(**ip)) {
+        Some(ip) => Err(format!("{} resolves to the internal address {}", host, ip)),
+        None if addresses.is_empty() => Err(format!("{} has no addresses", host)),
+        None => Ok(()),
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase()
+}
+
+/// HTTP client for requests to URLs chosen by users, which only connects to addresses
+/// that are not internal, unless the host is in `allowed_hosts`.
+///
+/// The host is resolved once per connection and the checked addresses are connected to,
+/// so that a name cannot be pointed to an internal address between the check and the
+/// request (DNS rebinding). The request still carries the original host name.
+pub(crate) fn checked_client(allowed_hosts: Vec<String>, timeout: Duration) -> Client {
+    let connector = Connector::new()
+        .connector(CheckedConnector {
+            resolver: Resolver::default(),
+            allowed_hosts,
+        })
+        .timeout(timeout)
+        .finish();
+
+    Client::builder()
+        .connector(connector)
+        .timeout(timeout)
+        .finish()
+}
+
+#[derive(Clone)]
+struct CheckedConnector {
+    resolver: Resolver<Uri>,
+    allowed_hosts: Vec<String>,
+}
+
+impl Service for CheckedConnector {
+    type Request = Connect<Uri>;
+    type Response = <TcpConnector<Uri> as Service>::Response;
+    type Error = ConnectError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, _: &mut task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Connect<Uri>) -> Self::Future {
+        let host = normalize_host(req.host());
+        let allowed = self.allowed_hosts.contains(&host);
+        let resolved = self.resolver.call(req);
+
+        Box::pin(async move {
+            let mut req = resolved.await?;
+
+            if !allowed {
+                let addrs = req.take_addrs().collect::<Vec<_>>();
+                if let Some(addr) = addrs.iter().find(|addr| is_internal(addr.ip())) {
+                    return Err(ConnectError::Io(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("{} resolves to the internal address {}", host, addr.ip()),
+                    )));
+                }
+                req = req.set_addrs(addrs);
+            }
+
+            TcpConnector::new().call(req).await
+        })
+    }
+}
+
+/// Loopback, private, link-local, multicast, reserved and other addresses not reachable
+/// from the internet, including IPv6 addresses that embed such an IPv4 address
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => match embedded_v4(ip) {
+            Some(ip) => is_internal_v4(ip),
+            None => is_internal_v6(ip),
+        },
+    }
+}
+
+fn is_internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // "This network", carrier-grade NAT, IETF protocol assignments, benchmarking
+        // and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240
+}
+
+fn is_internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local and link-local
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+}
+
+/// IPv4 address of an IPv4-mapped, IPv4-compatible, NAT64 (64:ff9b::/96)
+/// or 6to4 (2002::/16) address
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., a, b, c, d] = ip.octets();
+
+    match segments {
+        // :: and ::1 are not IPv4-compatible
+        _ if ip.is_loopback() || ip.is_unspecified() => None,
+        [0, 0, 0, 0, 0, 0xffff, ..] | [0, 0, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(a, b, c, d)),
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(a, b, c, d)),
+        [0x2002, high, low, ..] => Some(Ipv4Addr::new(
+            (high >> 8) as u8,
+            high as u8,
+            (low >> 8) as u8,
+            low as u8,
+        )),
+        _ => None,
+    }
+}
+
+struct Subscription {
+    /// Identifies the subscription while deliveries are in flight, as indices change on removal
+    id: u64,
+    webhook: Webhook,
+    deliveries: VecDeque<WebhookDelivery>,
+}
+
+#[derive(Clone)]
+struct Delivery {
+    subscription_id: Option<u64>,
+    webhook: Webhook,
+    event: WebhookEvent,
+    body: String,
+    attempt: u32,
+}
+
+/// Posts room events to the webhooks subscribed to them.
+///
+/// Owns the per-room subscriptions and their delivery logs, so that slow receivers
+/// never hold up the room state.
+pub struct WebhookDispatcher {
+    /// Server-wide webhooks, their deliveries are only logged to stdout
+    webhooks: Vec<Webhook>,
+    /// Internal hosts room webhooks may be sent to
+    allowed_hosts: Vec<String>,
+    rooms: HashMap<RoomId, Vec<Subscription>>,
+    next_id: u64,
+    /// Client for room webhooks, see [`checked_client`]
+    client: Client,
+    /// Client for server-wide webhooks, which are configured by the operator and may be internal
+    server_client: Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(webhooks: Vec<Webhook>, allowed_hosts: Vec<String>) -> Self {
+        Self {
+            webhooks,
+            rooms: HashMap::new(),
+            next_id: 0,
+            client: checked_client(allowed_hosts.clone(), REQUEST_TIMEOUT),
+            server_client: Client::builder().timeout(REQUEST_TIMEOUT).finish(),
+            allowed_hosts,
+        }
+    }
+
+    fn deliver(&mut self, ctx: &mut Context<Self>, delivery: Delivery) {
+        let client = match delivery.subscription_id {
+            Some(_) => &self.client,
+            None => &self.server_client,
+        };
+        let mut request = client
+            .post(&delivery.webhook.url)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, format!("{:?}", delivery.event));
+
+        if let Some(signature) = delivery.webhook.signature(&delivery.body) {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let body = delivery.body.clone();
+        let response = async move { request.send_body(body).await.map_err(|err| err.to_string()) };
+
+        ctx.spawn(response.into_actor(self).map(move |res, act, ctx| {
+            let (status, error) = match res {
+                Ok(res) if res.status().is_success() => (Some(res.status().as_u16()), None),
+                Ok(res) => (Some(res.status().as_u16()), Some(res.status().to_string())),
+                Err(err) => (None, Some(err)),
+            };
+
+            if let Some(error) = error.as_ref() {
+                println!(
+                    "Webhook delivery to {} failed (attempt {}): {}",
+                    delivery.webhook.url, delivery.attempt, error
+                );
+            }
+
+            act.log(&delivery, status, error.clone());
+
+            if error.is_some() && delivery.attempt < MAX_ATTEMPTS {
+                let delay = RETRY_DELAY * 2u32.pow(delivery.attempt - 1);
+                let retry = Delivery {
+                    attempt: delivery.attempt + 1,
+                    ..delivery
+                };
+                ctx.run_later(delay, move |act, ctx| act.deliver(ctx, retry));
+            }
+        }));
+    }
+
+    fn log(&mut self, delivery: &Delivery, status: Option<u16>, error: Option<String>) {
+        let subscription = self
+            .rooms
+            .values_mut()
+            .flatten()
+            .find(|s| Some(s.id) == delivery.subscription_id);
+
+        // Gone if the webhook or its room has been removed in the meantime.
+        if let Some(subscription) = subscription {
+            if subscription.deliveries.len() >= DELIVERY_LOG_LENGTH {
+                subscription.deliveries.pop_front();
+            }
+            subscription.deliveries.push_back(WebhookDelivery {
+                event: delivery.event,
+                attempt: delivery.attempt,
+                status,
+                error,
+                sent_at: unix_now(),
+            });
+        }
+    }
+}
+
+impl Actor for WebhookDispatcher {
+    type Context = Context<Self>;
+}
+
+/// An event happened in the room, `room` is its state after the event.
+pub struct Publish {
+    pub event: WebhookEvent,
+    pub room: RoomInfo,
+}
+
+impl Message for Publish {
+    type Result = ();
+}
+
+impl Handler<Publish> for WebhookDispatcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: Publish, ctx: &mut Self::Context) {
+        let event = msg.event;
+        let room_id = msg.room.id;
+        let payload = WebhookPayload {
+            event,
+            room_id,
+            sent_at: unix_now(),
+            room: msg.room,
+        };
+        let body = |format| {
+            // Not through a `Value`, which can't hold the u128 ids
+            match format {
+                WebhookFormat::Json => serde_json::to_string(&payload),
+                WebhookFormat::Slack => Ok(chatops::slack(event, &payload.room).to_string()),
+                WebhookFormat::Mattermost => {
+                    Ok(chatops::mattermost(event, &payload.room).to_string())
+                }
+            }
+        };
+
+        let server_wide = self.webhooks.iter().map(|webhook| (None, webhook));
+        let room = self
+            .rooms
+            .get(&room_id)
+            .into_iter()
+            .flatten()
+            .map(|s| (Some(s.id), &s.webhook));
+
+        let deliveries = server_wide
+            .chain(room)
+            .filter(|(_, webhook)| webhook.subscribed(event))
+            .filter_map(|(subscription_id, webhook)| match body(webhook.format) {
+                Ok(body) => Some(Delivery {
+                    subscription_id,
+                    webhook: webhook.clone(),
+                    event,
+                    body,
+                    attempt: 1,
+                }),
+                Err(err) => {
+                    println!("Failed to serialize webhook payload: {}", err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for delivery in deliveries {
+            self.deliver(ctx, delivery);
+        }
+    }
+}
+
+pub struct Subscribe {
+    pub room_id: RoomId,
+    pub webhook: NewWebhook,
+}
+
+impl Message for Subscribe {
+    type Result = Result<(), RoomError>;
+}
+
+impl Handler<Subscribe> for WebhookDispatcher {
+    type Result = ResponseActFuture<Self, Result<(), RoomError>>;
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Self::Context) -> Self::Result {
+        let room_id = msg.room_id;
+        let webhook = match Webhook::new(msg.webhook) {
+            Ok(webhook) => webhook,
+            Err(err) => return Box::pin(fut::err(err)),
+        };
+        let check = check_destination(webhook.url.clone(), self.allowed_hosts.clone());
+
+        Box::pin(check.into_actor(self).map(move |res, act, _| {
+            if let Err(err) = res {
+                println!("Rejected webhook {}: {}", webhook.url, err);
+                return Err(RoomError::InvalidWebhookUrl);
+            }
+
+            let subscriptions = act.rooms.entry(room_id).or_default();
+
+            if subscriptions.len() >= MAX_ROOM_WEBHOOKS {
+                return Err(RoomError::TooManyWebhooks);
+            }
+
+            subscriptions.push(Subscription {
+                id: act.next_id,
+                webhook,
+                deliveries: VecDeque::new(),
+            });
+            act.next_id += 1;
+
+            Ok(())
+        }))
+    }
+}
+
+pub struct Unsubscribe {
+    pub room_id: RoomId,
+    pub index: usize,
+}
+
+impl Message for Unsubscribe {
+    type Result = Result<(), RoomError>;
+}
+
+impl Handler<Unsubscribe> for WebhookDispatcher {
+    type Result = Result<(), RoomError>;
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Self::Context) -> Self::Result {
+        match self.rooms.get_mut(&msg.room_id) {
+            Some(subscriptions) if msg.index < subscriptions.len() => {
+                subscriptions.remove(msg.index);
+                Ok(())
+            }
+            _ => Err(RoomError::NoSuchWebhook(msg.index)),
+        }
+    }
+}
+
+/// The webhooks of the room with their recent deliveries
+pub struct ListWebhooks(pub RoomId);
+
+impl Message for ListWebhooks {
+    type Result = Vec<WebhookSummary>;
+}
+
+impl Handler<ListWebhooks> for WebhookDispatcher {
+    type Result = MessageResult<ListWebhooks>;
+
+    fn handle(&mut self, msg: ListWebhooks, _: &mut Self::Context) -> Self::Result {
+        let webhooks = self
+            .rooms
+            .get(&msg.0)
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, s)| WebhookSummary {
+                index,
+                url: s.webhook.url.clone(),
+                events: s.webhook.events.clone(),
+                format: s.webhook.format,
+                deliveries: s.deliveries.iter().cloned().collect(),
+            })
+            .collect();
+
+        MessageResult(webhooks)
+    }
+}
+
+/// The rooms have been removed, together with their webhooks.
+pub struct RoomsRemoved(pub Vec<RoomId>);
+
+impl Message for RoomsRemoved {
+    type Result = ();
+}
+
+impl Handler<RoomsRemoved> for WebhookDispatcher {
+    type Result = ();
+
+    fn handle(&mut self, msg: RoomsRemoved, _: &mut Self::Context) {
+        for room_id in msg.0 {
+            self.rooms.remove(&room_id);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    use actix_web::{
+        http::HeaderMap,
+        web::{Data, Path},
+        App, HttpRequest, HttpResponse, HttpServer,
+    };
+    use planpoker_common::api::WebhookPayload;
+
+    use super::*;
+
+    const ROOM: RoomId = 1;
+
+    #[derive(Clone)]
+    pub(crate) struct Received {
+        pub path: String,
+        pub headers: HeaderMap,
+        pub body: String,
+        pub at: Instant,
+    }
+
+    /// Local server recording the requests it receives
+    #[derive(Clone)]
+    pub(crate) struct Receiver {
+        pub url: String,
+        received: Arc<Mutex<Vec<Received>>>,
+        /// Statuses of the next responses, 200 once they are used up
+        statuses: Arc<Mutex<VecDeque<u16>>>,
+    }
+
+    impl Receiver {
+        pub fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let receiver = Receiver {
+                url: format!("http://{}", listener.local_addr().unwrap()),
+                received: Arc::default(),
+                statuses: Arc::default(),
+            };
+
+            let data = Data::new(receiver.clone());
+            HttpServer::new(move || {
+                App::new()
+                    .app_data(data.clone())
+                    .route("/{path:.*}", web::post().to(receive))
+            })
+            .listen(listener)
+            .unwrap()
+            .run();
+
+            receiver
+        }
+
+        pub fn fail_next(&self, statuses: &[u16]) {
+            self.statuses.lock().unwrap().extend(statuses);
+        }
+
+        pub fn received(&self) -> Vec<Received> {
+            self.received.lock().unwrap().clone()
+        }
+
+        /// Waits until the number of requests have been received.
+        pub async fn wait_for(&self, count: usize) -> Vec<Received> {
+            let started = Instant::now();
+
+            while self.received.lock().unwrap().len() < count {
+                assert!(
+                    started.elapsed() < Duration::from_secs(30),
+                    "{} requests received, expected {}",
+                    self.received().len(),
+                    count
+                );
+                actix_rt::time::delay_for(Duration::from_millis(20)).await;
+            }
+
+            self.received()
+        }
+    }
+
+    async fn receive(
+        receiver: Data<Receiver>,
+        path: Path<String>,
+        req: HttpRequest,
+        body: String,
+    ) -> HttpResponse {
+        receiver.received.lock().unwrap().push(Received {
+            path: format!("/{}", path.into_inner()),
+            headers: req.headers().clone(),
+            body,
+            at: Instant::now(),
+        });
+
+        let status = receiver.statuses.lock().unwrap().pop_front().unwrap_or(200);
+        HttpResponse::build(actix_web::http::StatusCode::from_u16(status).unwrap()).finish()
+    }
+
+    fn header_value<'a>(received: &'a Received, name: &str) -> Option<&'a str> {
+        received.headers.get(name).map(|v| v.to_str().unwrap())
+    }
+
+    fn dispatcher(allowed_hosts: &[&str]) -> Addr<WebhookDispatcher> {
+        let allowed_hosts = allowed_hosts.iter().map(|h| h.to_string()).collect();
+
+        WebhookDispatcher::new(Vec::new(), allowed_hosts).start()
+    }
+
+    async fn subscribe(
+        dispatcher: &Addr<WebhookDispatcher>,
+        url: &str,
+        secret: Option<&str>,
+        events: Vec<WebhookEvent>,
+    ) -> Result<(), RoomError> {
+        let webhook = NewWebhook {
+            url: url.to_owned(),
+            secret: secret.map(str::to_owned),
+            events,
+            format: WebhookFormat::Json,
+        };
+
+        dispatcher
+            .send(Subscribe {
+                room_id: ROOM,
+                webhook,
+            })
+            .await
+            .unwrap()
+    }
+
+    fn publish(dispatcher: &Addr<WebhookDispatcher>, room_id: RoomId, event: WebhookEvent) {
+        dispatcher.do_send(Publish {
+            event,
+            room: RoomInfo::new(room_id, 2),
+        });
+    }
+
+    #[test]
+    fn internal_addresses() {
+        let internal = [
+            "127.0.0.1",
+            "127.1.2.3",
+            "10.1.2.3",
+            "172.16.0.1",
+            "172.31.255.255",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "224.0.0.1",
+            "239.255.255.250",
+            "240.0.0.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "192.0.0.8",
+            "ff02::1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "2002:7f00:1::",
+            "2002:c0a8:101::1",
+            "::127.0.0.1",
+            "::10.0.0.1",
+        ];
+        let public = [
+            "93.184.216.34",
+            "172.32.0.1",
+            "100.128.0.1",
+            "198.20.0.1",
+            "192.0.2.1",
+            "223.255.255.255",
+            "2606:2800:220:1::",
+            "64:ff9b::5db8:d822",
+            "2002:5db8:d822::1",
+            "::93.184.216.34",
+        ];
+
+        for ip in internal.iter() {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in public.iter() {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn webhooks_to_internal_hosts_are_rejected() {
+        let dispatcher = dispatcher(&[]);
+
+        for url in [
+            "http://127.0.0.1:8082/api/v1/rooms",
+            "http://localhost/",
+            "http://[::1]:8080/",
+            "https://10.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://0.0.0.0/",
+            "ftp://example.com/",
+            "http:///no-host",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                subscribe(&dispatcher, url, None, Vec::new()).await,
+                Err(RoomError::InvalidWebhookUrl),
+                "{}",
+                url
+            );
+        }
+
+        let webhooks = dispatcher.send(ListWebhooks(ROOM)).await.unwrap();
+        assert!(webhooks.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn checked_client_connects_only_to_public_or_allowed_addresses() {
+        let receiver = Receiver::start();
+
+        let client = checked_client(Vec::new(), REQUEST_TIMEOUT);
+        let err = client.post(&receiver.url).send().await.unwrap_err();
+        assert!(
+            err.to_string().contains("internal address 127.0.0.1"),
+            "{}",
+            err
+        );
+        let url = receiver.url.replace("127.0.0.1", "localhost");
+        assert!(client.post(&url).send().await.is_err());
+        assert!(receiver.received().is_empty());
+
+        let client = checked_client(vec!["127.0.0.1".to_owned()], REQUEST_TIMEOUT);
+        let res = client.post(&receiver.url).send().await.unwrap();
+        assert!(res.status().is_success());
+        assert_eq!(receiver.received().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn allowed_internal_hosts_are_accepted() {
+        let dispatcher = dispatcher(&["127.0.0.1", "chat.internal"]);
+
+        assert_eq!(
+            subscribe(&dispatcher, "http://127.0.0.1:9/", None, Vec::new()).await,
+            Ok(())
+        );
+        assert_eq!(
+            subscribe(
+                &dispatcher,
+                "https://chat.internal/hooks/1",
+                None,
+                Vec::new()
+            )
+            .await,
+            Ok(())
+        );
+        assert_eq!(
+            subscribe(&dispatcher, "http://localhost:9/", None, Vec::new()).await,
+            Err(RoomError::InvalidWebhookUrl)
+        );
+    }
+
+    #[actix_rt::test]
+    async fn deliveries_are_signed() {
+        let receiver = Receiver::start();
+        let dispatcher = dispatcher(&["127.0.0.1"]);
+        let url = format!("{}/signed", receiver.url);
+        subscribe(&dispatcher, &url, Some("s3cret"), Vec::new())
+            .await
+            .unwrap();
+        let url = format!("{}/unsigned", receiver.url);
+        subscribe(&dispatcher, &url, None, Vec::new())
+            .await
+            .unwrap();
+
+        publish(&dispatcher, ROOM, WebhookEvent::RoundRevealed);
+        let mut received = receiver.wait_for(2).await;
+        received.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let (signed, unsigned) = (&received[0], &received[1]);
+        assert_eq!(signed.path, "/signed");
+        assert_eq!(unsigned.path, "/unsigned");
+
+        let mut mac = HmacSha256::new_varkey(b"s3cret").unwrap();
+        mac.update(signed.body.as_bytes());
+        let expected = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert_eq!(
+            header_value(signed, SIGNATURE_HEADER),
+            Some(format!("sha256={}", expected).as_str())
+        );
+        assert_eq!(header_value(unsigned, SIGNATURE_HEADER), None);
+
+        for received in received.iter() {
+            assert_eq!(header_value(received, EVENT_HEADER), Some("RoundRevealed"));
+            assert_eq!(
+                header_value(received, "content-type"),
+                Some("application/json")
+            );
+
+            let payload: WebhookPayload = serde_json::from_str(&received.body).unwrap();
+            assert_eq!(payload.event, WebhookEvent::RoundRevealed);
+            assert_eq!(payload.room_id, ROOM);
+            assert_eq!(payload.room.id, ROOM);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn only_subscribed_events_of_the_room_are_delivered() {
+        let receiver = Receiver::start();
+        let dispatcher = dispatcher(&["127.0.0.1"]);
+        let events = vec![WebhookEvent::EstimateFinalized];
+        subscribe(&dispatcher, &receiver.url, None, events)
+            .await
+            .unwrap();
+
+        publish(&dispatcher, ROOM, WebhookEvent::RoundRevealed);
+        publish(&dispatcher, ROOM + 1, WebhookEvent::EstimateFinalized);
+        publish(&dispatcher, ROOM, WebhookEvent::RoomClosed);
+        publish(&dispatcher, ROOM, WebhookEvent::EstimateFinalized);
+
+        receiver.wait_for(1).await;
+        actix_rt::time::delay_for(Duration::from_millis(200)).await;
+
+        let received = receiver.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            header_value(&received[0], EVENT_HEADER),
+            Some("EstimateFinalized")
+        );
+
+        let payload: WebhookPayload = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(payload.room_id, ROOM);
+    }
+
+    #[actix_rt::test]
+    async fn failed_deliveries_are_retried_with_backoff_and_logged() {
+        let receiver = Receiver::start();
+        let dispatcher = dispatcher(&["127.0.0.1"]);
+        subscribe(&dispatcher, &receiver.url, None, Vec::new())
+            .await
+            .unwrap();
+        receiver.fail_next(&[500, 503]);
+
+        publish(&dispatcher, ROOM, WebhookEvent::RoomClosed);
+        let received = receiver.wait_for(3).await;
+
+        // Waiting 1 and then 2 seconds, with some leeway for the timers
+        let leeway = Duration::from_millis(100);
+        assert!(received[1].at - received[0].at >= RETRY_DELAY - leeway);
+        assert!(received[2].at - received[1].at >= RETRY_DELAY * 2 - leeway);
+        assert!(received.iter().all(|r| r.body == received[0].body));
+
+        // The last attempt is logged once its response has been handled.
+        actix_rt::time::delay_for(Duration::from_millis(200)).await;
+        let webhooks = dispatcher.send(ListWebhooks(ROOM)).await.unwrap();
+        let deliveries = &webhooks[0].deliveries;
+
+        let log = deliveries
+            .iter()
+            .map(|d| (d.event, d.attempt, d.status, d.error.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            log,
+            [
+                (WebhookEvent::RoomClosed, 1, Some(500), true),
+                (WebhookEvent::RoomClosed, 2, Some(503), true),
+                (WebhookEvent::RoomClosed, 3, Some(200), false),
+            ]
+        );
+        assert!(deliveries[0].error.as_deref().unwrap().contains("500"));
+    }
+
+    #[actix_rt::test]
+    async fn deliveries_to_unreachable_receivers_are_logged() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        drop(listener);
+
+        let dispatcher = dispatcher(&["127.0.0.1"]);
+        subscribe(&dispatcher, &url, None, Vec::new())
+            .await
+            .unwrap();
+        publish(&dispatcher, ROOM, WebhookEvent::RoundRevealed);
+
+        let started = Instant::now();
+        let deliveries = loop {
+            let webhooks = dispatcher.send(ListWebhooks(ROOM)).await.unwrap();
+            if !webhooks[0].deliveries.is_empty() {
+                break webhooks[0].deliveries.clone();
+            }
+            assert!(started.elapsed() < Duration::from_secs(10));
+            actix_rt::time::delay_for(Duration::from_millis(20)).await;
+        };
+
+        assert_eq!(deliveries[0].attempt, 1);
+        assert_eq!(deliveries[0].status, None);
+        assert!(deliveries[0].error.is_some());
+    }
+
+    #[actix_rt::test]
+    async fn server_wide_webhooks_may_be_internal() {
+        let receiver = Receiver::start();
+        let webhook = Webhook::new(NewWebhook {
+            url: receiver.url.clone(),
+            secret: None,
+            events: Vec::new(),
+            format: WebhookFormat::Json,
+        })
+        .unwrap();
+        let dispatcher = WebhookDispatcher::new(vec![webhook], Vec::new()).start();
+
+        publish(&dispatcher, ROOM, WebhookEvent::RoundRevealed);
+
+        receiver.wait_for(1).await;
+    }
+}
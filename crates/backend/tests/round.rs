@@ -0,0 +1,102 @@
+//! Plays a round against the backend binary, with the client used by the command line tool
+
+use std::{
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use planpoker_client::Client;
+use planpoker_common::{RoomInfo, RoomMessage, Vote};
+
+/// Backend process listening on a free port, killed when dropped
+struct Backend {
+    process: Child,
+    url: String,
+}
+
+impl Backend {
+    fn start() -> Self {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .to_string();
+
+        let process = Command::new(env!("CARGO_BIN_EXE_planpoker-backend"))
+            .env("PLANPOKER_ADDRESS", &address)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let backend = Self {
+            process,
+            url: format!("ws://{}/ws/", address),
+        };
+
+        let started = Instant::now();
+        while TcpStream::connect(&address).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the backend did not start"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        backend
+    }
+}
+
+impl Drop for Backend {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn card(info: &RoomInfo, label: &str) -> u32 {
+    info.cards.iter().position(|c| c.as_str() == label).unwrap() as u32
+}
+
+#[tokio::test]
+async fn create_vote_reveal_reset() {
+    let backend = Backend::start();
+    let mut admin = Client::connect(&backend.url).await.unwrap();
+    let mut voter = Client::connect(&backend.url).await.unwrap();
+
+    let room = admin.create_room().await.unwrap();
+    admin.join(room).await.unwrap();
+    let info = voter.join(room).await.unwrap();
+    assert_eq!(info.admin, admin.user_id());
+
+    admin.vote(card(&info, "3")).unwrap();
+    voter.vote(card(&info, "5")).unwrap();
+    admin
+        .wait_for(|msg| match msg {
+            RoomMessage::RoomInfo(info) if info.users.len() == 2 && info.all_voted() => Some(()),
+            _ => None,
+        })
+        .await
+        .unwrap();
+
+    // Only the admin may reveal.
+    assert!(voter.reveal().await.is_err());
+
+    admin.reveal().await.unwrap();
+    let revealed = admin.room_info().await.unwrap();
+    let mut cards = revealed.revealed_cards();
+    cards.sort_unstable();
+    assert_eq!(cards, [card(&info, "3"), card(&info, "5")]);
+    voter
+        .wait_for(|msg| matches!(msg, RoomMessage::CardsRevealed).then(|| ()))
+        .await
+        .unwrap();
+
+    admin.reset().await.unwrap();
+    let reset = admin.room_info().await.unwrap();
+    assert!(reset.users.iter().all(|u| u.vote == Vote::None));
+    voter
+        .wait_for(|msg| matches!(msg, RoomMessage::Reset).then(|| ()))
+        .await
+        .unwrap();
+}
@@ -0,0 +1,105 @@
+use std::{future, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use planpoker_client::{Client, Event};
+use planpoker_common::{RoomId, RoomInfo, RoomMessage};
+use rand::{thread_rng, Rng};
+use tokio::{
+    sync::oneshot,
+    time::{self, Instant},
+};
+
+use crate::distribution::Distribution;
+
+/// What the bots do, shared by all of them
+pub struct Behaviour {
+    pub distribution: Distribution,
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Behaviour {
+    /// When to vote in a round starting now
+    fn vote_time(&self) -> Instant {
+        let delay = if self.max_delay > self.min_delay {
+            thread_rng().gen_range(self.min_delay..self.max_delay)
+        } else {
+            self.min_delay
+        };
+
+        Instant::now() + delay
+    }
+}
+
+/// Virtual user, voting once in every round until told to leave
+pub struct Bot {
+    pub number: usize,
+    pub url: String,
+    pub room: RoomId,
+    pub behaviour: Arc<Behaviour>,
+}
+
+impl Bot {
+    pub async fn run(self, mut leave: oneshot::Receiver<()>) -> Result<()> {
+        let mut client = Client::connect(self.url.as_str()).await?;
+        let mut room = client.join(self.room).await?;
+        let mut vote_at = Some(self.behaviour.vote_time());
+
+        loop {
+            let vote_timer = async {
+                match vote_at {
+                    Some(at) => time::sleep_until(at).await,
+                    None => future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = &mut leave => {
+                    client.leave()?;
+                    return Ok(());
+                }
+                _ = vote_timer => {
+                    vote_at = None;
+                    self.vote(&client, &room)?;
+                }
+                event = client.next_event() => match event {
+                    Some(Event::Message(RoomMessage::RoomInfo(info))) if info.id == self.room => {
+                        room = info;
+                    }
+                    // Every new round is voted on, including the one the vote was lost in.
+                    Some(Event::Message(RoomMessage::Reset))
+                    | Some(Event::Message(RoomMessage::Revote))
+                    | Some(Event::Connected) => {
+                        vote_at = Some(self.behaviour.vote_time());
+                    }
+                    Some(Event::Message(RoomMessage::NoSuchRoom(room))) => {
+                        return Err(anyhow!("room {} is gone", room));
+                    }
+                    Some(Event::Disconnected(reason)) => {
+                        vote_at = None;
+                        println!("Bot {} disconnected: {}", self.number, reason);
+                    }
+                    Some(_) => {}
+                    None => return Err(anyhow!("connection closed")),
+                },
+            }
+        }
+    }
+
+    fn vote(&self, client: &Client, room: &RoomInfo) -> Result<()> {
+        if !room.state.accepts_votes() {
+            return Ok(());
+        }
+
+        if let Some(card) = self.behaviour.distribution.pick(&room.cards) {
+            client.vote(card)?;
+            println!(
+                "Bot {} voted {}",
+                self.number,
+                room.cards[card as usize].as_str()
+            );
+        }
+
+        Ok(())
+    }
+}
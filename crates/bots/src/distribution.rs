@@ -0,0 +1,246 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use planpoker_common::{Card, CardValue};
+use rand::{
+    distributions::{Distribution as _, WeightedIndex},
+    thread_rng, Rng,
+};
+
+/// How the bots pick their cards
+#[derive(Debug)]
+pub enum Distribution {
+    /// Every card of the deck is equally likely
+    Uniform,
+    /// Bell curve around a card, the spread being the standard deviation in cards
+    Normal { center: String, spread: f64 },
+    /// Relative weights of the cards in the order of the deck, missing ones are never picked
+    Weights(Vec<f64>),
+}
+
+impl Distribution {
+    /// Picks the index of a card of the deck.
+    pub fn pick(&self, cards: &[Card]) -> Option<u32> {
+        let weights = self.weights(cards);
+
+        // Fall back to a uniform pick if the weights leave nothing to choose from.
+        let index = match WeightedIndex::new(&weights) {
+            Ok(index) => index.sample(&mut thread_rng()),
+            Err(_) if !cards.is_empty() => thread_rng().gen_range(0..cards.len()),
+            Err(_) => return None,
+        };
+
+        Some(index as u32)
+    }
+
+    fn weights(&self, cards: &[Card]) -> Vec<f64> {
+        match self {
+            Distribution::Uniform => vec![1.0; cards.len()],
+            Distribution::Normal { center, spread } => {
+                let center = find_card(cards, center).unwrap_or(cards.len() / 2) as f64;
+
+                (0..cards.len())
+                    .map(|i| {
+                        let distance = (i as f64 - center) / spread;
+                        (-distance * distance / 2.0).exp()
+                    })
+                    .collect()
+            }
+            Distribution::Weights(weights) => (0..cards.len())
+                .map(|i| weights.get(i).copied().unwrap_or(0.0))
+                .collect(),
+        }
+    }
+}
+
+/// Parses `uniform`, `normal:<card>[:<spread>]` or `weights:<weight>,<weight>,...`.
+impl FromStr for Distribution {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+
+        match (kind, args) {
+            ("uniform", None) => Ok(Distribution::Uniform),
+            ("normal", Some(args)) => {
+                let mut args = args.splitn(2, ':');
+                let center = args.next().unwrap_or_default().to_owned();
+                let spread = match args.next() {
+                    Some(spread) => spread.parse::<f64>()?,
+                    None => 1.0,
+                };
+
+                if !spread.is_finite() || spread <= 0.0 {
+                    return Err(anyhow!("the spread must be positive"));
+                }
+
+                Ok(Distribution::Normal { center, spread })
+            }
+            ("weights", Some(args)) => {
+                let weights = args
+                    .split(',')
+                    .map(|w| w.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(Distribution::Weights(weights))
+            }
+            _ => Err(anyhow!(
+                "expected uniform, normal:<card>[:<spread>] or weights:<weight>,..."
+            )),
+        }
+    }
+}
+
+/// Index of the card with the label, or else with the value
+fn find_card(cards: &[Card], card: &str) -> Option<usize> {
+    let by_label = cards
+        .iter()
+        .position(|c| c.as_str().eq_ignore_ascii_case(card));
+    let by_value = || {
+        let value = card.parse::<CardValue>().ok()?;
+        cards.iter().position(|c| c.value() == Some(value))
+    };
+
+    by_label.or_else(by_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use planpoker_common::Deck;
+
+    use super::*;
+
+    const SAMPLES: usize = 4000;
+
+    fn parse(s: &str) -> Distribution {
+        s.parse().unwrap()
+    }
+
+    /// How often each card is picked in `SAMPLES` picks
+    fn sample(distribution: &Distribution, cards: &[Card]) -> Vec<usize> {
+        let mut counts = vec![0; cards.len()];
+        for _ in 0..SAMPLES {
+            counts[distribution.pick(cards).unwrap() as usize] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn parses_the_distributions() {
+        assert!(matches!(parse("uniform"), Distribution::Uniform));
+        assert!(matches!(
+            parse("normal:5"),
+            Distribution::Normal { center, spread } if center == "5" && spread == 1.0
+        ));
+        assert!(matches!(
+            parse("normal:½:2.5"),
+            Distribution::Normal { center, spread } if center == "½" && spread == 2.5
+        ));
+        assert!(matches!(
+            parse("weights:1, 0,2.5"),
+            Distribution::Weights(weights) if weights == [1.0, 0.0, 2.5]
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_distributions() {
+        for invalid in [
+            "",
+            "gaussian",
+            "uniform:3",
+            "normal",
+            "normal:5:0",
+            "normal:5:-1",
+            "normal:5:inf",
+            "normal:5:wide",
+            "weights",
+            "weights:1,,2",
+            "weights:one",
+        ]
+        .iter()
+        {
+            assert!(invalid.parse::<Distribution>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn normal_weights_peak_at_the_center() {
+        let cards = Deck::ModifiedFibonacci.cards();
+
+        // By label and by value
+        for center in ["5", "5.0"].iter() {
+            let distribution = Distribution::Normal {
+                center: center.to_string(),
+                spread: 1.0,
+            };
+            let weights = distribution.weights(&cards);
+
+            assert_eq!(weights.len(), cards.len());
+            assert_eq!(weights[5], 1.0);
+            assert!((weights[4] - weights[6]).abs() < 1e-12);
+            assert!(weights[4] > weights[3] && weights[3] > weights[2]);
+        }
+
+        // Unknown centers fall back to the middle of the deck.
+        let distribution = parse("normal:7");
+        let weights = distribution.weights(&cards);
+        assert_eq!(weights[cards.len() / 2], 1.0);
+    }
+
+    #[test]
+    fn weights_follow_the_deck() {
+        let cards = Deck::Linear.cards();
+        let ones = vec![1.0; cards.len()];
+
+        assert_eq!(Distribution::Uniform.weights(&cards), ones);
+
+        // Missing weights are zero, extra ones are ignored.
+        let weights = parse("weights:1,2").weights(&cards);
+        assert_eq!(weights[..2], [1.0, 2.0]);
+        assert!(weights[2..].iter().all(|&weight| weight == 0.0));
+
+        let extra = vec!["1"; cards.len() + 1].join(",");
+        let weights = parse(&format!("weights:{}", extra)).weights(&cards);
+        assert_eq!(weights, ones);
+    }
+
+    #[test]
+    fn picks_follow_the_weights() {
+        let cards = Deck::Linear.cards();
+
+        let counts = sample(&parse("weights:0,3,0,1"), &cards);
+        assert_eq!(counts[0] + counts[2] + counts[4..].iter().sum::<usize>(), 0);
+        // 3 to 1, the bounds are more than seven standard deviations away
+        let share = counts[1] as f64 / SAMPLES as f64;
+        assert!((0.7..0.8).contains(&share), "{:?}", counts);
+
+        let counts = sample(&parse("normal:5:0.5"), &cards);
+        assert!(counts[4] > SAMPLES / 2, "{:?}", counts);
+        assert_eq!(counts[0] + counts[8], 0, "{:?}", counts);
+    }
+
+    #[test]
+    fn picks_are_uniform_without_usable_weights() {
+        let cards = Deck::Linear.cards();
+
+        for distribution in [Distribution::Uniform, parse("weights:0,0")].iter() {
+            let counts = sample(distribution, &cards);
+            assert!(
+                counts
+                    .iter()
+                    .all(|&count| count > SAMPLES / cards.len() / 2),
+                "{:?}",
+                counts
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_is_picked_from_an_empty_deck() {
+        assert_eq!(Distribution::Uniform.pick(&[]), None);
+        assert_eq!(parse("normal:5").pick(&[]), None);
+    }
+}
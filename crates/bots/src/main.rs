@@ -0,0 +1,141 @@
+mod bot;
+mod distribution;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use planpoker_common::RoomId;
+use structopt::StructOpt;
+use tokio::{
+    io::{self, AsyncBufReadExt, BufReader},
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+use bot::{Behaviour, Bot};
+use distribution::Distribution;
+
+/// Fills a room with virtual users voting at random, for demos and load tests.
+///
+/// While running, `add [count]` adds bots, `leave [count]` makes the last bots leave,
+/// and `quit` or Ctrl-C makes all of them leave.
+#[derive(StructOpt)]
+#[structopt(name = "planpoker-bots")]
+struct Options {
+    /// Websocket endpoint of the backend
+    #[structopt(long, env = "PLANPOKER_URL", default_value = "ws://127.0.0.1:8082/ws/")]
+    url: String,
+    /// How many bots join the room
+    #[structopt(short = "n", long, default_value = "5")]
+    count: usize,
+    /// How the cards are picked: `uniform`, `normal:<card>[:<spread>]` around a card,
+    /// or `weights:<weight>,...` per card of the deck
+    #[structopt(long, default_value = "uniform")]
+    distribution: Distribution,
+    /// Shortest time in seconds before voting in a new round
+    #[structopt(long, default_value = "1")]
+    min_delay: f64,
+    /// Longest time in seconds before voting in a new round
+    #[structopt(long, default_value = "10")]
+    max_delay: f64,
+    room: RoomId,
+}
+
+struct RunningBot {
+    leave: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::from_args();
+
+    if let Err(err) = run(options).await {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(options: Options) -> Result<()> {
+    let behaviour = Arc::new(Behaviour {
+        distribution: options.distribution,
+        min_delay: Duration::from_secs_f64(options.min_delay.max(0.0)),
+        max_delay: Duration::from_secs_f64(options.max_delay.max(0.0)),
+    });
+
+    let (url, room) = (options.url, options.room);
+    let spawn = |number| {
+        let bot = Bot {
+            number,
+            url: url.clone(),
+            room,
+            behaviour: behaviour.clone(),
+        };
+        let (leave, left) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            if let Err(err) = bot.run(left).await {
+                eprintln!("Bot {} stopped: {:#}", number, err);
+            }
+        });
+
+        RunningBot { leave, task }
+    };
+
+    let mut bots = (0..options.count).map(spawn).collect::<Vec<_>>();
+    let mut next_number = options.count;
+    println!("{} bots joining room {}", bots.len(), room);
+
+    let mut commands = BufReader::new(io::stdin()).lines();
+    let mut stdin_open = true;
+
+    loop {
+        let command = tokio::select! {
+            line = commands.next_line(), if stdin_open => match line? {
+                Some(line) => line,
+                // Keep running without input, such as in the background.
+                None => {
+                    stdin_open = false;
+                    continue;
+                }
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        };
+
+        let mut words = command.split_whitespace();
+        let (command, count) = (words.next(), words.next().map(str::parse::<usize>));
+
+        match (command, count.unwrap_or(Ok(1))) {
+            (Some("add"), Ok(count)) => {
+                bots.extend((next_number..next_number + count).map(spawn));
+                next_number += count;
+                println!("{} bots in the room", bots.len());
+            }
+            (Some("leave"), Ok(count)) => {
+                let count = count.min(bots.len());
+                leave(bots.split_off(bots.len() - count)).await;
+                println!("{} bots in the room", bots.len());
+            }
+            (Some("quit"), _) => break,
+            (None, _) => {}
+            _ => eprintln!("Commands are add [count], leave [count] and quit"),
+        }
+    }
+
+    leave(bots).await;
+    Ok(())
+}
+
+async fn leave(bots: Vec<RunningBot>) {
+    let tasks = bots
+        .into_iter()
+        .map(|bot| {
+            // The bot may have stopped already.
+            let _ = bot.leave.send(());
+            bot.task
+        })
+        .collect::<Vec<_>>();
+
+    for task in tasks {
+        let _ = task.await;
+    }
+}
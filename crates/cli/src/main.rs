@@ -0,0 +1,387 @@
+use std::{env, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use planpoker_client::{Client, Event};
+use planpoker_common::{
+    serialize_json, CardValue, RoomId, RoomInfo, RoomMessage, RoomRequest, UserId, Vote,
+};
+use structopt::StructOpt;
+
+/// Planning poker from the terminal
+#[derive(StructOpt)]
+#[structopt(name = "planpoker")]
+struct Options {
+    /// Websocket endpoint of the backend
+    #[structopt(long, env = "PLANPOKER_URL", default_value = "ws://127.0.0.1:8082/ws/")]
+    url: String,
+    /// File keeping the session, so that all commands act as the same user.
+    /// Use different files to act as different users. Defaults to `~/.planpoker-session`.
+    #[structopt(long, env = "PLANPOKER_SESSION_FILE", parse(from_os_str))]
+    session: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Creates a room and prints its id
+    Create,
+    /// Joins the room and prints what happens in it until interrupted
+    Join { room: RoomId },
+    /// Votes with the card of the given label or value, then waits for the votes to be revealed.
+    /// The vote is withdrawn when leaving the room, so the command only exits after the reveal.
+    Vote { room: RoomId, card: String },
+    /// Reveals the votes and prints them, only allowed for the admin of the room
+    Reveal { room: RoomId },
+    /// Discards the votes and starts a new round, only allowed for the admin of the room
+    Reset { room: RoomId },
+    /// Joins the room and prints every message from the server as a line of JSON
+    Stream { room: RoomId },
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::from_args();
+
+    if let Err(err) = run(options).await {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(options: Options) -> Result<()> {
+    let session = options.session.unwrap_or_else(|| {
+        env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".planpoker-session")
+    });
+    // Every command is a single connection, failing rather than reconnecting.
+    let mut client = Client::builder(options.url)
+        .session_file(session)
+        .reconnect(false)
+        .connect()
+        .await?;
+
+    match options.command {
+        Command::Create => println!("{}", client.create_room().await?),
+        Command::Join { room } => {
+            client.join(room).await?;
+            follow(&mut client).await?;
+        }
+        Command::Vote { room, card } => {
+            let info = client.join(room).await?;
+            let index = find_card(&info, &card).ok_or_else(|| {
+                let cards = info.cards.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+                anyhow!(
+                    "no card {:?} in the deck, the cards are {}",
+                    card,
+                    cards.join(" ")
+                )
+            })?;
+
+            client.vote(index)?;
+            println!(
+                "Voted {}, waiting for the votes to be revealed",
+                info.cards[index as usize].as_str()
+            );
+
+            let revealed = client
+                .wait_for(|msg| match msg {
+                    RoomMessage::CardsRevealed => Some(true),
+                    RoomMessage::Reset | RoomMessage::Revote => Some(false),
+                    _ => None,
+                })
+                .await?;
+
+            if !revealed {
+                return Err(anyhow!(
+                    "the votes were discarded before they were revealed"
+                ));
+            }
+
+            print_results(&client.room_info().await?);
+        }
+        Command::Reveal { room } => {
+            client.join(room).await?;
+            client.reveal().await?;
+            print_results(&client.room_info().await?);
+        }
+        Command::Reset { room } => {
+            client.join(room).await?;
+            client.reset().await?;
+            println!("Votes reset");
+        }
+        Command::Stream { room } => {
+            client.send(RoomRequest::JoinRoom(room))?;
+
+            while let Some(event) = client.next_event().await {
+                if let Event::Message(msg) = event {
+                    println!("{}", serialize_json(&msg)?);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the state of the room whenever it changes, and the votes once they are revealed.
+async fn follow(client: &mut Client) -> Result<()> {
+    let mut room: Option<RoomInfo> = None;
+    let mut last_status = String::new();
+    let mut revealed = false;
+
+    while let Some(event) = client.next_event().await {
+        let msg = match event {
+            Event::Message(msg) => msg,
+            Event::Disconnected(reason) => return Err(anyhow!("disconnected: {}", reason)),
+            Event::Connected => continue,
+        };
+
+        match msg {
+            RoomMessage::RoomInfo(info) => {
+                let status = status(&info);
+                if status != last_status {
+                    println!("{}", status);
+                    last_status = status;
+                }
+
+                if revealed {
+                    print_results(&info);
+                    revealed = false;
+                }
+
+                room = Some(info);
+            }
+            RoomMessage::CardsRevealed => revealed = true,
+            RoomMessage::Reset => println!("Votes reset"),
+            RoomMessage::Revote => println!("Voting again"),
+            RoomMessage::EstimateSet(estimate) => println!("Estimate set: {}", estimate),
+            RoomMessage::TimerExpired => println!("Time is up"),
+            RoomMessage::Nudge => println!("The admin is waiting for your vote"),
+            RoomMessage::Chat(message) => {
+                let name = match (message.name.as_ref(), room.as_ref()) {
+                    (Some(name), _) => name.clone(),
+                    (None, Some(room)) => user_name(room, message.user_id),
+                    (None, None) => "Someone".to_owned(),
+                };
+                println!("{}: {}", name, message.text);
+            }
+            RoomMessage::Error(err) => eprintln!("Error: {:?}", err),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn status(room: &RoomInfo) -> String {
+    let voted = room
+        .users
+        .iter()
+        .filter(|u| u.vote.value().is_some())
+        .count();
+    let story = match room.current_story() {
+        Some(story) => format!(" on {:?}", story.title),
+        None => String::new(),
+    };
+
+    format!(
+        "{:?}{}: {} of {} voted",
+        room.state,
+        story,
+        voted,
+        room.users.len()
+    )
+}
+
+fn print_results(room: &RoomInfo) {
+    for line in results(room) {
+        println!("{}", line);
+    }
+}
+
+/// The revealed votes, per card in anonymous rooms, with their average and the suggested estimate
+fn results(room: &RoomInfo) -> Vec<String> {
+    let mut lines = Vec::new();
+    let label = |card: u32| {
+        room.cards
+            .get(card as usize)
+            .map(|c| c.as_str())
+            .unwrap_or("?")
+    };
+
+    match room.distribution.as_ref() {
+        Some(distribution) => {
+            for count in distribution {
+                lines.push(format!("  {}: {}", label(count.card), count.count));
+            }
+        }
+        None => {
+            for user in room.users.iter() {
+                if let Vote::Revealed(card) = user.vote {
+                    lines.push(format!(
+                        "  {}: {}",
+                        user_name(room, user.user_id),
+                        label(card)
+                    ));
+                }
+            }
+        }
+    }
+
+    let values = room
+        .revealed_cards()
+        .into_iter()
+        .filter_map(|card| room.cards.get(card as usize)?.value())
+        .map(CardValue::as_f64)
+        .collect::<Vec<_>>();
+
+    if !values.is_empty() {
+        lines.push(format!(
+            "Avg: {:.1}",
+            values.iter().sum::<f64>() / values.len() as f64
+        ));
+    }
+
+    if let Some(suggested) = room.suggested_estimate() {
+        lines.push(format!("Suggested: {}", suggested));
+    }
+
+    lines
+}
+
+/// Same fallback as the frontend for users without a name
+fn user_name(room: &RoomInfo, user_id: UserId) -> String {
+    match room.users.iter().position(|u| u.user_id == user_id) {
+        Some(i) => room.users[i]
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("User {}", i)),
+        None => "Someone".to_owned(),
+    }
+}
+
+/// Index of the card with the label, or else with the value, such as `0.5` for `½`.
+fn find_card(room: &RoomInfo, card: &str) -> Option<u32> {
+    let card = card.trim();
+    let by_label = room
+        .cards
+        .iter()
+        .position(|c| c.as_str().eq_ignore_ascii_case(card));
+    let by_value = || {
+        let value = card.parse::<CardValue>().ok()?;
+        room.cards.iter().position(|c| c.value() == Some(value))
+    };
+
+    by_label.or_else(by_value).map(|i| i as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use planpoker_common::{CardCount, Deck, RoomState, Story};
+
+    use super::*;
+
+    fn room(deck: Deck) -> RoomInfo {
+        let mut room = RoomInfo::new(1, 10);
+        room.cards = deck.cards();
+        room.add_user(10, Some("Alice".to_owned()));
+        room.add_user(11, None);
+        room.add_user(12, Some("Carol".to_owned()));
+        room
+    }
+
+    #[test]
+    fn cards_are_found_by_label() {
+        let room = room(Deck::ModifiedFibonacci);
+
+        assert_eq!(find_card(&room, "5"), Some(5));
+        assert_eq!(find_card(&room, " 13 "), Some(7));
+        assert_eq!(find_card(&room, "☕"), Some(11));
+
+        let room = self::room(Deck::TShirt);
+        assert_eq!(find_card(&room, "xl"), Some(4));
+    }
+
+    #[test]
+    fn cards_are_found_by_value() {
+        let room = room(Deck::ModifiedFibonacci);
+
+        assert_eq!(find_card(&room, "½"), Some(1));
+        assert_eq!(find_card(&room, "0.5"), Some(1));
+        assert_eq!(find_card(&room, "5.0"), Some(5));
+
+        // T-shirt sizes are valued like the Fibonacci deck.
+        let room = self::room(Deck::TShirt);
+        assert_eq!(find_card(&room, "8"), Some(4));
+    }
+
+    #[test]
+    fn unknown_cards_are_not_found() {
+        let room = room(Deck::ModifiedFibonacci);
+
+        assert_eq!(find_card(&room, "4"), None);
+        assert_eq!(find_card(&room, "0.25"), None);
+        assert_eq!(find_card(&room, "XL"), None);
+        assert_eq!(find_card(&room, ""), None);
+    }
+
+    #[test]
+    fn status_counts_the_votes() {
+        let mut room = room(Deck::Linear);
+        assert_eq!(status(&room), "Waiting: 0 of 3 voted");
+
+        room.state = RoomState::Voting;
+        room.users[0].vote = Vote::Hidden(2);
+        room.users[2].vote = Vote::Hidden(4);
+        room.stories.push(Story::new("Login page"));
+        room.current_story = Some(0);
+        assert_eq!(status(&room), "Voting on \"Login page\": 2 of 3 voted");
+    }
+
+    #[test]
+    fn results_list_the_votes_by_user() {
+        let mut room = room(Deck::ModifiedFibonacci);
+        room.state = RoomState::Revealed;
+        room.users[0].vote = Vote::Revealed(4);
+        room.users[1].vote = Vote::Revealed(5);
+        // Coffee breaks are left out of the average.
+        room.users[2].vote = Vote::Revealed(11);
+
+        assert_eq!(
+            results(&room),
+            [
+                "  Alice: 3",
+                "  User 1: 5",
+                "  Carol: ☕",
+                "Avg: 4.0",
+                "Suggested: 5",
+            ]
+        );
+    }
+
+    #[test]
+    fn results_of_anonymous_rooms_list_the_distribution() {
+        let mut room = room(Deck::Linear);
+        room.state = RoomState::Revealed;
+        room.distribution = Some(vec![
+            CardCount { card: 0, count: 2 },
+            CardCount { card: 3, count: 1 },
+        ]);
+
+        assert_eq!(
+            results(&room),
+            ["  1: 2", "  4: 1", "Avg: 2.0", "Suggested: 1"]
+        );
+    }
+
+    #[test]
+    fn results_without_votes_have_no_average() {
+        let mut room = room(Deck::ModifiedFibonacci);
+        room.users[0].vote = Vote::Revealed(11);
+
+        assert_eq!(results(&room), ["  Alice: ☕"]);
+    }
+}
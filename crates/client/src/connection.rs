@@ -0,0 +1,122 @@
+use std::{io, path::PathBuf, time::Instant};
+
+use futures_util::{SinkExt, StreamExt};
+use planpoker_common::{deserialize_json, serialize_json, RoomMessage, RoomRequest};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::header, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::Error;
+
+const SESSION_COOKIE: &str = "session";
+
+/// Session token identifying the user, kept across reconnects and optionally in a file
+pub(crate) struct Session {
+    token: Option<String>,
+    file: Option<PathBuf>,
+}
+
+impl Session {
+    /// Reads the token from the file, a missing file starts a new session.
+    pub async fn load(file: Option<PathBuf>) -> Self {
+        let token = match file.as_ref() {
+            Some(file) => tokio::fs::read_to_string(file)
+                .await
+                .ok()
+                .map(|token| token.trim().to_owned())
+                .filter(|token| !token.is_empty()),
+            None => None,
+        };
+
+        Self { token, file }
+    }
+
+    async fn update(&mut self, token: &str) -> Result<(), Error> {
+        if let Some(file) = self.file.as_ref() {
+            tokio::fs::write(file, token)
+                .await
+                .map_err(Error::Session)?;
+        }
+
+        self.token = Some(token.to_owned());
+        Ok(())
+    }
+}
+
+/// Websocket connection to the backend, speaking the same JSON protocol as the frontend
+pub(crate) struct Connection {
+    ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// When anything was last received, the server pings regularly
+    last_seen: Instant,
+}
+
+impl Connection {
+    /// Connects with the session, which is updated when the server issues a new token.
+    pub async fn connect(url: &str, session: &mut Session) -> Result<Self, Error> {
+        let mut request = url.into_client_request()?;
+
+        if let Some(token) = session.token.as_ref() {
+            let cookie = format!("{}={}", SESSION_COOKIE, token)
+                .parse()
+                .map_err(|_| {
+                    Error::Session(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid session token",
+                    ))
+                })?;
+            request.headers_mut().insert(header::COOKIE, cookie);
+        }
+
+        let (ws, response) = tokio_tungstenite::connect_async(request).await?;
+
+        let new_token = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .filter_map(|cookie| cookie.to_str().ok())
+            .filter_map(|cookie| cookie.split(';').next())
+            .find_map(|cookie| cookie.strip_prefix(SESSION_COOKIE)?.strip_prefix('='));
+
+        if let Some(token) = new_token {
+            session.update(token).await?;
+        }
+
+        Ok(Self {
+            ws,
+            last_seen: Instant::now(),
+        })
+    }
+
+    pub async fn send(&mut self, request: &RoomRequest) -> Result<(), Error> {
+        self.ws
+            .send(Message::Text(serialize_json(request)?))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn ping(&mut self) -> Result<(), Error> {
+        self.ws.send(Message::Ping(Vec::new())).await?;
+        Ok(())
+    }
+
+    /// Next message from the server, `None` once the connection is closed.
+    /// Pings are answered while waiting.
+    pub async fn next(&mut self) -> Result<Option<RoomMessage>, Error> {
+        while let Some(msg) = self.ws.next().await {
+            let msg = msg?;
+            self.last_seen = Instant::now();
+
+            if let Message::Text(text) = msg {
+                return Ok(Some(deserialize_json(&text)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+}
@@ -0,0 +1,56 @@
+use std::{fmt, io};
+
+use planpoker_common::{RoomError, RoomId};
+use tokio_tungstenite::tungstenite;
+
+#[derive(Debug)]
+pub enum Error {
+    WebSocket(Box<tungstenite::Error>),
+    Json(serde_json::Error),
+    /// Reading or writing the session file failed
+    Session(io::Error),
+    /// The server rejected the request
+    Room(RoomError),
+    NoSuchRoom(RoomId),
+    /// The connection was lost while waiting for a response
+    Disconnected(String),
+    /// The connection is closed for good, either by the server or because reconnecting is disabled
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::WebSocket(err) => write!(f, "websocket error: {}", err),
+            Error::Json(err) => write!(f, "invalid message: {}", err),
+            Error::Session(err) => write!(f, "failed to access the session file: {}", err),
+            Error::Room(err) => write!(f, "rejected by the server: {:?}", err),
+            Error::NoSuchRoom(room) => write!(f, "no room with id {}", room),
+            Error::Disconnected(reason) => write!(f, "disconnected: {}", reason),
+            Error::Closed => f.write_str("connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::WebSocket(err) => Some(err.as_ref()),
+            Error::Json(err) => Some(err),
+            Error::Session(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<tungstenite::Error> for Error {
+    fn from(err: tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(err))
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
@@ -0,0 +1,583 @@
+//! Async client for the planning poker backend, for bots, command line tools and tests.
+//!
+//! The connection is kept in a background task, which answers heartbeats and reconnects when
+//! the connection drops, joining the room again with the same session.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), planpoker_client::Error> {
+//! let mut client = planpoker_client::Client::connect("ws://127.0.0.1:8082/ws/").await?;
+//! let room = client.create_room().await?;
+//! let info = client.join(room).await?;
+//! client.vote(0)?;
+//!
+//! while let Some(event) = client.next_event().await {
+//!     println!("{:?}", event);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod connection;
+mod error;
+
+use std::{path::PathBuf, time::Duration};
+
+use planpoker_common::{RoomId, RoomInfo, RoomMessage, RoomRequest, UserId};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use connection::{Connection, Session};
+
+pub use error::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How often the server is pinged
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How long without anything from the server before the connection is considered dead
+const SERVER_TIMEOUT: Duration = Duration::from_secs(15);
+/// Delay before the first reconnect attempt, doubled for every failed one
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+pub enum Event {
+    /// Connected again after the connection was lost
+    Connected,
+    /// The connection was lost or could not be established.
+    /// The server drops the vote together with the user, so it has to be cast again.
+    Disconnected(String),
+    Message(RoomMessage),
+}
+
+pub struct ClientBuilder {
+    url: String,
+    session_file: Option<PathBuf>,
+    reconnect: bool,
+    heartbeat: Heartbeat,
+}
+
+/// How often the server is pinged and how long it may stay silent, shortened by the tests
+#[derive(Clone, Copy)]
+struct Heartbeat {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl ClientBuilder {
+    /// Keeps the session token in the file, so that every client using it is the same user.
+    /// Without a file, the session only lasts as long as the client.
+    pub fn session_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.session_file = Some(file.into());
+        self
+    }
+
+    /// Whether to reconnect when the connection drops, enabled by default.
+    /// Without reconnecting, the event stream ends with the connection.
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Connects to the server, failing if the first attempt does not succeed.
+    pub async fn connect(self) -> Result<Client> {
+        let mut session = Session::load(self.session_file).await;
+        let conn = Connection::connect(&self.url, &mut session).await?;
+
+        let (requests, request_rx) = mpsc::unbounded_channel();
+        let (event_tx, events) = mpsc::unbounded_channel();
+
+        tokio::spawn(run(
+            conn,
+            self.url,
+            session,
+            self.reconnect,
+            self.heartbeat,
+            request_rx,
+            event_tx,
+        ));
+
+        let mut client = Client {
+            requests,
+            events,
+            user_id: None,
+        };
+
+        client.send(RoomRequest::UserInfo)?;
+        client
+            .wait_for(|msg| matches!(msg, RoomMessage::UserInfo(_)).then(|| ()))
+            .await?;
+
+        Ok(client)
+    }
+}
+
+/// Connection to the server as one user
+pub struct Client {
+    requests: UnboundedSender<RoomRequest>,
+    events: UnboundedReceiver<Event>,
+    user_id: Option<UserId>,
+}
+
+impl Client {
+    pub fn builder(url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            url: url.into(),
+            session_file: None,
+            reconnect: true,
+            heartbeat: Heartbeat {
+                interval: HEARTBEAT_INTERVAL,
+                timeout: SERVER_TIMEOUT,
+            },
+        }
+    }
+
+    /// Connects as a new user, reconnecting when the connection drops.
+    pub async fn connect(url: impl Into<String>) -> Result<Self> {
+        Self::builder(url).connect().await
+    }
+
+    pub fn user_id(&self) -> UserId {
+        self.user_id.expect("user id is requested on connect")
+    }
+
+    /// Sender for requests, for sending while waiting for events elsewhere
+    pub fn sender(&self) -> UnboundedSender<RoomRequest> {
+        self.requests.clone()
+    }
+
+    /// Sends the request without waiting for a response.
+    /// Requests sent while disconnected are delivered after reconnecting.
+    pub fn send(&self, request: RoomRequest) -> Result<()> {
+        self.requests.send(request).map_err(|_| Error::Closed)
+    }
+
+    /// Next event, `None` once the connection is closed for good.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        let event = self.events.recv().await?;
+
+        if let Event::Message(RoomMessage::UserInfo(user_id)) = event {
+            self.user_id = Some(user_id);
+        }
+
+        Some(event)
+    }
+
+    /// Waits for a message the function returns a value for, discarding the others.
+    /// Fails on errors from the server and when the connection is lost.
+    pub async fn wait_for<T, F>(&mut self, mut f: F) -> Result<T>
+    where
+        F: FnMut(&RoomMessage) -> Option<T>,
+    {
+        loop {
+            match self.next_event().await {
+                Some(Event::Message(RoomMessage::Error(err))) => return Err(Error::Room(err)),
+                Some(Event::Message(msg)) => {
+                    if let Some(value) = f(&msg) {
+                        return Ok(value);
+                    }
+                }
+                Some(Event::Disconnected(reason)) => return Err(Error::Disconnected(reason)),
+                Some(Event::Connected) => {}
+                None => return Err(Error::Closed),
+            }
+        }
+    }
+
+    /// Creates a room with the user as its admin, without joining it.
+    pub async fn create_room(&mut self) -> Result<RoomId> {
+        self.send(RoomRequest::CreateRoom)?;
+        self.wait_for(|msg| match msg {
+            RoomMessage::RoomCreated(room) => Some(*room),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Joins the room and waits for its info. The room is joined again after reconnecting.
+    pub async fn join(&mut self, room: RoomId) -> Result<RoomInfo> {
+        self.send(RoomRequest::JoinRoom(room))?;
+        self.wait_for(|msg| match msg {
+            RoomMessage::RoomInfo(info) if info.id == room => Some(Ok(info.clone())),
+            RoomMessage::NoSuchRoom(room) => Some(Err(Error::NoSuchRoom(*room))),
+            _ => None,
+        })
+        .await?
+    }
+
+    pub fn leave(&self) -> Result<()> {
+        self.send(RoomRequest::LeaveRoom)
+    }
+
+    /// Votes with the card at the index of the deck, voting for the same card again withdraws
+    /// the vote.
+    pub fn vote(&self, card: u32) -> Result<()> {
+        self.send(RoomRequest::Vote(card))
+    }
+
+    /// Reveals the votes, only allowed for the admin of the room.
+    pub async fn reveal(&mut self) -> Result<()> {
+        self.send(RoomRequest::Reveal)?;
+        self.wait_for(|msg| matches!(msg, RoomMessage::CardsRevealed).then(|| ()))
+            .await
+    }
+
+    /// Discards the votes and starts a new round, only allowed for the admin of the room.
+    pub async fn reset(&mut self) -> Result<()> {
+        self.send(RoomRequest::Reset)?;
+        self.wait_for(|msg| matches!(msg, RoomMessage::Reset).then(|| ()))
+            .await
+    }
+
+    /// Waits for the next info of the room, such as the votes after they were revealed.
+    pub async fn room_info(&mut self) -> Result<RoomInfo> {
+        self.wait_for(|msg| match msg {
+            RoomMessage::RoomInfo(info) => Some(info.clone()),
+            _ => None,
+        })
+        .await
+    }
+}
+
+/// Forwards requests and messages, reconnecting until the client is dropped.
+async fn run(
+    conn: Connection,
+    url: String,
+    mut session: Session,
+    reconnect: bool,
+    heartbeat: Heartbeat,
+    mut requests: UnboundedReceiver<RoomRequest>,
+    events: UnboundedSender<Event>,
+) {
+    let mut conn = Some(conn);
+    let mut room = None;
+    let mut delay = MIN_RECONNECT_DELAY;
+
+    loop {
+        let reason = match conn.take() {
+            Some(conn) => serve(conn, heartbeat, &mut room, &mut requests, &events).await,
+            None => match Connection::connect(&url, &mut session).await {
+                Ok(mut conn) => {
+                    delay = MIN_RECONNECT_DELAY;
+                    if events.send(Event::Connected).is_err() {
+                        return;
+                    }
+
+                    let resumed = async {
+                        conn.send(&RoomRequest::UserInfo).await?;
+                        if let Some(room) = room {
+                            conn.send(&RoomRequest::JoinRoom(room)).await?;
+                        }
+                        Ok::<_, Error>(())
+                    };
+
+                    match resumed.await {
+                        Ok(()) => serve(conn, heartbeat, &mut room, &mut requests, &events).await,
+                        Err(err) => Some(err.to_string()),
+                    }
+                }
+                Err(err) => Some(err.to_string()),
+            },
+        };
+
+        // The client was dropped.
+        let reason = match reason {
+            Some(reason) => reason,
+            None => return,
+        };
+
+        if events.send(Event::Disconnected(reason)).is_err() || !reconnect {
+            return;
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+/// Serves the connection until it is lost, returning why, or `None` when the client is dropped.
+async fn serve(
+    mut conn: Connection,
+    heartbeat: Heartbeat,
+    room: &mut Option<RoomId>,
+    requests: &mut UnboundedReceiver<RoomRequest>,
+    events: &UnboundedSender<Event>,
+) -> Option<String> {
+    let mut ticks = tokio::time::interval(heartbeat.interval);
+
+    loop {
+        tokio::select! {
+            request = requests.recv() => {
+                let request = request?;
+                match request {
+                    RoomRequest::JoinRoom(id) => *room = Some(id),
+                    RoomRequest::LeaveRoom => *room = None,
+                    _ => {}
+                }
+
+                if let Err(err) = conn.send(&request).await {
+                    return Some(err.to_string());
+                }
+            }
+            msg = conn.next() => match msg {
+                Ok(Some(msg)) => {
+                    if let RoomMessage::NoSuchRoom(id) = msg {
+                        if *room == Some(id) {
+                            *room = None;
+                        }
+                    }
+
+                    events.send(Event::Message(msg)).ok()?;
+                }
+                Ok(None) => return Some("connection closed by the server".to_owned()),
+                Err(err) => return Some(err.to_string()),
+            },
+            _ = ticks.tick() => {
+                if conn.last_seen().elapsed() > heartbeat.timeout {
+                    return Some("the server stopped responding".to_owned());
+                }
+                if let Err(err) = conn.ping().await {
+                    return Some(err.to_string());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use futures_util::{SinkExt, StreamExt};
+    use planpoker_common::{deserialize_json, serialize_json, RoomError};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::{
+        tungstenite::{
+            handshake::server::{Request, Response},
+            http::header,
+            Message,
+        },
+        WebSocketStream,
+    };
+
+    use super::*;
+
+    /// Local websocket server standing in for the backend, driven by the test
+    struct Stub {
+        url: String,
+        listener: TcpListener,
+        /// Session token issued to clients that do not send it
+        token: &'static str,
+    }
+
+    /// Server side of a connection, with the session cookie the client sent
+    struct ServerConnection {
+        ws: WebSocketStream<TcpStream>,
+        cookie: Option<String>,
+    }
+
+    impl Stub {
+        async fn start(token: &'static str) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}/ws/", listener.local_addr().unwrap());
+
+            Self {
+                url,
+                listener,
+                token,
+            }
+        }
+
+        // The error response of the handshake callback is a type of tungstenite.
+        #[allow(clippy::result_large_err)]
+        async fn accept(&self) -> ServerConnection {
+            let (stream, _) = self.listener.accept().await.unwrap();
+            let mut cookie = None;
+            let token = format!("session={}", self.token);
+
+            let ws =
+                tokio_tungstenite::accept_hdr_async(stream, |req: &Request, mut res: Response| {
+                    cookie = req
+                        .headers()
+                        .get(header::COOKIE)
+                        .map(|cookie| cookie.to_str().unwrap().to_owned());
+                    if cookie.as_deref() != Some(token.as_str()) {
+                        let set_cookie = format!("{}; Path=/; HttpOnly", token).parse().unwrap();
+                        res.headers_mut().insert(header::SET_COOKIE, set_cookie);
+                    }
+                    Ok(res)
+                })
+                .await
+                .unwrap();
+
+            ServerConnection { ws, cookie }
+        }
+
+        /// Accepts the next connection and answers the user info request of the client.
+        async fn accept_user(&self, user_id: UserId) -> ServerConnection {
+            let mut conn = self.accept().await;
+            assert!(matches!(conn.receive().await, RoomRequest::UserInfo));
+            conn.send(RoomMessage::UserInfo(user_id)).await;
+            conn
+        }
+    }
+
+    impl ServerConnection {
+        async fn receive(&mut self) -> RoomRequest {
+            loop {
+                match self.ws.next().await.unwrap().unwrap() {
+                    Message::Text(text) => return deserialize_json(&text).unwrap(),
+                    // Pings are answered while reading.
+                    _ => continue,
+                }
+            }
+        }
+
+        async fn send(&mut self, msg: RoomMessage) {
+            let text = serialize_json(&msg).unwrap();
+            self.ws.send(Message::Text(text)).await.unwrap();
+        }
+    }
+
+    async fn next_message(client: &mut Client) -> RoomMessage {
+        match client.next_event().await {
+            Some(Event::Message(msg)) => msg,
+            event => panic!("expected a message, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_with_the_same_session_and_joins_again() {
+        let stub = Stub::start("abc").await;
+
+        let (client, mut server) = tokio::join!(Client::connect(&stub.url), stub.accept_user(1));
+        let mut client = client.unwrap();
+        assert_eq!(client.user_id(), 1);
+        assert_eq!(server.cookie, None);
+
+        client.send(RoomRequest::JoinRoom(7)).unwrap();
+        assert!(matches!(server.receive().await, RoomRequest::JoinRoom(7)));
+        drop(server);
+
+        assert!(matches!(
+            client.next_event().await,
+            Some(Event::Disconnected(_))
+        ));
+
+        let mut server = stub.accept_user(1).await;
+        assert_eq!(server.cookie.as_deref(), Some("session=abc"));
+        assert!(matches!(server.receive().await, RoomRequest::JoinRoom(7)));
+
+        assert!(matches!(client.next_event().await, Some(Event::Connected)));
+        assert!(matches!(
+            next_message(&mut client).await,
+            RoomMessage::UserInfo(1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn silent_servers_time_out() {
+        let stub = Stub::start("abc").await;
+        let mut builder = Client::builder(&stub.url).reconnect(false);
+        builder.heartbeat = Heartbeat {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(100),
+        };
+
+        let (client, server) = tokio::join!(builder.connect(), stub.accept_user(1));
+        let mut client = client.unwrap();
+
+        // The server neither reads the pings nor sends anything, but keeps the connection open.
+        let event = tokio::time::timeout(Duration::from_secs(5), client.next_event()).await;
+        match event.expect("no timeout within 5 seconds") {
+            Some(Event::Disconnected(reason)) => {
+                assert_eq!(reason, "the server stopped responding")
+            }
+            event => panic!("expected a disconnect, got {:?}", event),
+        }
+        assert!(client.next_event().await.is_none());
+        assert!(matches!(client.reveal().await, Err(Error::Closed)));
+
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn rejected_requests_are_errors() {
+        let stub = Stub::start("abc").await;
+        let (client, mut server) = tokio::join!(Client::connect(&stub.url), stub.accept_user(1));
+        let mut client = client.unwrap();
+
+        let server_side = async {
+            assert!(matches!(server.receive().await, RoomRequest::Reveal));
+            server.send(RoomMessage::Error(RoomError::NotAdmin)).await;
+        };
+        let (res, _) = tokio::join!(client.reveal(), server_side);
+
+        assert!(matches!(res, Err(Error::Room(RoomError::NotAdmin))));
+    }
+
+    #[tokio::test]
+    async fn join_waits_for_the_info_of_the_room() {
+        let stub = Stub::start("abc").await;
+        let (client, mut server) = tokio::join!(Client::connect(&stub.url), stub.accept_user(1));
+        let mut client = client.unwrap();
+
+        let server_side = async {
+            assert!(matches!(server.receive().await, RoomRequest::JoinRoom(7)));
+            // Unrelated messages are skipped.
+            server.send(RoomMessage::RoomCreated(3)).await;
+            server
+                .send(RoomMessage::RoomInfo(RoomInfo::new(3, 1)))
+                .await;
+            server
+                .send(RoomMessage::RoomInfo(RoomInfo::new(7, 1)))
+                .await;
+
+            assert!(matches!(server.receive().await, RoomRequest::JoinRoom(8)));
+            server.send(RoomMessage::NoSuchRoom(8)).await;
+        };
+        let joins = async {
+            let info = client.join(7).await.unwrap();
+            let missing = client.join(8).await;
+            (info, missing)
+        };
+        let ((info, missing), _) = tokio::join!(joins, server_side);
+
+        assert_eq!(info.id, 7);
+        assert!(matches!(missing, Err(Error::NoSuchRoom(8))));
+    }
+
+    #[tokio::test]
+    async fn wait_for_fails_when_the_connection_is_lost() {
+        let stub = Stub::start("abc").await;
+        let builder = Client::builder(&stub.url).reconnect(false);
+        let (client, server) = tokio::join!(builder.connect(), stub.accept_user(1));
+        let mut client = client.unwrap();
+        drop(server);
+
+        let res = client.room_info().await;
+
+        assert!(matches!(res, Err(Error::Disconnected(_))), "{:?}", res);
+    }
+
+    #[tokio::test]
+    async fn session_file_keeps_the_token() {
+        let file = env::temp_dir().join(format!("planpoker-client-test-{}", process::id()));
+        fs::write(&file, "expired\n").unwrap();
+        let stub = Stub::start("abc").await;
+
+        // The token from the file is sent, and replaced by the one the server issues.
+        let builder = Client::builder(&stub.url).session_file(&file);
+        let (client, server) = tokio::join!(builder.connect(), stub.accept_user(1));
+        client.unwrap();
+        assert_eq!(server.cookie.as_deref(), Some("session=expired"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "abc");
+
+        // Another client with the same file is the same user.
+        let builder = Client::builder(&stub.url).session_file(&file);
+        let (client, server) = tokio::join!(builder.connect(), stub.accept_user(1));
+        client.unwrap();
+        assert_eq!(server.cookie.as_deref(), Some("session=abc"));
+
+        fs::remove_file(&file).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+//! Request and response bodies of the REST API under `/api/v1`
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CardValue, Deck, RoomError, RoomId, RoomInfo};
+
+/// Version of the REST API, part of its path
+pub const API_VERSION: &str = "v1";
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct CreateRoom {
+    pub deck: Deck,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RoomCreated {
+    pub id: RoomId,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NewStory {
+    pub title: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct StorySummary {
+    /// Index of the story in the room, used to refer to it in requests
+    pub index: usize,
+    pub title: String,
+    #[serde(default)]
+    pub estimate: Option<CardValue>,
+    /// How many rounds of voting the story has had
+    pub rounds: usize,
+}
+
+/// Token to be sent in the `Authorization: Bearer` header
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApiToken {
+    pub token: String,
+    /// Seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ApiError {
+    pub error: String,
+    /// Set when the request was rejected by the room
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_error: Option<RoomError>,
+}
+
+/// Issue tracker of a room, for importing stories and writing the final estimates back
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum TrackerSettings {
+    Jira(JiraSettings),
+    GitHub(GitHubSettings),
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct JiraSettings {
+    /// Such as `https://example.atlassian.net`
+    pub url: String,
+    /// Jira Cloud user, the token is then an API token sent with basic auth.
+    /// Without a user the token is sent as a bearer token, like personal access tokens of Jira Server.
+    #[serde(default)]
+    pub user: Option<String>,
+    pub token: String,
+    /// Number field the final estimate is written to, such as `customfield_10016`.
+    /// Estimates are not written back if not set.
+    #[serde(default)]
+    pub estimate_field: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GitHubSettings {
+    /// `owner/name` of the repository
+    pub repo: String,
+    pub token: String,
+    /// The final estimate is written back as a label with this prefix, such as `estimate: 5`,
+    /// replacing earlier estimate labels. Estimates are not written back if not set.
+    #[serde(default)]
+    pub label_prefix: Option<String>,
+    /// API of a GitHub Enterprise server, `https://api.github.com` if not set
+    #[serde(default)]
+    pub api_url: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ImportIssues {
+    /// JQL for Jira, a search query for GitHub issues
+    pub query: String,
+}
+
+/// Room events webhooks can subscribe to
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum WebhookEvent {
+    RoundRevealed,
+    EstimateFinalized,
+    RoomClosed,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::RoundRevealed,
+        WebhookEvent::EstimateFinalized,
+        WebhookEvent::RoomClosed,
+    ];
+}
+
+/// Format of the requests sent to a webhook
+#[derive(Copy, Clone, Default, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum WebhookFormat {
+    /// [`WebhookPayload`]
+    #[default]
+    Json,
+    /// Result summary for a Slack incoming webhook, formatted with blocks
+    Slack,
+    /// Result summary for a Mattermost incoming webhook, formatted as an attachment
+    Mattermost,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NewWebhook {
+    /// `http` or `https` URL the events are posted to
+    pub url: String,
+    /// Key for the `X-Planpoker-Signature` header, an HMAC-SHA256 of the request body
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events to post, all events if empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WebhookSummary {
+    /// Index of the webhook in the room, used to delete it
+    pub index: usize,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub format: WebhookFormat,
+    /// Recent delivery attempts, oldest first
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WebhookDelivery {
+    pub event: WebhookEvent,
+    /// Starts at 1, failed deliveries are retried with increasing delays
+    pub attempt: u32,
+    /// HTTP status of the response, if there was one
+    #[serde(default)]
+    pub status: Option<u16>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Seconds since the Unix epoch
+    pub sent_at: u64,
+}
+
+/// Body of the requests sent to webhooks
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    pub room_id: RoomId,
+    /// Seconds since the Unix epoch
+    pub sent_at: u64,
+    /// The room as the clients see it after the event
+    pub room: RoomInfo,
+}
@@ -0,0 +1,364 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Card, CardValue};
+
+/// Built-in decks a room can be created with
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Deck {
+    /// 1 to 9
+    #[default]
+    Linear,
+    /// 1, 2, 3, 5, 8, 13, 21, 34, 55, 89
+    Fibonacci,
+    /// 0, ½, 1, 2, 3, 5, 8, 13, 20, 40, 100 and a coffee break card
+    ModifiedFibonacci,
+    /// 1, 2, 4, 8, 16, 32, 64
+    PowersOfTwo,
+    /// XS to XXL, valued like the Fibonacci deck
+    TShirt,
+}
+
+impl Deck {
+    pub const ALL: [Deck; 5] = [
+        Deck::Linear,
+        Deck::Fibonacci,
+        Deck::ModifiedFibonacci,
+        Deck::PowersOfTwo,
+        Deck::TShirt,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Deck::Linear => "Linear",
+            Deck::Fibonacci => "Fibonacci",
+            Deck::ModifiedFibonacci => "Modified Fibonacci",
+            Deck::PowersOfTwo => "Powers of two",
+            Deck::TShirt => "T-shirt sizes",
+        }
+    }
+
+    /// Cards of the deck, ordered by value, followed by the valueless cards
+    pub fn cards(self) -> Vec<Card> {
+        let mut cards = match self {
+            Deck::Linear => numbered(&[1, 2, 3, 4, 5, 6, 7, 8, 9]),
+            Deck::Fibonacci => numbered(&[1, 2, 3, 5, 8, 13, 21, 34, 55, 89]),
+            Deck::ModifiedFibonacci => {
+                let mut cards = vec![Card::valued("0", 0), Card::valued("½", 0.5)];
+                cards.extend(numbered(&[1, 2, 3, 5, 8, 13, 20, 40, 100]));
+                cards.push(Card::valueless("☕"));
+                cards
+            }
+            Deck::PowersOfTwo => numbered(&[1, 2, 4, 8, 16, 32, 64]),
+            Deck::TShirt => vec![
+                Card::valued("XS", 1),
+                Card::valued("S", 2),
+                Card::valued("M", 3),
+                Card::valued("L", 5),
+                Card::valued("XL", 8),
+                Card::valued("XXL", 13),
+            ],
+        };
+
+        cards.push(Card::valueless("?"));
+        cards
+    }
+}
+
+fn numbered(values: &[u32]) -> Vec<Card> {
+    values
+        .iter()
+        .map(|&value| Card::valued(value.to_string(), value))
+        .collect()
+}
+
+/// How the revealed votes are mapped back onto a card of the deck
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Rounding {
+    /// The card nearest to the average
+    NearestCard,
+    /// The lowest card at or above the average
+    RoundUp,
+    /// The card nearest to the median
+    #[default]
+    MedianCard,
+}
+
+impl Rounding {
+    pub const ALL: [Rounding; 3] = [
+        Rounding::NearestCard,
+        Rounding::RoundUp,
+        Rounding::MedianCard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rounding::NearestCard => "Nearest to average",
+            Rounding::RoundUp => "Round average up",
+            Rounding::MedianCard => "Nearest to median",
+        }
+    }
+
+    /// Suggests a card for the votes, given as card indexes.
+    /// Returns the index of the suggested card, or `None` if none of the votes have a value.
+    pub fn suggest(self, cards: &[Card], votes: &[u32]) -> Option<u32> {
+        let mut values = votes
+            .iter()
+            .filter_map(|&card| cards.get(card as usize)?.value())
+            .map(CardValue::as_f64)
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        match self {
+            Rounding::NearestCard => nearest_card(cards, average(&values)),
+            Rounding::RoundUp => card_at_or_above(cards, average(&values)),
+            Rounding::MedianCard => nearest_card(cards, median(&mut values)),
+        }
+    }
+}
+
+fn average(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+
+    let mid = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+fn valued_cards(cards: &[Card]) -> impl Iterator<Item = (u32, f64)> + '_ {
+    cards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, card)| Some((i as u32, card.value()?.as_f64())))
+}
+
+/// On a tie the higher card wins, it is safer to overestimate.
+fn nearest_card(cards: &[Card], target: f64) -> Option<u32> {
+    valued_cards(cards)
+        .min_by(|(_, a), (_, b)| {
+            let da = (a - target).abs();
+            let db = (b - target).abs();
+            da.total_cmp(&db).then(b.total_cmp(a))
+        })
+        .map(|(i, _)| i)
+}
+
+/// Falls back to the highest card if the target is above all of them.
+fn card_at_or_above(cards: &[Card], target: f64) -> Option<u32> {
+    let lowest_above = valued_cards(cards)
+        .filter(|(_, value)| *value >= target)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    lowest_above
+        .or_else(|| valued_cards(cards).max_by(|(_, a), (_, b)| a.total_cmp(b)))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(deck: Deck) -> Vec<String> {
+        deck.cards()
+            .iter()
+            .map(|card| card.as_str().to_owned())
+            .collect()
+    }
+
+    fn values(deck: Deck) -> Vec<Option<f64>> {
+        deck.cards()
+            .iter()
+            .map(|card| card.value().map(CardValue::as_f64))
+            .collect()
+    }
+
+    /// Suggests a card for the votes, with cards given by their labels.
+    fn suggest(deck: Deck, rounding: Rounding, votes: &[&str]) -> Option<String> {
+        let cards = deck.cards();
+        let votes = votes
+            .iter()
+            .map(|vote| {
+                cards
+                    .iter()
+                    .position(|card| card.as_str() == *vote)
+                    .expect("card of the deck") as u32
+            })
+            .collect::<Vec<_>>();
+
+        rounding
+            .suggest(&cards, &votes)
+            .map(|card| cards[card as usize].as_str().to_owned())
+    }
+
+    #[test]
+    fn linear_deck() {
+        assert_eq!(
+            labels(Deck::Linear),
+            ["1", "2", "3", "4", "5", "6", "7", "8", "9", "?"]
+        );
+    }
+
+    #[test]
+    fn fibonacci_deck() {
+        assert_eq!(
+            labels(Deck::Fibonacci),
+            ["1", "2", "3", "5", "8", "13", "21", "34", "55", "89", "?"]
+        );
+    }
+
+    #[test]
+    fn modified_fibonacci_deck() {
+        assert_eq!(
+            labels(Deck::ModifiedFibonacci),
+            ["0", "½", "1", "2", "3", "5", "8", "13", "20", "40", "100", "☕", "?"]
+        );
+        assert_eq!(values(Deck::ModifiedFibonacci)[1], Some(0.5));
+    }
+
+    #[test]
+    fn powers_of_two_deck() {
+        assert_eq!(
+            labels(Deck::PowersOfTwo),
+            ["1", "2", "4", "8", "16", "32", "64", "?"]
+        );
+    }
+
+    #[test]
+    fn t_shirt_deck() {
+        assert_eq!(
+            labels(Deck::TShirt),
+            ["XS", "S", "M", "L", "XL", "XXL", "?"]
+        );
+        assert_eq!(
+            values(Deck::TShirt),
+            [
+                Some(1.0),
+                Some(2.0),
+                Some(3.0),
+                Some(5.0),
+                Some(8.0),
+                Some(13.0),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn decks_are_ordered_by_value_with_valueless_cards_last() {
+        for deck in Deck::ALL.iter().copied() {
+            let values = values(deck);
+            let valued = values.iter().take_while(|v| v.is_some()).count();
+
+            assert!(values[valued..].iter().all(Option::is_none), "{:?}", deck);
+            assert!(
+                values[..valued].windows(2).all(|w| w[0] < w[1]),
+                "{:?}",
+                deck
+            );
+        }
+    }
+
+    #[test]
+    fn nearest_card_to_average() {
+        let suggest = |votes: &[&str]| suggest(Deck::Fibonacci, Rounding::NearestCard, votes);
+
+        assert_eq!(suggest(&["3", "5", "8"]).as_deref(), Some("5"));
+        // Average 4.67 is closer to 5 than to 3
+        assert_eq!(suggest(&["1", "5", "8"]).as_deref(), Some("5"));
+        assert_eq!(suggest(&["8", "8", "13"]).as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn nearest_card_prefers_the_higher_card_on_a_tie() {
+        // Average 4 is half way between 3 and 5
+        assert_eq!(
+            suggest(Deck::Fibonacci, Rounding::NearestCard, &["3", "5"]).as_deref(),
+            Some("5")
+        );
+        assert_eq!(
+            suggest(Deck::ModifiedFibonacci, Rounding::NearestCard, &["½", "1"]).as_deref(),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn round_up_average() {
+        let suggest = |votes: &[&str]| suggest(Deck::Fibonacci, Rounding::RoundUp, votes);
+
+        // Average 5.33 rounds up to 8, even though 5 is nearer
+        assert_eq!(suggest(&["3", "5", "8"]).as_deref(), Some("8"));
+        // An average on a card is that card
+        assert_eq!(suggest(&["5", "5"]).as_deref(), Some("5"));
+        assert_eq!(suggest(&["3", "5"]).as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn median_card() {
+        let suggest = |votes: &[&str]| suggest(Deck::Fibonacci, Rounding::MedianCard, votes);
+
+        // The outlier does not pull the estimate up
+        assert_eq!(suggest(&["3", "3", "89"]).as_deref(), Some("3"));
+        assert_eq!(suggest(&["89", "3", "5"]).as_deref(), Some("5"));
+        // Median 6.5 of an even number of votes, nearer to 8
+        assert_eq!(suggest(&["1", "5", "8", "13"]).as_deref(), Some("8"));
+        // Median 4 is half way between 3 and 5
+        assert_eq!(suggest(&["1", "3", "5", "8"]).as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn valueless_votes_are_ignored() {
+        for rounding in Rounding::ALL.iter().copied() {
+            let suggest = |votes: &[&str]| suggest(Deck::ModifiedFibonacci, rounding, votes);
+
+            assert_eq!(suggest(&["?", "☕"]), None, "{:?}", rounding);
+            assert_eq!(suggest(&[]), None, "{:?}", rounding);
+            assert_eq!(
+                suggest(&["?", "5", "☕"]).as_deref(),
+                Some("5"),
+                "{:?}",
+                rounding
+            );
+        }
+    }
+
+    #[test]
+    fn votes_outside_the_deck_are_ignored() {
+        let cards = Deck::Linear.cards();
+
+        for rounding in Rounding::ALL.iter().copied() {
+            assert_eq!(rounding.suggest(&cards, &[100, 200]), None);
+            assert_eq!(rounding.suggest(&cards, &[2, 100]), Some(2));
+        }
+    }
+
+    #[test]
+    fn targets_outside_the_range_of_the_deck() {
+        let cards = Deck::PowersOfTwo.cards();
+        let highest = Some(6);
+
+        assert_eq!(nearest_card(&cards, -10.0), Some(0));
+        assert_eq!(nearest_card(&cards, 1000.0), highest);
+        assert_eq!(card_at_or_above(&cards, -10.0), Some(0));
+        assert_eq!(card_at_or_above(&cards, 1000.0), highest);
+        assert_eq!(nearest_card(&[Card::valueless("?")], 1.0), None);
+        assert_eq!(card_at_or_above(&[Card::valueless("?")], 1.0), None);
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(Deck::default(), Deck::Linear);
+        assert_eq!(Rounding::default(), Rounding::MedianCard);
+    }
+}
@@ -7,20 +7,35 @@ pub use serde_json::from_str as deserialize_json;
 #[cfg(feature = "json")]
 pub use serde_json::to_string as serialize_json;
 
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod api;
+mod deck;
+#[cfg(feature = "schema")]
+pub mod schema;
+mod state;
+
+pub use deck::{Deck, Rounding};
+pub use state::RoomState;
+
+/// Version of the protocol, to be bumped whenever the types sent between
+/// the clients and the server change. See [`schema`] for the JSON Schema of the types.
+pub const PROTOCOL_VERSION: u32 = 5;
 
 pub type RoomId = u32;
 pub type UserId = u128;
 pub type SessionId = u128;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum RoomStateChange {
     UserJoined,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum RoomMessage {
     UserInfo(UserId),
     RoomCreated(RoomId),
@@ -31,10 +46,72 @@ pub enum RoomMessage {
     UserVoted(UserId),
     CardsRevealed,
     Reset,
+    TimerStarted(RoundTimer),
+    TimerStopped,
+    TimerExpired,
+    /// Everyone has voted and the votes will be revealed after the given number of seconds
+    RevealPending(u32),
+    /// A pending reveal was cancelled because not everyone has voted anymore
+    RevealCancelled,
+    /// The revealed votes were archived and a new round of voting on the same story started
+    Revote,
+    /// The admin decided on the final estimate of the current story
+    EstimateSet(CardValue),
+    Chat(ChatMessage),
+    /// Earlier chat messages of the room, oldest first, sent when joining
+    ChatHistory(Vec<ChatMessage>),
+    Reaction {
+        user_id: UserId,
+        reaction: Reaction,
+    },
+    /// The admin is waiting for the user to vote
+    Nudge,
     Disconnected,
+    Error(RoomError),
+}
+
+/// Reasons for a request to be rejected by the server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum RoomError {
+    /// Too many requests have been sent in a short time
+    RateLimited,
+    MessageTooLarge,
+    /// The user already owns the maximum number of rooms
+    TooManyRooms,
+    RoomFull(RoomId),
+    /// No more rooms can be created on the server
+    ServerFull,
+    /// Only the admin of the room can do this
+    NotAdmin,
+    NotInRoom,
+    /// Not allowed while the votes are revealed
+    AlreadyRevealed,
+    /// Not allowed before the votes are revealed
+    NotRevealed,
+    NoSuchStory(usize),
+    /// The card index is not in the deck of the room
+    NoSuchCard(u32),
+    RoomClosed,
+    NoSuchRoom(RoomId),
+    EmptyChatMessage,
+    /// The chat message is longer than the given number of characters
+    ChatMessageTooLong(usize),
+    /// Estimates must be finite and not negative
+    InvalidEstimate,
+    /// Webhook URLs must use `http` or `https`
+    InvalidWebhookUrl,
+    /// The room already has the maximum number of webhooks
+    TooManyWebhooks,
+    NoSuchWebhook(usize),
+    /// The room has no issue tracker configured
+    NoTracker,
+    /// Tracker URLs must use `http` or `https`, GitHub repositories are given as `owner/name`
+    InvalidTrackerSettings,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum RoomRequest {
     CreateRoom,
     JoinRoom(RoomId),
@@ -44,12 +121,71 @@ pub enum RoomRequest {
     UserInfo,
     Reset,
     Reveal,
+    /// Starts a round timer, replacing the current one
+    StartTimer {
+        seconds: u32,
+        /// Reveal the votes when the time runs out
+        auto_reveal: bool,
+    },
+    StopTimer,
+    UpdateSettings(RoomSettings),
+    AddStory(String),
+    /// Starts voting on the story with the given index
+    SelectStory(usize),
+    /// Archives the revealed votes and votes again on the same story
+    Revote,
+    /// Sets the final estimate of the revealed round
+    SetEstimate(CardValue),
+    SendChat {
+        text: String,
+        /// Index of the story the message is about
+        #[serde(default)]
+        story: Option<usize>,
+    },
+    React(Reaction),
+    /// Reminds the users who have not voted yet
+    Nudge,
+}
+
+/// Emoji reactions, shown to everyone in the room but not stored
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Reaction {
+    ThumbsUp,
+    ThumbsDown,
+    Party,
+    Thinking,
+    Surprised,
+    Coffee,
+}
+
+impl Reaction {
+    pub const ALL: [Reaction; 6] = [
+        Reaction::ThumbsUp,
+        Reaction::ThumbsDown,
+        Reaction::Party,
+        Reaction::Thinking,
+        Reaction::Surprised,
+        Reaction::Coffee,
+    ];
+
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Reaction::ThumbsUp => "👍",
+            Reaction::ThumbsDown => "👎",
+            Reaction::Party => "🎉",
+            Reaction::Thinking => "🤔",
+            Reaction::Surprised => "😮",
+            Reaction::Coffee => "☕",
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Card {
     display: Cow<'static, str>,
-    value: Option<u32>,
+    value: Option<CardValue>,
 }
 
 impl Card {
@@ -57,18 +193,18 @@ impl Card {
         Self::new(display, None)
     }
 
-    pub fn valued<T: Into<Cow<'static, str>>>(display: T, value: u32) -> Self {
-        Self::new(display, Some(value))
+    pub fn valued<T: Into<Cow<'static, str>>, V: Into<CardValue>>(display: T, value: V) -> Self {
+        Self::new(display, Some(value.into()))
     }
 
-    pub fn new<T: Into<Cow<'static, str>>>(display: T, value: Option<u32>) -> Self {
+    pub fn new<T: Into<Cow<'static, str>>>(display: T, value: Option<CardValue>) -> Self {
         Self {
             display: display.into(),
             value,
         }
     }
 
-    pub fn value(&self) -> Option<u32> {
+    pub fn value(&self) -> Option<CardValue> {
         self.value
     }
 
@@ -77,14 +213,110 @@ impl Card {
     }
 }
 
+/// Numeric value of a card, such as story points or days.
+///
+/// Whole values are serialized as integers in human readable formats,
+/// so they stay compatible with the earlier integer only values.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct CardValue(f64);
+
+impl CardValue {
+    pub fn as_f64(self) -> f64 {
+        self.0
+    }
+
+    pub fn is_valid(self) -> bool {
+        self.0.is_finite() && self.0 >= 0.0
+    }
+
+    fn as_whole(self) -> Option<u64> {
+        if self.0.fract() == 0.0 && self.0 >= 0.0 && self.0 <= u32::MAX as f64 {
+            Some(self.0 as u64)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<u32> for CardValue {
+    fn from(value: u32) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<f64> for CardValue {
+    fn from(value: f64) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for CardValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_whole() {
+            Some(value) => write!(f, "{}", value),
+            // Rounded to hundredths to avoid printing float noise like 0.30000000000000004
+            None => write!(f, "{}", (self.0 * 100.0).round() / 100.0),
+        }
+    }
+}
+
+impl std::str::FromStr for CardValue {
+    type Err = std::num::ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().replace(',', ".").parse().map(Self)
+    }
+}
+
+impl Serialize for CardValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_whole() {
+            Some(value) if serializer.is_human_readable() => serializer.serialize_u64(value),
+            _ => serializer.serialize_f64(self.0),
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for CardValue {
+    fn schema_name() -> String {
+        "CardValue".to_owned()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <f64 as schemars::JsonSchema>::json_schema(gen)
+    }
+}
+
+impl<'de> Deserialize<'de> for CardValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Integers are accepted as well, see `Serialize`.
+        f64::deserialize(deserializer).map(Self)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RoomInfo {
     pub id: RoomId,
     pub admin: UserId,
     pub users: Vec<RoomUser>,
     pub cards: Vec<Card>,
 
-    pub revealed: bool,
+    #[serde(default)]
+    pub state: RoomState,
+    #[serde(default)]
+    pub timer: Option<RoundTimer>,
+    #[serde(default)]
+    pub settings: RoomSettings,
+    /// Revealed votes without the voters, sent instead of the per user votes in anonymous mode
+    #[serde(default)]
+    pub distribution: Option<Vec<CardCount>>,
+    #[serde(default)]
+    pub stories: Vec<Story>,
+    /// Index of the story currently being voted on
+    #[serde(default)]
+    pub current_story: Option<usize>,
 }
 
 impl RoomInfo {
@@ -94,14 +326,70 @@ impl RoomInfo {
             admin,
             users: vec![],
             cards: vec![],
-            revealed: false,
+            state: RoomState::default(),
+            timer: None,
+            settings: RoomSettings::default(),
+            distribution: None,
+            stories: vec![],
+            current_story: None,
         }
     }
 
-    pub fn add_user(&mut self, user_id: UserId) -> bool {
+    pub fn current_story(&self) -> Option<&Story> {
+        self.current_story.and_then(|i| self.stories.get(i))
+    }
+
+    pub fn current_story_mut(&mut self) -> Option<&mut Story> {
+        match self.current_story {
+            Some(i) => self.stories.get_mut(i),
+            None => None,
+        }
+    }
+
+    /// Card indexes of the revealed votes. In anonymous mode they are taken from the distribution.
+    pub fn revealed_cards(&self) -> Vec<u32> {
+        match self.distribution.as_ref() {
+            Some(distribution) => distribution
+                .iter()
+                .flat_map(|c| std::iter::repeat_n(c.card, c.count as usize))
+                .collect(),
+            None => self
+                .users
+                .iter()
+                .filter_map(|u| match u.vote {
+                    Vote::Revealed(card) => Some(card),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Index of the card suggested as the final estimate, using the rounding of the room.
+    pub fn suggested_card(&self) -> Option<u32> {
+        self.settings
+            .rounding
+            .suggest(&self.cards, &self.revealed_cards())
+    }
+
+    /// Value of the suggested card
+    pub fn suggested_estimate(&self) -> Option<CardValue> {
+        self.cards.get(self.suggested_card()? as usize)?.value()
+    }
+
+    /// Returns true if there are users in the room and all of them have voted.
+    pub fn all_voted(&self) -> bool {
+        !self.users.is_empty()
+            && self
+                .users
+                .iter()
+                .all(|u| matches!(u.vote, Vote::Hidden(_) | Vote::Unknown))
+    }
+
+    pub fn add_user(&mut self, user_id: UserId, name: Option<String>) -> bool {
         if !self.users.iter().any(|u| u.user_id == user_id) {
             self.users.push(RoomUser {
                 user_id,
+                name,
                 vote: Vote::None,
             });
             true
@@ -111,15 +399,191 @@ impl RoomInfo {
     }
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct RoomSettings {
+    /// Reveal the votes automatically once everyone has voted
+    pub auto_reveal: bool,
+    /// Seconds to wait before the automatic reveal, during which votes can still be changed
+    pub auto_reveal_delay: u32,
+    /// Only reveal how many votes each card got, not who voted what
+    pub anonymous: bool,
+    /// How the suggested estimate is picked from the revealed votes
+    pub rounding: Rounding,
+}
+
+/// Number of votes for a card
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CardCount {
+    /// Index of the card in the deck
+    pub card: u32,
+    pub count: u32,
+}
+
+/// Round timer, driven by the server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RoundTimer {
+    /// Length of the round in seconds
+    pub duration: u32,
+    /// Time left in milliseconds at the moment the message was sent
+    pub remaining_ms: u64,
+    pub auto_reveal: bool,
+}
+
+/// A backlog item to be estimated in the room
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Story {
+    pub title: String,
+    /// Earlier rounds of voting on the story, oldest first
+    #[serde(default)]
+    pub rounds: Vec<Round>,
+    /// The final estimate, as decided by the admin
+    #[serde(default)]
+    pub estimate: Option<CardValue>,
+    /// Set if the story was imported from an issue tracker
+    #[serde(default)]
+    pub issue: Option<Issue>,
+}
+
+impl Story {
+    pub fn new<T: Into<String>>(title: T) -> Self {
+        Self {
+            title: title.into(),
+            rounds: vec![],
+            estimate: None,
+            issue: None,
+        }
+    }
+
+    pub fn last_round(&self) -> Option<&Round> {
+        self.rounds.last()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Issue {
+    /// Such as `PROJ-123` in Jira or `#42` on GitHub
+    pub key: String,
+    /// Link to the issue in the tracker
+    pub url: String,
+}
+
+impl Issue {
+    /// Whether the link is a web page, other schemes such as `javascript:` must not be followed
+    pub fn has_web_url(&self) -> bool {
+        let url = self.url.to_ascii_lowercase();
+        url.starts_with("http://") || url.starts_with("https://")
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ChatMessage {
+    pub user_id: UserId,
+    /// Name of the sender at the time the message was sent
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub text: String,
+    /// Index of the story the message is about
+    #[serde(default)]
+    pub story: Option<usize>,
+    /// Seconds since the Unix epoch
+    pub sent_at: u64,
+}
+
+/// Revealed votes of a finished round
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Round {
+    pub votes: Vec<RoundVote>,
+    /// Final estimate, if the round was the one that decided it
+    #[serde(default)]
+    pub estimate: Option<CardValue>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RoundVote {
+    /// The voter, left out for rounds voted in anonymous mode
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<UserId>,
+    /// Index of the card in the deck
+    pub card: u32,
+}
+
+/// Voters with the lowest and highest valued cards of a round
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Outliers {
+    pub lowest: Vec<UserId>,
+    pub highest: Vec<UserId>,
+}
+
+impl Round {
+    /// Finds the voters who voted for the lowest and the highest valued cards.
+    /// Returns `None` if the votes did not diverge or the voters are unknown.
+    pub fn outliers(&self, cards: &[Card]) -> Option<Outliers> {
+        let values = self
+            .votes
+            .iter()
+            .filter_map(|v| {
+                let value = cards.get(v.card as usize)?.value()?;
+                Some((v.user_id?, value.as_f64()))
+            })
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        let min = values
+            .iter()
+            .map(|(_, value)| *value)
+            .fold(f64::INFINITY, f64::min);
+        let max = values
+            .iter()
+            .map(|(_, value)| *value)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        if min == max {
+            return None;
+        }
+
+        let voters_with = |value: f64| {
+            values
+                .iter()
+                .filter(|(_, v)| *v == value)
+                .map(|(user_id, _)| *user_id)
+                .collect()
+        };
+
+        Some(Outliers {
+            lowest: voters_with(min),
+            highest: voters_with(max),
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct RoomUser {
     pub user_id: UserId,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
     #[serde(skip_serializing_if = "vote_hidden")]
     pub vote: Vote,
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum Vote {
     None,
     Unknown,
@@ -148,3 +612,150 @@ fn vote_hidden(vote: &Vote) -> bool {
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_json<T: Serialize>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    fn from_json<'a, T: Deserialize<'a>>(json: &'a str) -> T {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn whole_card_values_stay_json_integers() {
+        assert_eq!(to_json(&CardValue::from(0)), "0");
+        assert_eq!(to_json(&CardValue::from(13)), "13");
+        assert_eq!(to_json(&CardValue::from(5.0)), "5");
+
+        assert_eq!(from_json::<CardValue>("13"), CardValue::from(13));
+        assert_eq!(from_json::<CardValue>("13.0"), CardValue::from(13));
+    }
+
+    #[test]
+    fn fractional_card_values_round_trip_through_json() {
+        for value in &[0.5, 2.25, 0.1 + 0.2] {
+            let json = to_json(&CardValue::from(*value));
+
+            assert_eq!(json, value.to_string());
+            assert_eq!(from_json::<CardValue>(&json).as_f64(), *value);
+        }
+    }
+
+    #[test]
+    fn card_values_round_trip_through_bincode() {
+        for value in &[0.0, 0.5, 13.0, 100.0] {
+            let value = CardValue::from(*value);
+            let bytes = bincode::serialize(&value).unwrap();
+
+            assert_eq!(bincode::deserialize::<CardValue>(&bytes).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn cards_with_integer_values_are_unchanged() {
+        // Cards as serialized when their values were `Option<u32>`
+        for json in &[
+            r#"{"display":"5","value":5}"#,
+            r#"{"display":"?","value":null}"#,
+        ] {
+            let card = from_json::<Card>(json);
+            assert_eq!(to_json(&card), *json);
+        }
+
+        let card = from_json::<Card>(r#"{"display":"½","value":0.5}"#);
+        assert_eq!(card.value(), Some(CardValue::from(0.5)));
+    }
+
+    #[test]
+    fn old_room_info_is_accepted() {
+        let json = r#"{
+            "id": 7,
+            "admin": 1,
+            "users": [{"user_id": 1, "vote": {"Revealed": 2}}, {"user_id": 2}],
+            "cards": [
+                {"display": "1", "value": 1},
+                {"display": "2", "value": 2},
+                {"display": "3", "value": 3},
+                {"display": "?", "value": null}
+            ],
+            "revealed": true
+        }"#;
+
+        let room = from_json::<RoomInfo>(json);
+        assert_eq!(room.cards[2].value(), Some(CardValue::from(3)));
+        assert_eq!(room.cards[3].value(), None);
+        assert_eq!(room.users[0].vote, Vote::Revealed(2));
+        assert_eq!(room.users[1].vote, Vote::Unknown);
+    }
+
+    /// Requests as they were before any of them carried card values
+    #[derive(Serialize)]
+    enum OldRoomRequest {
+        CreateRoom,
+        JoinRoom(RoomId),
+        LeaveRoom,
+        Vote(u32),
+        RoomInfo,
+        UserInfo,
+        Reset,
+        Reveal,
+    }
+
+    #[test]
+    fn old_requests_are_accepted() {
+        let old = [
+            OldRoomRequest::CreateRoom,
+            OldRoomRequest::JoinRoom(7),
+            OldRoomRequest::LeaveRoom,
+            OldRoomRequest::Vote(3),
+            OldRoomRequest::RoomInfo,
+            OldRoomRequest::UserInfo,
+            OldRoomRequest::Reset,
+            OldRoomRequest::Reveal,
+        ];
+
+        for request in old.iter() {
+            let binary = bincode::deserialize::<RoomRequest>(&bincode::serialize(request).unwrap());
+            let json = serde_json::from_str::<RoomRequest>(&to_json(request));
+
+            for decoded in [binary.ok(), json.ok()].iter() {
+                let matches = match (request, decoded) {
+                    (OldRoomRequest::CreateRoom, Some(RoomRequest::CreateRoom))
+                    | (OldRoomRequest::LeaveRoom, Some(RoomRequest::LeaveRoom))
+                    | (OldRoomRequest::RoomInfo, Some(RoomRequest::RoomInfo))
+                    | (OldRoomRequest::UserInfo, Some(RoomRequest::UserInfo))
+                    | (OldRoomRequest::Reset, Some(RoomRequest::Reset))
+                    | (OldRoomRequest::Reveal, Some(RoomRequest::Reveal)) => true,
+                    (OldRoomRequest::JoinRoom(a), Some(RoomRequest::JoinRoom(b)))
+                    | (OldRoomRequest::Vote(a), Some(RoomRequest::Vote(b))) => a == b,
+                    _ => false,
+                };
+
+                assert!(matches, "{:?}", decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn estimates_round_trip() {
+        let request = RoomRequest::SetEstimate(CardValue::from(0.5));
+        let bytes = bincode::serialize(&request).unwrap();
+        assert!(matches!(
+            bincode::deserialize(&bytes).unwrap(),
+            RoomRequest::SetEstimate(value) if value == CardValue::from(0.5)
+        ));
+
+        assert_eq!(
+            to_json(&RoomMessage::EstimateSet(CardValue::from(8))),
+            r#"{"EstimateSet":8}"#
+        );
+        assert!(matches!(
+            from_json(r#"{"SetEstimate":8}"#),
+            RoomRequest::SetEstimate(value) if value == CardValue::from(8)
+        ));
+    }
+}
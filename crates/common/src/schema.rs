@@ -0,0 +1,110 @@
+//! JSON Schema of the protocol types, enabled with the `schema` feature
+
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{Metadata, RootSchema, Schema, SchemaObject},
+    Map,
+};
+
+use crate::{
+    api::{
+        ApiError, ApiToken, CreateRoom, ImportIssues, NewStory, NewWebhook, RoomCreated,
+        StorySummary, TrackerSettings, WebhookPayload, WebhookSummary,
+    },
+    RoomInfo, RoomMessage, RoomRequest, Story, PROTOCOL_VERSION,
+};
+
+/// Schemas of all protocol types, keyed by type name.
+///
+/// Use [`SchemaSettings::openapi3`] for the definitions of an OpenAPI document.
+pub fn definitions(settings: SchemaSettings) -> Map<String, Schema> {
+    let mut gen = settings.into_generator();
+    add_definitions(&mut gen);
+    gen.take_definitions()
+}
+
+/// JSON Schema document with the definitions of all protocol types.
+/// The messages sent over the websocket are [`RoomRequest`] and [`RoomMessage`].
+pub fn protocol_schema() -> RootSchema {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    add_definitions(&mut gen);
+
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                title: Some(format!("Planning poker protocol v{}", PROTOCOL_VERSION)),
+                ..Default::default()
+            })),
+            ..Default::default()
+        },
+        definitions: gen.take_definitions(),
+    }
+}
+
+fn add_definitions(gen: &mut SchemaGenerator) {
+    // Everything else is reachable from these.
+    gen.subschema_for::<RoomRequest>();
+    gen.subschema_for::<RoomMessage>();
+    gen.subschema_for::<RoomInfo>();
+    gen.subschema_for::<Story>();
+
+    gen.subschema_for::<CreateRoom>();
+    gen.subschema_for::<RoomCreated>();
+    gen.subschema_for::<NewStory>();
+    gen.subschema_for::<StorySummary>();
+    gen.subschema_for::<ApiToken>();
+    gen.subschema_for::<ApiError>();
+    gen.subschema_for::<NewWebhook>();
+    gen.subschema_for::<WebhookSummary>();
+    gen.subschema_for::<WebhookPayload>();
+    gen.subschema_for::<TrackerSettings>();
+    gen.subschema_for::<ImportIssues>();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::*;
+
+    /// Writes the snapshot of a new protocol version, when set to `1`
+    const UPDATE_SNAPSHOT: &str = "PLANPOKER_UPDATE_SCHEMA";
+
+    fn snapshot_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("schema")
+            .join(format!("protocol-v{}.json", PROTOCOL_VERSION))
+    }
+
+    #[test]
+    fn schema_matches_the_snapshot_of_the_protocol_version() {
+        let path = snapshot_path();
+        let schema = serde_json::to_value(protocol_schema()).unwrap();
+
+        // The snapshots of released versions are never overwritten, only compared with.
+        if env::var(UPDATE_SNAPSHOT).as_deref() == Ok("1") && !path.exists() {
+            let json = serde_json::to_string_pretty(&schema).unwrap();
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, json + "\n").unwrap();
+            return;
+        }
+
+        let snapshot = fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!(
+                "no snapshot at {}, write it with {}=1",
+                path.display(),
+                UPDATE_SNAPSHOT
+            )
+        });
+        let snapshot: serde_json::Value = serde_json::from_str(&snapshot).unwrap();
+
+        assert!(
+            schema == snapshot,
+            "the protocol changed since version {}: bump PROTOCOL_VERSION and write the \
+             snapshot of the new version with {}=1",
+            PROTOCOL_VERSION,
+            UPDATE_SNAPSHOT
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+use crate::RoomError;
+
+/// Lifecycle of a round of voting in a room.
+///
+/// The transitions return the next state, or the reason the action is not allowed in the current one.
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum RoomState {
+    /// A new round has started and nobody has voted yet
+    #[default]
+    Waiting,
+    Voting,
+    Revealed,
+    /// The votes are revealed and the final estimate has been set
+    Finalized,
+    /// The room does not accept any changes anymore
+    Closed,
+}
+
+impl RoomState {
+    pub fn vote(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Waiting | RoomState::Voting => Ok(RoomState::Voting),
+            // Room must be reset before voting is allowed.
+            RoomState::Revealed | RoomState::Finalized => Err(RoomError::AlreadyRevealed),
+            RoomState::Closed => Err(RoomError::RoomClosed),
+        }
+    }
+
+    pub fn reveal(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Waiting | RoomState::Voting => Ok(RoomState::Revealed),
+            RoomState::Revealed | RoomState::Finalized => Err(RoomError::AlreadyRevealed),
+            RoomState::Closed => Err(RoomError::RoomClosed),
+        }
+    }
+
+    /// Setting the final estimate, which can be changed until the next round
+    pub fn finalize(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Revealed | RoomState::Finalized => Ok(RoomState::Finalized),
+            RoomState::Waiting | RoomState::Voting => Err(RoomError::NotRevealed),
+            RoomState::Closed => Err(RoomError::RoomClosed),
+        }
+    }
+
+    /// Voting again on the same story after the votes were revealed
+    pub fn revote(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Revealed | RoomState::Finalized => Ok(RoomState::Waiting),
+            RoomState::Waiting | RoomState::Voting => Err(RoomError::NotRevealed),
+            RoomState::Closed => Err(RoomError::RoomClosed),
+        }
+    }
+
+    /// Discarding the votes, or moving on to another story
+    pub fn reset(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Closed => Err(RoomError::RoomClosed),
+            _ => Ok(RoomState::Waiting),
+        }
+    }
+
+    pub fn close(self) -> Result<Self, RoomError> {
+        match self {
+            RoomState::Closed => Err(RoomError::RoomClosed),
+            _ => Ok(RoomState::Closed),
+        }
+    }
+
+    pub fn accepts_votes(self) -> bool {
+        self.vote().is_ok()
+    }
+
+    pub fn is_revealed(self) -> bool {
+        matches!(self, RoomState::Revealed | RoomState::Finalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use RoomState::*;
+
+    const STATES: [RoomState; 5] = [Waiting, Voting, Revealed, Finalized, Closed];
+
+    type Transition = fn(RoomState) -> Result<RoomState, RoomError>;
+    type Expected = [Result<RoomState, RoomError>; 5];
+
+    /// Every transition with its expected result in each state, in the order of `STATES`
+    fn transitions() -> Vec<(&'static str, Transition, Expected)> {
+        use RoomError::{AlreadyRevealed, NotRevealed, RoomClosed};
+
+        vec![
+            (
+                "vote",
+                RoomState::vote,
+                [
+                    Ok(Voting),
+                    Ok(Voting),
+                    Err(AlreadyRevealed),
+                    Err(AlreadyRevealed),
+                    Err(RoomClosed),
+                ],
+            ),
+            (
+                "reveal",
+                RoomState::reveal,
+                [
+                    Ok(Revealed),
+                    Ok(Revealed),
+                    Err(AlreadyRevealed),
+                    Err(AlreadyRevealed),
+                    Err(RoomClosed),
+                ],
+            ),
+            (
+                "finalize",
+                RoomState::finalize,
+                [
+                    Err(NotRevealed),
+                    Err(NotRevealed),
+                    Ok(Finalized),
+                    Ok(Finalized),
+                    Err(RoomClosed),
+                ],
+            ),
+            (
+                "revote",
+                RoomState::revote,
+                [
+                    Err(NotRevealed),
+                    Err(NotRevealed),
+                    Ok(Waiting),
+                    Ok(Waiting),
+                    Err(RoomClosed),
+                ],
+            ),
+            (
+                "reset",
+                RoomState::reset,
+                [
+                    Ok(Waiting),
+                    Ok(Waiting),
+                    Ok(Waiting),
+                    Ok(Waiting),
+                    Err(RoomClosed),
+                ],
+            ),
+            (
+                "close",
+                RoomState::close,
+                [
+                    Ok(Closed),
+                    Ok(Closed),
+                    Ok(Closed),
+                    Ok(Closed),
+                    Err(RoomClosed),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_transition_in_every_state() {
+        for (name, transition, expected) in transitions() {
+            for (state, expected) in STATES.iter().zip(expected.iter()) {
+                assert_eq!(
+                    &transition(*state),
+                    expected,
+                    "{} in state {:?}",
+                    name,
+                    state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn queries_follow_the_transitions() {
+        for state in STATES.iter().copied() {
+            assert_eq!(state.accepts_votes(), state.vote().is_ok(), "{:?}", state);
+            assert_eq!(
+                state.is_revealed(),
+                matches!(state, Revealed | Finalized),
+                "{:?}",
+                state
+            );
+        }
+    }
+
+    #[test]
+    fn rooms_start_waiting() {
+        assert_eq!(RoomState::default(), Waiting);
+    }
+}
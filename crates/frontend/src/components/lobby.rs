@@ -52,6 +52,13 @@ impl Component for Lobby {
                     }
                 }
                 RoomMessage::RoomCreated(id) => push_route(Route::Room { id }),
+                RoomMessage::Error(err) => {
+                    log::warn!("Request failed: {:?}", err);
+
+                    if self.state == LobbyState::CreatingRoom {
+                        self.state = LobbyState::Idle;
+                    }
+                }
                 msg => {
                     log::info!("Unhandled message: {:?}", msg);
                 }
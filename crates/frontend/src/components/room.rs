@@ -1,14 +1,24 @@
+use planpoker_common::CardValue;
+use planpoker_common::ChatMessage;
+use planpoker_common::Reaction;
+use planpoker_common::RoomError;
 use planpoker_common::RoomInfo;
 use planpoker_common::RoomMessage;
 use planpoker_common::RoomRequest;
+use planpoker_common::RoomSettings;
+use planpoker_common::Rounding;
+use planpoker_common::Story;
 use planpoker_common::UserId;
 use planpoker_common::Vote;
+use std::time::Duration;
 use yew::prelude::*;
 use yew_router::push_route;
+use yew_services::interval::{IntervalService, IntervalTask};
 
 use crate::agents::RoomAgent;
 use crate::components::card::Card;
 use crate::components::loading::Loading;
+use crate::notifications::{self, NotificationSettings};
 use crate::route::Route;
 
 #[derive(Properties, Clone, Copy)]
@@ -25,13 +35,64 @@ pub struct Room {
     user_info: Option<UserId>,
 
     vote: Option<u32>,
+    /// Title of the story the admin is about to add
+    story_input: String,
+    /// Final estimate typed by the admin
+    estimate_input: String,
+
+    chat: Vec<ChatMessage>,
+    chat_input: String,
+    /// Send chat messages as comments on the current story
+    chat_thread: bool,
+
+    /// Reactions being animated, with the time they disappear in milliseconds since the epoch
+    reactions: Vec<(Reaction, f64)>,
+    /// Until when the nudge notification is shown, in milliseconds since the epoch
+    nudge_deadline: Option<f64>,
+
+    notification_settings: NotificationSettings,
+    /// Events the user has missed while the tab was in the background
+    unread: u32,
+    /// Title of the page without the unread count
+    page_title: String,
+
+    /// When the round timer runs out, in milliseconds since the epoch
+    timer_deadline: Option<f64>,
+    timer_auto_reveal: bool,
+    /// When the votes will be revealed automatically, in milliseconds since the epoch
+    reveal_deadline: Option<f64>,
+    /// Re-renders the countdowns while they are running
+    _tick: Option<IntervalTask>,
 }
 
 pub enum Msg {
     Request(RoomRequest),
     Response(RoomMessage),
+    Tick,
+    ToggleAutoReveal,
+    UpdateStoryInput(String),
+    AddStory,
+    UpdateEstimateInput(String),
+    SetEstimate,
+    UpdateChatInput(String),
+    ToggleChatThread,
+    SendChat,
+    UpdateNotificationSettings(NotificationSettings),
 }
 
+/// Timer lengths the admin can choose from, in seconds
+const TIMER_PRESETS: [u32; 4] = [30, 60, 120, 300];
+/// Delays the admin can choose from for the automatic reveal, in seconds
+const AUTO_REVEAL_DELAYS: [u32; 4] = [0, 3, 5, 10];
+/// Same as the limit enforced by the server
+const MAX_CHAT_LENGTH: usize = 1000;
+/// How many chat messages are kept
+const CHAT_HISTORY_LENGTH: usize = 200;
+/// How long a reaction is animated, in milliseconds
+const REACTION_DURATION: f64 = 3000.0;
+/// How long the nudge notification is shown, in milliseconds
+const NUDGE_DURATION: f64 = 5000.0;
+
 impl Component for Room {
     type Message = Msg;
     type Properties = RoomProps;
@@ -44,6 +105,20 @@ impl Component for Room {
             room_info: None,
             user_info: None,
             vote: None,
+            story_input: String::new(),
+            estimate_input: String::new(),
+            chat: vec![],
+            chat_input: String::new(),
+            chat_thread: false,
+            reactions: vec![],
+            nudge_deadline: None,
+            notification_settings: NotificationSettings::load(),
+            unread: 0,
+            page_title: notifications::page_title(),
+            timer_deadline: None,
+            timer_auto_reveal: false,
+            reveal_deadline: None,
+            _tick: None,
         }
     }
 
@@ -51,6 +126,45 @@ impl Component for Room {
         match msg {
             Msg::Request(req) => self.send_request(req),
             Msg::Response(res) => self.handle_response(res),
+            Msg::Tick => self.expire_notices(),
+            Msg::ToggleAutoReveal => self.timer_auto_reveal = !self.timer_auto_reveal,
+            Msg::UpdateStoryInput(title) => self.story_input = title,
+            Msg::AddStory => {
+                let title = std::mem::take(&mut self.story_input);
+
+                if !title.trim().is_empty() {
+                    self.send_request(RoomRequest::AddStory(title));
+                }
+            }
+            Msg::UpdateEstimateInput(estimate) => self.estimate_input = estimate,
+            Msg::SetEstimate => match self.estimate_input.trim().parse() {
+                Ok(estimate) => {
+                    self.estimate_input.clear();
+                    self.send_request(RoomRequest::SetEstimate(estimate));
+                }
+                Err(_) => log::warn!("Invalid estimate: {}", self.estimate_input),
+            },
+            Msg::UpdateNotificationSettings(settings) => {
+                if settings.enabled && !self.notification_settings.enabled {
+                    notifications::request_permission();
+                }
+
+                settings.save();
+                self.notification_settings = settings;
+            }
+            Msg::UpdateChatInput(text) => self.chat_input = text,
+            Msg::ToggleChatThread => self.chat_thread = !self.chat_thread,
+            Msg::SendChat => {
+                let text = std::mem::take(&mut self.chat_input);
+                let story = match self.room_info.as_ref() {
+                    Some(room_info) if self.chat_thread => room_info.current_story,
+                    _ => None,
+                };
+
+                if !text.trim().is_empty() {
+                    self.send_request(RoomRequest::SendChat { text, story });
+                }
+            }
         };
 
         true
@@ -62,24 +176,29 @@ impl Component for Room {
 
     fn view(&self) -> Html {
         if let Some(room_info) = self.room_info.as_ref() {
-            let vote_results = if room_info.revealed {
+            let vote_results = if room_info.state.is_revealed() {
                 let votes = room_info
-                    .users
-                    .iter()
-                    .filter_map(|user| user.vote.value())
+                    .revealed_cards()
+                    .into_iter()
                     .filter_map(|card_index| room_info.cards.get(card_index as usize))
                     .filter_map(|card| card.value())
+                    .map(CardValue::as_f64)
                     .collect::<Vec<_>>();
-                log::info!("votes: {:?}", &votes);
-                let sum = votes.iter().sum::<u32>() as f32;
-                log::info!("sum: {}", sum);
 
-                let avg = sum / votes.len() as f32;
-
-                log::info!("avg: {:?}", avg);
+                // Without any numeric vote, such as only `?` cards, there is no average.
+                let average = if votes.is_empty() {
+                    html! {}
+                } else {
+                    let avg = votes.iter().sum::<f64>() / votes.len() as f64;
+                    html! { <p>{ format!("Avg: {:.1}", avg) }</p> }
+                };
 
                 html! {
-                    <p>{ "Avg: " }{avg}</p>
+                    <>
+                    { self.distribution_view(room_info) }
+                    { average }
+                    { self.estimate_view(room_info) }
+                    </>
                 }
             } else {
                 html! {}
@@ -91,6 +210,26 @@ impl Component for Room {
                         <div class="admin-actions">
                             <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::Reveal))>{ "Reveal" }</button>
                             <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::Reset))>{ "Reset" }</button>
+                            {
+                                if room_info.state.accepts_votes() {
+                                    html! {
+                                        <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::Nudge))>{ "Nudge" }</button>
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                            {
+                                if room_info.state.is_revealed() {
+                                    html! {
+                                        <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::Revote))>{ "Re-vote" }</button>
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                            { self.timer_actions_view() }
+                            { self.settings_view(room_info) }
                         </div>
                     }
                 }
@@ -99,10 +238,18 @@ impl Component for Room {
 
             html! {
                 <>
+                { self.nudge_view() }
+                { self.reactions_view() }
+                { self.stories_view(room_info) }
+                { self.timer_view() }
+                { self.pending_reveal_view() }
                 { self.cards_view(room_info) }
                 { self.users_view(room_info) }
                 { vote_results }
                 { admin_actions }
+                { self.history_view(room_info) }
+                { self.chat_view(room_info) }
+                { self.notification_settings_view() }
                 </>
             }
         } else {
@@ -117,7 +264,6 @@ impl Room {
     fn cards_view(&self, room_info: &RoomInfo) -> Html {
         let card_views = room_info.cards.iter().enumerate().map(|(i, card)| {
             let selected = self.vote == Some(i as u32);
-            log::info!("{:?} == Some({}) : {}", self.vote, i, selected);
             html! {
                 <Card
                     onclick=self.link.callback(move |_| Msg::Request(RoomRequest::Vote(i as u32)))
@@ -131,21 +277,538 @@ impl Room {
         }
     }
 
+    fn timer_view(&self) -> Html {
+        match self.timer_deadline {
+            Some(deadline) => {
+                let remaining = ((deadline - js_sys::Date::now()) / 1000.0).ceil().max(0.0) as u32;
+
+                html! {
+                    <div class="timer">
+                        { format!("{}:{:02}", remaining / 60, remaining % 60) }
+                    </div>
+                }
+            }
+            None => html! {},
+        }
+    }
+
+    fn pending_reveal_view(&self) -> Html {
+        match self.reveal_deadline {
+            Some(deadline) => {
+                let remaining = ((deadline - js_sys::Date::now()) / 1000.0).ceil().max(0.0) as u32;
+
+                html! {
+                    <div class="pending-reveal">
+                        { format!("Everyone has voted. Revealing in {} s", remaining) }
+                    </div>
+                }
+            }
+            None => html! {},
+        }
+    }
+
+    fn settings_view(&self, room_info: &RoomInfo) -> Html {
+        let settings = room_info.settings.clone();
+
+        let toggle_auto_reveal = {
+            let settings = RoomSettings {
+                auto_reveal: !settings.auto_reveal,
+                ..settings.clone()
+            };
+            self.link
+                .callback(move |_| Msg::Request(RoomRequest::UpdateSettings(settings.clone())))
+        };
+
+        let delays = AUTO_REVEAL_DELAYS.iter().map(|&delay| {
+            let class = if settings.auto_reveal_delay == delay {
+                "selected"
+            } else {
+                ""
+            };
+            let disabled = !settings.auto_reveal;
+            let settings = RoomSettings {
+                auto_reveal_delay: delay,
+                ..settings.clone()
+            };
+
+            html! {
+                <button
+                    class=class
+                    disabled=disabled
+                    onclick=self.link.callback(move |_| Msg::Request(RoomRequest::UpdateSettings(settings.clone())))>
+                    { if delay == 0 { "No delay".to_owned() } else { format!("{} s", delay) } }
+                </button>
+            }
+        });
+
+        let roundings = Rounding::ALL.iter().map(|&rounding| {
+            let class = if settings.rounding == rounding {
+                "selected"
+            } else {
+                ""
+            };
+            let settings = RoomSettings {
+                rounding,
+                ..settings.clone()
+            };
+
+            html! {
+                <button
+                    class=class
+                    onclick=self.link.callback(move |_| Msg::Request(RoomRequest::UpdateSettings(settings.clone())))>
+                    { rounding.name() }
+                </button>
+            }
+        });
+
+        let toggle_anonymous = {
+            let settings = RoomSettings {
+                anonymous: !settings.anonymous,
+                ..settings.clone()
+            };
+            self.link
+                .callback(move |_| Msg::Request(RoomRequest::UpdateSettings(settings.clone())))
+        };
+
+        html! {
+            <div class="room-settings">
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=settings.auto_reveal
+                        onclick=toggle_auto_reveal />
+                    { "Reveal when everyone has voted" }
+                </label>
+                { for delays }
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=settings.anonymous
+                        disabled=room_info.state.is_revealed()
+                        onclick=toggle_anonymous />
+                    { "Anonymous voting" }
+                </label>
+                <div class="rounding">
+                    { for roundings }
+                </div>
+            </div>
+        }
+    }
+
+    fn timer_actions_view(&self) -> Html {
+        let auto_reveal = self.timer_auto_reveal;
+
+        let presets = TIMER_PRESETS.iter().map(|&seconds| {
+            let label = if seconds < 60 {
+                format!("{}s", seconds)
+            } else {
+                format!("{} min", seconds / 60)
+            };
+
+            html! {
+                <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::StartTimer { seconds, auto_reveal }))>
+                    { label }
+                </button>
+            }
+        });
+
+        html! {
+            <div class="timer-actions">
+                { for presets }
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=self.timer_auto_reveal
+                        onclick=self.link.callback(|_| Msg::ToggleAutoReveal) />
+                    { "Reveal when time runs out" }
+                </label>
+                {
+                    if self.timer_deadline.is_some() {
+                        html! {
+                            <button onclick=self.link.callback(|_| Msg::Request(RoomRequest::StopTimer))>{ "Stop timer" }</button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    }
+
+    /// Histogram of the votes, shown instead of the per user votes in anonymous mode.
+    fn distribution_view(&self, room_info: &RoomInfo) -> Html {
+        let distribution = match room_info.distribution.as_ref() {
+            Some(distribution) => distribution,
+            None => return html! {},
+        };
+
+        let max = distribution.iter().map(|c| c.count).max().unwrap_or(1);
+
+        let bars = distribution.iter().map(|c| {
+            let label = room_info
+                .cards
+                .get(c.card as usize)
+                .map_or("", |card| card.as_str());
+            let style = format!("width: {}%", c.count * 100 / max);
+
+            html! {
+                <div class="histogram-row">
+                    <span class="histogram-label">{ label }</span>
+                    <span class="histogram-bar" style=style></span>
+                    <span class="histogram-count">{ c.count }</span>
+                </div>
+            }
+        });
+
+        html! {
+            <div class="histogram">
+                { for bars }
+            </div>
+        }
+    }
+
+    fn stories_view(&self, room_info: &RoomInfo) -> Html {
+        let is_admin = self.user_info == Some(room_info.admin);
+
+        let story_views = room_info.stories.iter().enumerate().map(|(i, story)| {
+            let current = room_info.current_story == Some(i);
+            let class = if current { "story current" } else { "story" };
+
+            html! {
+                <li class=class>
+                    {
+                        match story.issue.as_ref() {
+                            Some(issue) if issue.has_web_url() => html! {
+                                <a class="issue" href=issue.url.clone() target="_blank">{ &issue.key }</a>
+                            },
+                            Some(issue) => html! {
+                                <span class="issue">{ &issue.key }</span>
+                            },
+                            None => html! {},
+                        }
+                    }
+                    { &story.title }
+                    {
+                        match story.estimate {
+                            Some(estimate) => format!(" ({})", estimate),
+                            None => String::new(),
+                        }
+                    }
+                    {
+                        if is_admin && !current {
+                            html! {
+                                <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::SelectStory(i)))>{ "Vote" }</button>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
+                </li>
+            }
+        });
+
+        let add_story = if is_admin {
+            html! {
+                <div class="add-story">
+                    <input
+                        type="text"
+                        placeholder="Story title"
+                        value=self.story_input.clone()
+                        oninput=self.link.callback(|e: InputData| Msg::UpdateStoryInput(e.value)) />
+                    <button onclick=self.link.callback(|_| Msg::AddStory)>{ "Add story" }</button>
+                </div>
+            }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <div class="stories">
+                <ul>
+                    { for story_views }
+                </ul>
+                { add_story }
+            </div>
+        }
+    }
+
+    fn estimate_view(&self, room_info: &RoomInfo) -> Html {
+        let is_admin = self.user_info == Some(room_info.admin);
+        let estimate = room_info.current_story().and_then(|story| story.estimate);
+        let suggestion = room_info.suggested_estimate();
+
+        let suggested_card = match room_info
+            .suggested_card()
+            .and_then(|i| room_info.cards.get(i as usize))
+        {
+            Some(card) => {
+                let onclick = match suggestion {
+                    Some(suggestion) if is_admin => self
+                        .link
+                        .callback(move |_| Msg::Request(RoomRequest::SetEstimate(suggestion))),
+                    _ => Callback::noop(),
+                };
+
+                html! {
+                    <div class="suggestion">
+                        <span>{ "Suggested" }</span>
+                        <Card
+                            onclick=onclick
+                            selected=estimate.is_some() && estimate == suggestion
+                            value=card.as_str().to_owned() />
+                    </div>
+                }
+            }
+            None => html! {},
+        };
+
+        let current = match estimate {
+            Some(estimate) => html! {
+                <p class="estimate">{ "Estimate: " }{ estimate }</p>
+            },
+            None => html! {},
+        };
+
+        let actions = if is_admin {
+            let accept = match suggestion {
+                Some(suggestion) if estimate != Some(suggestion) => html! {
+                    <button onclick=self.link.callback(move |_| Msg::Request(RoomRequest::SetEstimate(suggestion)))>
+                        { format!("Use {}", suggestion) }
+                    </button>
+                },
+                _ => html! {},
+            };
+
+            html! {
+                <div class="estimate-actions">
+                    { accept }
+                    <input
+                        type="number"
+                        min="0"
+                        step="any"
+                        placeholder="Estimate"
+                        value=self.estimate_input.clone()
+                        oninput=self.link.callback(|e: InputData| Msg::UpdateEstimateInput(e.value)) />
+                    <button onclick=self.link.callback(|_| Msg::SetEstimate)>{ "Set estimate" }</button>
+                </div>
+            }
+        } else {
+            html! {}
+        };
+
+        html! {
+            <>
+            { suggested_card }
+            { current }
+            { actions }
+            </>
+        }
+    }
+
+    /// Earlier rounds of the current story, shown while voting on it again.
+    fn history_view(&self, room_info: &RoomInfo) -> Html {
+        let story = match room_info.current_story() {
+            Some(story) if !story.rounds.is_empty() => story,
+            _ => return html! {},
+        };
+
+        let rounds = story.rounds.iter().enumerate().map(|(i, round)| {
+            let votes = round
+                .votes
+                .iter()
+                .filter_map(|v| room_info.cards.get(v.card as usize))
+                .map(|card| card.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let estimate = match round.estimate {
+                Some(estimate) => format!(" (estimate: {})", estimate),
+                None => String::new(),
+            };
+
+            html! {
+                <li>{ format!("Round {}: {}{}", i + 1, votes, estimate) }</li>
+            }
+        });
+
+        html! {
+            <div class="history">
+                <h3>{ &story.title }</h3>
+                <ol>
+                    { for rounds }
+                </ol>
+            </div>
+        }
+    }
+
+    fn notification_settings_view(&self) -> Html {
+        let options: [(&str, fn(&mut NotificationSettings) -> &mut bool); 7] = [
+            ("Desktop notifications", |s| &mut s.enabled),
+            ("Votes revealed", |s| &mut s.cards_revealed),
+            ("New round", |s| &mut s.reset),
+            ("Nudges", |s| &mut s.nudge),
+            ("Time is up", |s| &mut s.timer_expired),
+            ("Estimate set", |s| &mut s.estimate_set),
+            ("Chat messages", |s| &mut s.chat),
+        ];
+
+        let checkboxes = options.iter().map(|&(label, option)| {
+            let mut settings = self.notification_settings.clone();
+            let checked = *option(&mut settings);
+            *option(&mut settings) = !checked;
+
+            html! {
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=checked
+                        onclick=self.link.callback(move |_| Msg::UpdateNotificationSettings(settings.clone())) />
+                    { label }
+                </label>
+            }
+        });
+
+        html! {
+            <div class="notification-settings">
+                { for checkboxes }
+            </div>
+        }
+    }
+
+    fn nudge_view(&self) -> Html {
+        if self.nudge_deadline.is_some() {
+            html! {
+                <div class="nudge">{ "Waiting for your vote!" }</div>
+            }
+        } else {
+            html! {}
+        }
+    }
+
+    fn reactions_view(&self) -> Html {
+        let buttons = Reaction::ALL.iter().map(|&reaction| {
+            html! {
+                <button
+                    class="reaction-button"
+                    onclick=self.link.callback(move |_| Msg::Request(RoomRequest::React(reaction)))>
+                    { reaction.emoji() }
+                </button>
+            }
+        });
+
+        // Spread the reactions out so they don't all float up at the same spot.
+        let floating = self.reactions.iter().enumerate().map(|(i, (reaction, _))| {
+            let style = format!("left: {}%", (i * 37) % 90 + 5);
+
+            html! {
+                <span class="reaction" style=style>{ reaction.emoji() }</span>
+            }
+        });
+
+        html! {
+            <div class="reactions">
+                { for buttons }
+                <div class="reactions-floating">
+                    { for floating }
+                </div>
+            </div>
+        }
+    }
+
+    fn chat_view(&self, room_info: &RoomInfo) -> Html {
+        let messages = self.chat.iter().map(|message| {
+            let name = message.name.clone().unwrap_or_else(|| {
+                match room_info
+                    .users
+                    .iter()
+                    .position(|u| u.user_id == message.user_id)
+                {
+                    Some(i) => format!("User {}", i),
+                    None => "Someone".to_owned(),
+                }
+            });
+            let story = message
+                .story
+                .and_then(|i| room_info.stories.get(i))
+                .map(|story| html! { <span class="chat-story">{ &story.title }</span> });
+            let sent_at = js_sys::Date::new(&(message.sent_at as f64 * 1000.0).into());
+
+            html! {
+                <li class="chat-message">
+                    <span class="chat-time">
+                        { format!("{:02}:{:02}", sent_at.get_hours(), sent_at.get_minutes()) }
+                    </span>
+                    <span class="chat-name">{ name }</span>
+                    { story.unwrap_or_else(|| html! {}) }
+                    <span class="chat-text">{ &message.text }</span>
+                </li>
+            }
+        });
+
+        let thread = match room_info.current_story() {
+            Some(story) => html! {
+                <label>
+                    <input
+                        type="checkbox"
+                        checked=self.chat_thread
+                        onclick=self.link.callback(|_| Msg::ToggleChatThread) />
+                    { format!("About \"{}\"", story.title) }
+                </label>
+            },
+            None => html! {},
+        };
+
+        html! {
+            <div class="chat">
+                <ul class="chat-messages">
+                    { for messages }
+                </ul>
+                <textarea
+                    placeholder="Message"
+                    maxlength=MAX_CHAT_LENGTH.to_string()
+                    value=self.chat_input.clone()
+                    oninput=self.link.callback(|e: InputData| Msg::UpdateChatInput(e.value)) />
+                { thread }
+                <button onclick=self.link.callback(|_| Msg::SendChat)>{ "Send" }</button>
+            </div>
+        }
+    }
+
     fn users_view(&self, room_info: &RoomInfo) -> Html {
+        let outliers = if room_info.state.is_revealed() {
+            None
+        } else {
+            room_info
+                .current_story()
+                .and_then(Story::last_round)
+                .and_then(|round| round.outliers(&room_info.cards))
+        };
+
         let user_views = room_info.users.iter().enumerate().map(|(i, u)| {
+            let name = u.name.clone().unwrap_or_else(|| format!("User {}", i));
+
             let a = match u.vote {
-                Vote::None => format!("User {} (not voted)", i),
-                Vote::Unknown => format!("User {} (voted)", i),
+                Vote::None => format!("{} (not voted)", name),
+                Vote::Unknown => format!("{} (voted)", name),
                 Vote::Revealed(vote) => format!(
-                    "User {} (vote: {})",
-                    i,
-                    room_info.cards[vote as usize].as_str()
+                    "{} (vote: {})",
+                    name,
+                    room_info
+                        .cards
+                        .get(vote as usize)
+                        .map_or("?", |card| card.as_str())
                 ),
                 _ => "".to_string(),
             };
 
+            let class = match outliers.as_ref() {
+                Some(o) if o.lowest.contains(&u.user_id) => "user lowest",
+                Some(o) if o.highest.contains(&u.user_id) => "user highest",
+                _ => "user",
+            };
+
             html! {
-                { a }
+                <div class=class>{ a }</div>
             }
         });
 
@@ -169,6 +832,8 @@ impl Room {
     }
 
     fn handle_response(&mut self, msg: RoomMessage) {
+        self.notify(&msg);
+
         match msg {
             RoomMessage::UserInfo(user_info) => {
                 self.user_info = Some(user_info);
@@ -194,15 +859,120 @@ impl Room {
             }
             RoomMessage::RoomInfo(room_info) => {
                 log::info!("Room info: {:?}", &room_info);
+                self.update_timer(&room_info);
                 self.room_info = Some(room_info);
             }
-            RoomMessage::Reset => {
+            RoomMessage::Reset | RoomMessage::Revote => {
                 self.vote = None;
+                self.reveal_deadline = None;
+            }
+            RoomMessage::ChatHistory(messages) => {
+                self.chat = messages;
+            }
+            RoomMessage::Chat(message) => {
+                if self.chat.len() >= CHAT_HISTORY_LENGTH {
+                    self.chat.remove(0);
+                }
+
+                self.chat.push(message);
+            }
+            RoomMessage::Reaction { reaction, .. } => {
+                self.reactions
+                    .push((reaction, js_sys::Date::now() + REACTION_DURATION));
+                self.update_tick();
+            }
+            RoomMessage::Nudge => {
+                self.nudge_deadline = Some(js_sys::Date::now() + NUDGE_DURATION);
+                self.update_tick();
+            }
+            RoomMessage::RevealPending(seconds) => {
+                self.reveal_deadline = Some(js_sys::Date::now() + seconds as f64 * 1000.0);
+                self.update_tick();
+            }
+            RoomMessage::RevealCancelled | RoomMessage::CardsRevealed => {
+                self.reveal_deadline = None;
+                self.update_tick();
+            }
+            RoomMessage::Error(RoomError::RoomFull(id)) => {
+                log::info!("Room is full: {}", id);
+                self.go_to_lobby();
+            }
+            RoomMessage::Error(RoomError::NoSuchCard(card)) => {
+                log::warn!("No such card: {}", card);
+                self.vote = None;
+            }
+            RoomMessage::Error(err) => {
+                log::warn!("Request failed: {:?}", err);
             }
             msg => println!("Unhandled msg: {:?}", msg),
         }
     }
 
+    /// Keeps the local countdown in sync with the timer sent by the server.
+    fn update_timer(&mut self, room_info: &RoomInfo) {
+        self.timer_deadline = room_info
+            .timer
+            .as_ref()
+            .map(|timer| js_sys::Date::now() + timer.remaining_ms as f64);
+
+        self.update_tick();
+    }
+
+    /// Counts the event as unread, and shows a notification for it,
+    /// if the tab is in the background and the user wants to know about it.
+    fn notify(&mut self, msg: &RoomMessage) {
+        if !notifications::page_hidden() {
+            return;
+        }
+
+        if let Some((title, body)) = self.notification_settings.notification_for(msg) {
+            self.unread += 1;
+            notifications::set_page_title(&format!("({}) {}", self.unread, self.page_title));
+
+            if self.notification_settings.enabled {
+                notifications::show(&title, body.as_deref());
+            }
+
+            self.update_tick();
+        }
+    }
+
+    /// Removes the reactions and notifications that have been shown long enough,
+    /// and clears the unread count once the user is back.
+    fn expire_notices(&mut self) {
+        let now = js_sys::Date::now();
+
+        if self.unread > 0 && !notifications::page_hidden() {
+            self.unread = 0;
+            notifications::set_page_title(&self.page_title);
+        }
+
+        self.reactions.retain(|(_, until)| *until > now);
+
+        if self.nudge_deadline.map_or(false, |until| until <= now) {
+            self.nudge_deadline = None;
+        }
+
+        self.update_tick();
+    }
+
+    /// Keeps the interval running only while there is a countdown or animation to show.
+    fn update_tick(&mut self) {
+        if self.timer_deadline.is_none()
+            && self.reveal_deadline.is_none()
+            && self.reactions.is_empty()
+            && self.nudge_deadline.is_none()
+            && self.unread == 0
+        {
+            self._tick = None;
+        } else if self._tick.is_none() {
+            self._tick = Some(IntervalService::spawn(
+                Duration::from_millis(500),
+                self.link.callback(|_| Msg::Tick),
+            ));
+        }
+    }
+
     fn join_room(&mut self) {
         log::info!("joining room {}", self.props.id);
 
@@ -1,5 +1,6 @@
 mod agents;
 mod components;
+mod notifications;
 mod route;
 
 use agents::WebSocketAgent;
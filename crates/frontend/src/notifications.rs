@@ -0,0 +1,127 @@
+use planpoker_common::RoomMessage;
+use serde::{Deserialize, Serialize};
+use web_sys::{Notification, NotificationOptions, NotificationPermission};
+use yew::format::Json;
+use yew_services::storage::{Area, StorageService};
+
+const SETTINGS_KEY: &str = "planpoker.notifications";
+
+/// Which room events the user wants to be notified about while the tab is in the background.
+/// Stored in the local storage of the browser.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Show desktop notifications, the unread count in the title is shown regardless
+    pub enabled: bool,
+    pub cards_revealed: bool,
+    pub reset: bool,
+    pub nudge: bool,
+    pub timer_expired: bool,
+    pub estimate_set: bool,
+    pub chat: bool,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cards_revealed: true,
+            reset: true,
+            nudge: true,
+            timer_expired: true,
+            estimate_set: false,
+            chat: false,
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn load() -> Self {
+        let storage = match StorageService::new(Area::Local) {
+            Ok(storage) => storage,
+            Err(_) => return Self::default(),
+        };
+
+        let Json(settings) = storage.restore(SETTINGS_KEY);
+        settings.unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        match StorageService::new(Area::Local) {
+            Ok(mut storage) => storage.store(SETTINGS_KEY, Json(self)),
+            Err(err) => log::warn!("Can't save notification settings: {}", err),
+        }
+    }
+
+    /// Title and body of the notification for the message, if the user wants to be notified about it.
+    pub fn notification_for(&self, msg: &RoomMessage) -> Option<(String, Option<String>)> {
+        match msg {
+            RoomMessage::CardsRevealed if self.cards_revealed => {
+                Some(("Votes revealed".to_owned(), None))
+            }
+            RoomMessage::Reset | RoomMessage::Revote if self.reset => {
+                Some(("New round started".to_owned(), None))
+            }
+            RoomMessage::Nudge if self.nudge => Some(("Waiting for your vote".to_owned(), None)),
+            RoomMessage::TimerExpired if self.timer_expired => {
+                Some(("Time is up".to_owned(), None))
+            }
+            RoomMessage::EstimateSet(estimate) if self.estimate_set => {
+                Some((format!("Estimate set: {}", estimate), None))
+            }
+            RoomMessage::Chat(message) if self.chat => {
+                let title = match message.name.as_ref() {
+                    Some(name) => format!("Message from {}", name),
+                    None => "New message".to_owned(),
+                };
+
+                Some((title, Some(message.text.clone())))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Asks the user for permission to show notifications. The answer is checked when showing them.
+pub fn request_permission() {
+    if let Err(err) = Notification::request_permission() {
+        log::warn!("Can't request notification permission: {:?}", err);
+    }
+}
+
+pub fn show(title: &str, body: Option<&str>) {
+    if Notification::permission() != NotificationPermission::Granted {
+        return;
+    }
+
+    let mut options = NotificationOptions::new();
+    // Replace the previous notification instead of stacking them up.
+    options.tag("planpoker");
+
+    if let Some(body) = body {
+        options.body(body);
+    }
+
+    if let Err(err) = Notification::new_with_options(title, &options) {
+        log::warn!("Can't show notification: {:?}", err);
+    }
+}
+
+pub fn page_hidden() -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .map_or(false, |document| document.hidden())
+}
+
+pub fn page_title() -> String {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .map(|document| document.title())
+        .unwrap_or_default()
+}
+
+pub fn set_page_title(title: &str) {
+    if let Some(document) = web_sys::window().and_then(|window| window.document()) {
+        document.set_title(title);
+    }
+}
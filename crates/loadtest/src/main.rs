@@ -0,0 +1,215 @@
+mod scenario;
+mod stats;
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use planpoker_client::Client;
+use planpoker_common::RoomId;
+use structopt::StructOpt;
+use tokio::{sync::Semaphore, task::JoinHandle};
+
+use scenario::{RoomClock, Scenario};
+use stats::{phase_done, Memory, Metric, Stats};
+
+/// How often the memory of the server is sampled for the peak
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Plays rounds of voting in many rooms at once and reports the latencies of the broadcasts.
+///
+/// Every room has an admin, who reveals once all members voted and then resets. The server
+/// should run with `PLANPOKER_IP_RATE_LIMIT` raised, since all connections come from one address.
+/// Rejections by other limits of the server are reported with the setting to raise.
+#[derive(StructOpt)]
+#[structopt(name = "planpoker-loadtest")]
+struct Options {
+    /// Websocket endpoint of the backend
+    #[structopt(long, env = "PLANPOKER_URL", default_value = "ws://127.0.0.1:8082/ws/")]
+    url: String,
+    #[structopt(long, default_value = "100")]
+    rooms: usize,
+    /// Members voting in every room, besides the admin
+    #[structopt(long, default_value = "10")]
+    users: usize,
+    #[structopt(long, default_value = "5")]
+    rounds: usize,
+    /// How many connections are opened at once
+    #[structopt(long, default_value = "100")]
+    concurrency: usize,
+    /// Longest random delay in seconds before a member votes
+    #[structopt(long, default_value = "1")]
+    think_time: f64,
+    /// How long in seconds to wait for a round before giving up on a room
+    #[structopt(long, default_value = "30")]
+    timeout: f64,
+    /// Process id of the server for reporting its memory, found by name if it runs locally
+    #[structopt(long)]
+    server_pid: Option<u32>,
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::from_args();
+
+    if let Err(err) = run(options).await {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(options: Options) -> Result<()> {
+    let stats = Arc::new(Stats::new());
+    let connecting = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let scenario = Arc::new(Scenario {
+        rounds: options.rounds,
+        think_time: Duration::from_secs_f64(options.think_time.max(0.0)),
+        timeout: Duration::from_secs_f64(options.timeout.max(0.0)),
+    });
+
+    let mut memory = Memory::find(options.server_pid);
+    if memory.is_none() {
+        println!("Server process not found, use --server-pid to report its memory");
+    }
+    if let Some(memory) = memory.as_mut() {
+        memory.checkpoint("before");
+    }
+
+    let started = Instant::now();
+    let mut rooms = Vec::new();
+    for _ in 0..options.rooms {
+        let (url, connecting) = (options.url.clone(), connecting.clone());
+
+        rooms.push(tokio::spawn(async move {
+            let _permit = connecting.acquire().await;
+            let mut client = connect(url).await?;
+            let room = client.create_room().await?;
+            client.join(room).await?;
+            Ok::<_, planpoker_client::Error>((client, room))
+        }));
+    }
+
+    let mut admins = Vec::new();
+    for res in wait_all(rooms, &mut memory).await {
+        match res {
+            Ok(admin) => admins.push(admin),
+            Err(err) => stats.client_error("creating room", &err),
+        }
+    }
+    if admins.is_empty() {
+        stats.print();
+        return Err(anyhow!("no room could be created"));
+    }
+    phase_done(&format!("Created {} rooms", admins.len()), started);
+
+    let started = Instant::now();
+    let mut joins = Vec::new();
+    for (_, room) in admins.iter() {
+        for _ in 0..options.users {
+            let (url, room, connecting) = (options.url.clone(), *room, connecting.clone());
+            let stats = stats.clone();
+
+            joins.push(tokio::spawn(async move {
+                let _permit = connecting.acquire().await;
+                let mut client = connect(url).await?;
+
+                let joined = Instant::now();
+                let info = client.join(room).await?;
+                stats.record(Metric::Join, joined.elapsed());
+
+                Ok::<_, planpoker_client::Error>((client, info))
+            }));
+        }
+    }
+
+    let mut members = HashMap::<RoomId, Vec<_>>::new();
+    let mut users = admins.len();
+    for res in wait_all(joins, &mut memory).await {
+        match res {
+            Ok((client, info)) => {
+                members.entry(info.id).or_default().push((client, info));
+                users += 1;
+            }
+            Err(err) => stats.client_error("joining room", &err),
+        }
+    }
+    phase_done(&format!("Connected {} users", users), started);
+    if let Some(memory) = memory.as_mut() {
+        memory.checkpoint("connected");
+    }
+
+    let started = Instant::now();
+    let mut tasks = Vec::new();
+    for (client, room) in admins {
+        let clock = Arc::new(RoomClock::new());
+        let room_members = members.remove(&room).unwrap_or_default();
+
+        tasks.push(tokio::spawn(scenario::admin(
+            client,
+            room_members.len(),
+            scenario.clone(),
+            clock.clone(),
+            stats.clone(),
+        )));
+
+        for (client, info) in room_members {
+            tasks.push(tokio::spawn(scenario::member(
+                client,
+                info,
+                scenario.clone(),
+                clock.clone(),
+                stats.clone(),
+            )));
+        }
+    }
+
+    wait_all(tasks, &mut memory).await;
+    phase_done(&format!("Played {} rounds", options.rounds), started);
+    if let Some(memory) = memory.as_mut() {
+        memory.checkpoint("after rounds");
+    }
+
+    println!();
+    stats.print();
+    if let Some(memory) = memory.as_ref() {
+        println!();
+        memory.print();
+    }
+
+    Ok(())
+}
+
+fn connect(url: String) -> impl std::future::Future<Output = planpoker_client::Result<Client>> {
+    // Lost connections are errors of the server under load, not to be hidden by reconnecting.
+    Client::builder(url).reconnect(false).connect()
+}
+
+/// Waits for the tasks while sampling the memory of the server.
+async fn wait_all<T>(tasks: Vec<JoinHandle<T>>, memory: &mut Option<Memory>) -> Vec<T> {
+    let mut results = Vec::new();
+    let mut sample = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+
+    for mut task in tasks {
+        loop {
+            tokio::select! {
+                res = &mut task => {
+                    match res {
+                        Ok(result) => results.push(result),
+                        Err(err) => eprintln!("Task failed: {}", err),
+                    }
+                    break;
+                }
+                _ = sample.tick() => {
+                    if let Some(memory) = memory.as_mut() {
+                        memory.sample();
+                    }
+                }
+            }
+        }
+    }
+
+    results
+}
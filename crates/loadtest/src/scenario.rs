@@ -0,0 +1,167 @@
+use std::{
+    future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use planpoker_client::{Client, Error, Event};
+use planpoker_common::{RoomInfo, RoomMessage, Vote};
+use rand::{thread_rng, Rng};
+use tokio::time::{self, Instant};
+
+use crate::stats::{Metric, Stats};
+
+pub struct Scenario {
+    pub rounds: usize,
+    /// Longest random delay before a member votes
+    pub think_time: Duration,
+    /// How long to wait for anything before giving up on a room
+    pub timeout: Duration,
+}
+
+impl Scenario {
+    fn vote_time(&self) -> Instant {
+        Instant::now() + thread_rng().gen_range(Duration::ZERO..=self.think_time)
+    }
+}
+
+/// When the admin of a room last revealed and reset, to measure the broadcasts against
+pub struct RoomClock {
+    start: Instant,
+    reveal: AtomicU64,
+    reset: AtomicU64,
+}
+
+impl RoomClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            reveal: AtomicU64::new(0),
+            reset: AtomicU64::new(0),
+        }
+    }
+
+    fn mark(&self, at: &AtomicU64) {
+        at.store(self.start.elapsed().as_micros() as u64, Ordering::SeqCst);
+    }
+
+    fn since(&self, at: &AtomicU64) -> Duration {
+        self.start.elapsed() - Duration::from_micros(at.load(Ordering::SeqCst))
+    }
+}
+
+/// Plays the rounds as the admin: reveals once all members voted, then resets.
+pub async fn admin(
+    mut client: Client,
+    members: usize,
+    scenario: Arc<Scenario>,
+    clock: Arc<RoomClock>,
+    stats: Arc<Stats>,
+) {
+    for _ in 0..scenario.rounds {
+        let all_voted = client.wait_for(|msg| match msg {
+            RoomMessage::RoomInfo(info) if !info.state.is_revealed() => {
+                let voted = info.users.iter().filter(|u| u.vote != Vote::None).count();
+                (voted >= members).then_some(())
+            }
+            _ => None,
+        });
+
+        // Reveal anyway if members are missing, they are reported as timed out.
+        match time::timeout(scenario.timeout, all_voted).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => return stats.client_error("admin", &err),
+            Err(_) => stats.error("admin: timed out waiting for votes"),
+        }
+
+        clock.mark(&clock.reveal);
+        if let Err(err) = client.reveal().await {
+            return stats.client_error("admin", &err);
+        }
+
+        clock.mark(&clock.reset);
+        if let Err(err) = client.reset().await {
+            return stats.client_error("admin", &err);
+        }
+    }
+}
+
+/// Votes once in every round and measures the broadcasts, until the last round is reset.
+pub async fn member(
+    mut client: Client,
+    room: RoomInfo,
+    scenario: Arc<Scenario>,
+    clock: Arc<RoomClock>,
+    stats: Arc<Stats>,
+) {
+    let user_id = client.user_id();
+    let mut cards = room.cards.len();
+    let mut resets = 0;
+    let mut vote_at = Some(scenario.vote_time());
+    let mut voted_at: Option<Instant> = None;
+
+    loop {
+        let vote_timer = async {
+            match vote_at {
+                Some(at) => time::sleep_until(at).await,
+                None => future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = vote_timer => {
+                vote_at = None;
+                if cards > 0 {
+                    let card = thread_rng().gen_range(0..cards) as u32;
+                    if client.vote(card).is_err() {
+                        return stats.client_error("member", &Error::Closed);
+                    }
+                    voted_at = Some(Instant::now());
+                }
+            }
+            event = client.next_event() => match event {
+                Some(Event::Message(RoomMessage::RoomInfo(info))) => {
+                    cards = info.cards.len();
+
+                    let voted = info
+                        .users
+                        .iter()
+                        .any(|u| u.user_id == user_id && u.vote != Vote::None);
+                    if let (true, Some(at)) = (voted, voted_at) {
+                        stats.record(Metric::Vote, at.elapsed());
+                        voted_at = None;
+                    }
+                }
+                Some(Event::Message(RoomMessage::CardsRevealed)) => {
+                    stats.record(Metric::Reveal, clock.since(&clock.reveal));
+                }
+                Some(Event::Message(RoomMessage::Reset)) => {
+                    stats.record(Metric::Reset, clock.since(&clock.reset));
+
+                    resets += 1;
+                    if resets >= scenario.rounds {
+                        let _ = client.leave();
+                        return;
+                    }
+
+                    vote_at = Some(scenario.vote_time());
+                    voted_at = None;
+                }
+                Some(Event::Message(RoomMessage::Error(err))) => {
+                    stats.client_error("member", &Error::Room(err));
+                }
+                Some(Event::Disconnected(reason)) => {
+                    return stats.client_error("member", &Error::Disconnected(reason));
+                }
+                Some(_) => {}
+                None => return stats.client_error("member", &Error::Closed),
+            },
+            _ = time::sleep(scenario.timeout) => {
+                return stats.error("member: timed out waiting for the round to end");
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use hdrhistogram::Histogram;
+use planpoker_client::Error;
+use planpoker_common::RoomError;
+use sysinfo::{Pid, System};
+
+/// Name of the server executable, for finding its process
+const SERVER_NAME: &str = "planpoker-backend";
+/// Latencies above this are recorded as this
+const MAX_LATENCY: Duration = Duration::from_secs(60);
+
+/// What is measured, in the order of the report
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Metric {
+    /// From joining the room to receiving its info
+    Join,
+    /// From voting to receiving the room info with the vote
+    Vote,
+    /// From the admin revealing to each member receiving `CardsRevealed`
+    Reveal,
+    /// From the admin resetting to each member receiving `Reset`
+    Reset,
+}
+
+/// Measurements of all connections, shared by the tasks
+pub struct Stats {
+    latencies: Mutex<BTreeMap<Metric, Histogram<u64>>>,
+    /// Requests rejected by the server, by reason
+    errors: Mutex<BTreeMap<String, u64>>,
+    /// Environment variables of the server limits that rejected requests
+    limits: Mutex<BTreeSet<&'static str>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            latencies: Mutex::new(BTreeMap::new()),
+            errors: Mutex::new(BTreeMap::new()),
+            limits: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn record(&self, metric: Metric, latency: Duration) {
+        let micros = latency.min(MAX_LATENCY).as_micros() as u64;
+
+        self.latencies
+            .lock()
+            .unwrap()
+            .entry(metric)
+            .or_insert_with(|| {
+                Histogram::new_with_max(MAX_LATENCY.as_micros() as u64, 3)
+                    .expect("valid histogram bounds")
+            })
+            .saturating_record(micros.max(1));
+    }
+
+    pub fn error(&self, reason: impl Into<String>) {
+        *self
+            .errors
+            .lock()
+            .unwrap()
+            .entry(reason.into())
+            .or_insert(0) += 1;
+    }
+
+    /// Records an error of the client, noting the limit of the server that caused it, if any.
+    pub fn client_error(&self, context: &str, err: &Error) {
+        if let Error::Room(err) = err {
+            if let Some(limit) = limit_variable(err) {
+                self.limits.lock().unwrap().insert(limit);
+            }
+        }
+
+        self.error(format!("{}: {}", context, err));
+    }
+
+    pub fn print(&self) {
+        for line in self.report() {
+            println!("{}", line);
+        }
+    }
+
+    /// Percentiles of every metric, followed by the errors and the limits to raise
+    fn report(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:<8} {:>8} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            "", "count", "p50 ms", "p90 ms", "p99 ms", "p99.9 ms", "max ms"
+        )];
+
+        let ms = |micros: u64| micros as f64 / 1000.0;
+        for (metric, histogram) in self.latencies.lock().unwrap().iter() {
+            lines.push(format!(
+                "{:<8} {:>8} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+                format!("{:?}", metric),
+                histogram.len(),
+                ms(histogram.value_at_quantile(0.5)),
+                ms(histogram.value_at_quantile(0.9)),
+                ms(histogram.value_at_quantile(0.99)),
+                ms(histogram.value_at_quantile(0.999)),
+                ms(histogram.max()),
+            ));
+        }
+
+        let errors = self.errors.lock().unwrap();
+        if !errors.is_empty() {
+            lines.push(String::new());
+            lines.push("Errors:".to_owned());
+            for (reason, count) in errors.iter() {
+                lines.push(format!("  {:>6} {}", count, reason));
+            }
+        }
+
+        let limits = self.limits.lock().unwrap();
+        if !limits.is_empty() {
+            lines.push(String::new());
+            lines.push(
+                "Requests were rejected by limits of the server, raise them with:".to_owned(),
+            );
+            for limit in limits.iter() {
+                lines.push(format!("  {}", limit));
+            }
+        }
+
+        lines
+    }
+}
+
+/// Environment variable of the server limit behind the rejection, if it was one.
+/// All connections come from one address, which the rate limit per address counts together.
+fn limit_variable(err: &RoomError) -> Option<&'static str> {
+    match err {
+        RoomError::RateLimited => Some("PLANPOKER_IP_RATE_LIMIT"),
+        RoomError::TooManyRooms => Some("PLANPOKER_MAX_ROOMS_PER_USER"),
+        RoomError::RoomFull(_) => Some("PLANPOKER_MAX_ROOM_MEMBERS"),
+        RoomError::ServerFull => Some("PLANPOKER_MAX_ROOMS"),
+        _ => None,
+    }
+}
+
+/// Resident memory of the server process, which must run on the same machine
+pub struct Memory {
+    system: System,
+    pid: Pid,
+    samples: Vec<(String, u64)>,
+    peak: u64,
+}
+
+impl Memory {
+    /// Watches the process with the id, or else the only process of the backend.
+    pub fn find(pid: Option<u32>) -> Option<Self> {
+        let mut system = System::new();
+
+        let pid = match pid {
+            Some(pid) => Pid::from_u32(pid),
+            None => {
+                system.refresh_processes();
+                // The name is cut short on some systems, the executable is not.
+                // Threads are listed too, but have a kind unlike processes.
+                let mut backends = system.processes().values().filter(|p| {
+                    let exe = p.exe().and_then(|exe| exe.file_name());
+                    p.thread_kind().is_none()
+                        && (p.name() == SERVER_NAME || exe == Some(SERVER_NAME.as_ref()))
+                });
+                let pid = backends.next()?.pid();
+                if backends.next().is_some() {
+                    return None;
+                }
+                pid
+            }
+        };
+
+        if !system.refresh_process(pid) {
+            return None;
+        }
+
+        Some(Self {
+            system,
+            pid,
+            samples: Vec::new(),
+            peak: 0,
+        })
+    }
+
+    /// Current memory use in bytes, 0 once the process is gone.
+    pub fn sample(&mut self) -> u64 {
+        self.system.refresh_process(self.pid);
+        let memory = self.system.process(self.pid).map_or(0, |p| p.memory());
+        self.peak = self.peak.max(memory);
+        memory
+    }
+
+    /// Samples the memory, to be reported with the label.
+    pub fn checkpoint(&mut self, label: &str) {
+        let memory = self.sample();
+        self.samples.push((label.to_owned(), memory));
+    }
+
+    pub fn print(&self) {
+        let mb = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+
+        println!("Server memory (pid {}):", self.pid);
+        for (label, memory) in self.samples.iter() {
+            println!("  {:<24} {:>8.1} MB", label, mb(*memory));
+        }
+        println!("  {:<24} {:>8.1} MB", "peak", mb(self.peak));
+    }
+}
+
+/// Prints the phase and how long it took.
+pub fn phase_done(name: &str, started: Instant) {
+    println!("{} in {:.1}s", name, started.elapsed().as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Percentiles of the metric in the report, in milliseconds
+    fn row(stats: &Stats, metric: Metric) -> Vec<f64> {
+        let name = format!("{:?}", metric);
+        let line = stats
+            .report()
+            .into_iter()
+            .find(|line| line.split_whitespace().next() == Some(name.as_str()))
+            .unwrap();
+
+        line.split_whitespace()
+            .skip(1)
+            .map(|value| value.parse().unwrap())
+            .collect()
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        // The histogram keeps three significant digits.
+        assert!(
+            (actual - expected).abs() <= expected / 1000.0 + 0.001,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn reports_the_percentiles_of_each_metric() {
+        let stats = Stats::new();
+        for ms in 1..=1000 {
+            stats.record(Metric::Vote, Duration::from_millis(ms));
+        }
+        stats.record(Metric::Join, Duration::from_micros(1500));
+
+        let vote = row(&stats, Metric::Vote);
+        assert_eq!(vote[0], 1000.0);
+        for (actual, expected) in vote[1..]
+            .iter()
+            .zip([500.0, 900.0, 990.0, 999.0, 1000.0].iter())
+        {
+            assert_near(*actual, *expected);
+        }
+
+        let join = row(&stats, Metric::Join);
+        assert_eq!(join[0], 1.0);
+        assert!(join[1..].iter().all(|&ms| ms == 1.5), "{:?}", join);
+
+        // Metrics are reported in their order, without the ones never recorded.
+        let names = stats
+            .report()
+            .iter()
+            .skip(1)
+            .map(|line| line.split_whitespace().next().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Join", "Vote"]);
+    }
+
+    #[test]
+    fn latencies_are_clamped() {
+        let stats = Stats::new();
+        stats.record(Metric::Reveal, Duration::from_secs(3600));
+        stats.record(Metric::Reset, Duration::from_secs(0));
+
+        let reveal = row(&stats, Metric::Reveal);
+        assert_near(reveal[5], MAX_LATENCY.as_millis() as f64);
+        // Recorded as a microsecond, the lowest value of the histogram
+        let reset = row(&stats, Metric::Reset);
+        assert_eq!(reset[5], 0.0);
+        assert_eq!(reset[0], 1.0);
+    }
+
+    #[test]
+    fn errors_are_counted_by_reason() {
+        let stats = Stats::new();
+        stats.error("member: timed out waiting for the round to end");
+        stats.client_error("member", &Error::Closed);
+        stats.client_error("member", &Error::Closed);
+
+        let report = stats.report();
+        assert_eq!(
+            report[1..],
+            [
+                "",
+                "Errors:",
+                "       2 member: connection closed",
+                "       1 member: timed out waiting for the round to end",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejections_by_limits_name_the_setting() {
+        let stats = Stats::new();
+        stats.client_error("joining room", &Error::Room(RoomError::RateLimited));
+        stats.client_error("joining room", &Error::Room(RoomError::RoomFull(3)));
+        stats.client_error("joining room", &Error::Room(RoomError::RateLimited));
+        stats.client_error("admin", &Error::Room(RoomError::NotAdmin));
+
+        let report = stats.report();
+        let limits = report
+            .iter()
+            .skip_while(|line| !line.contains("limits of the server"))
+            .skip(1)
+            .map(|line| line.trim())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            limits,
+            ["PLANPOKER_IP_RATE_LIMIT", "PLANPOKER_MAX_ROOM_MEMBERS"]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use planpoker_client::Event;
+use planpoker_common::{RoomError, RoomId, RoomInfo, RoomMessage, RoomRequest, UserId};
+
+pub enum Action {
+    Request(RoomRequest),
+    Quit,
+}
+
+/// State of the client, everything shown comes from the server except the card cursor.
+pub struct App {
+    pub room_id: RoomId,
+    pub room: Option<RoomInfo>,
+    pub user_id: UserId,
+    pub connected: bool,
+    /// Index of the card the cursor is on
+    pub selected: usize,
+    /// Own vote, which the server only sends once it is revealed
+    pub vote: Option<u32>,
+    /// Last notice or error, shown in the status line
+    pub status: Option<String>,
+    timer_deadline: Option<Instant>,
+}
+
+impl App {
+    pub fn new(room_id: RoomId, user_id: UserId) -> Self {
+        Self {
+            room_id,
+            room: None,
+            user_id,
+            connected: true,
+            selected: 0,
+            vote: None,
+            status: None,
+            timer_deadline: None,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.room.as_ref().map(|room| room.admin) == Some(self.user_id)
+    }
+
+    /// Same fallback as the frontend for users without a name
+    pub fn user_name(&self, user_id: UserId) -> String {
+        let users = self.room.as_ref().map_or(&[][..], |room| &room.users);
+
+        match users.iter().position(|u| u.user_id == user_id) {
+            Some(i) => users[i]
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("User {}", i)),
+            None => "Someone".to_owned(),
+        }
+    }
+
+    pub fn timer_remaining(&self) -> Option<Duration> {
+        self.timer_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn on_event(&mut self, event: Event) {
+        match event {
+            Event::Connected => {
+                self.connected = true;
+                self.status = None;
+            }
+            Event::Disconnected(reason) => {
+                self.connected = false;
+                // The server drops the user from the room together with the vote.
+                self.vote = None;
+                self.status = Some(format!("Disconnected ({}), reconnecting", reason));
+            }
+            Event::Message(msg) => self.on_message(msg),
+        }
+    }
+
+    fn on_message(&mut self, msg: RoomMessage) {
+        match msg {
+            RoomMessage::UserInfo(user_id) => self.user_id = user_id,
+            RoomMessage::RoomInfo(room) if room.id == self.room_id => {
+                self.selected = self.selected.min(room.cards.len().saturating_sub(1));
+                self.timer_deadline = room
+                    .timer
+                    .as_ref()
+                    .map(|timer| Instant::now() + Duration::from_millis(timer.remaining_ms));
+                self.room = Some(room);
+            }
+            RoomMessage::NoSuchRoom(room) => {
+                self.room = None;
+                self.status = Some(format!("There is no room {}", room));
+            }
+            RoomMessage::CardsRevealed => self.status = Some("Votes revealed".to_owned()),
+            RoomMessage::Reset => {
+                self.vote = None;
+                self.status = Some("Votes reset".to_owned());
+            }
+            RoomMessage::Revote => {
+                self.vote = None;
+                self.status = Some("Voting again".to_owned());
+            }
+            RoomMessage::EstimateSet(estimate) => {
+                self.status = Some(format!("Estimate set: {}", estimate));
+            }
+            RoomMessage::TimerExpired => self.status = Some("Time is up".to_owned()),
+            RoomMessage::RevealPending(seconds) => {
+                self.status = Some(format!("Everyone voted, revealing in {}s", seconds));
+            }
+            RoomMessage::Nudge => {
+                self.status = Some("The admin is waiting for your vote".to_owned());
+            }
+            RoomMessage::Chat(message) => {
+                let user_id = message.user_id;
+                let name = message.name.unwrap_or_else(|| self.user_name(user_id));
+                self.status = Some(format!("{}: {}", name, message.text));
+            }
+            RoomMessage::Error(RoomError::NoSuchCard(_)) => {
+                self.vote = None;
+                self.status = Some("That card is not in the deck".to_owned());
+            }
+            RoomMessage::Error(err) => self.status = Some(format!("Error: {:?}", err)),
+            _ => {}
+        }
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let cards = self.room.as_ref().map_or(0, |room| room.cards.len());
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::Quit)
+            }
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                if self.selected + 1 < cards {
+                    self.selected += 1;
+                }
+                None
+            }
+            KeyCode::Enter | KeyCode::Char(' ') if self.selected < cards => {
+                let card = self.selected as u32;
+                // Voting for the same card again withdraws the vote, like in the frontend.
+                self.vote = if self.vote == Some(card) {
+                    None
+                } else {
+                    Some(card)
+                };
+                Some(Action::Request(RoomRequest::Vote(card)))
+            }
+            KeyCode::Char('r') => Some(Action::Request(RoomRequest::Reveal)),
+            KeyCode::Char('x') => Some(Action::Request(RoomRequest::Reset)),
+            KeyCode::Char('v') => Some(Action::Request(RoomRequest::Revote)),
+            KeyCode::Char('n') => Some(Action::Request(RoomRequest::Nudge)),
+            KeyCode::Char('e') => {
+                let estimate = self
+                    .room
+                    .as_ref()
+                    .and_then(|room| room.suggested_estimate());
+                match estimate {
+                    Some(estimate) => Some(Action::Request(RoomRequest::SetEstimate(estimate))),
+                    None => {
+                        self.status = Some("There is no suggested estimate".to_owned());
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+mod app;
+mod ui;
+
+use std::{env, io, panic, path::PathBuf, thread, time::Duration};
+
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyEvent, KeyEventKind},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use planpoker_client::Client;
+use planpoker_common::{RoomId, RoomRequest};
+use ratatui::{backend::CrosstermBackend, Terminal};
+use structopt::StructOpt;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use app::{Action, App};
+
+/// Planning poker in a full-screen terminal UI
+#[derive(StructOpt)]
+#[structopt(name = "planpoker-tui")]
+struct Options {
+    /// Websocket endpoint of the backend
+    #[structopt(long, env = "PLANPOKER_URL", default_value = "ws://127.0.0.1:8082/ws/")]
+    url: String,
+    /// File keeping the session, shared with the command line client.
+    /// Defaults to `~/.planpoker-session`.
+    #[structopt(long, env = "PLANPOKER_SESSION_FILE", parse(from_os_str))]
+    session: Option<PathBuf>,
+    room: RoomId,
+}
+
+#[tokio::main]
+async fn main() {
+    let options = Options::from_args();
+
+    if let Err(err) = run(options).await {
+        eprintln!("Error: {:#}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(options: Options) -> Result<()> {
+    let session = options.session.unwrap_or_else(|| {
+        env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".planpoker-session")
+    });
+
+    let client = Client::builder(options.url)
+        .session_file(session)
+        .connect()
+        .await?;
+
+    let mut terminal = setup_terminal()?;
+    let result = event_loop(&mut terminal, client, options.room).await;
+    restore_terminal()?;
+
+    result
+}
+
+async fn event_loop(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    mut client: Client,
+    room: RoomId,
+) -> Result<()> {
+    let mut app = App::new(room, client.user_id());
+    let requests = client.sender();
+    requests.send(RoomRequest::JoinRoom(room))?;
+    let mut keys = spawn_key_reader();
+    // Redraws the timer countdown
+    let mut tick = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        terminal.draw(|f| ui::draw(f, &app))?;
+
+        tokio::select! {
+            key = keys.recv() => match key.and_then(|key| app.on_key(key)) {
+                Some(Action::Request(request)) => {
+                    let _ = requests.send(request);
+                }
+                Some(Action::Quit) => return Ok(()),
+                None => {}
+            },
+            event = client.next_event() => match event {
+                Some(event) => app.on_event(event),
+                None => return Ok(()),
+            },
+            _ = tick.tick() => {}
+        }
+    }
+}
+
+/// Reads key presses on a thread of its own, since crossterm only offers blocking reads.
+fn spawn_key_reader() -> UnboundedReceiver<KeyEvent> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press && tx.send(key).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
+fn setup_terminal() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+
+    // Leave the terminal usable when panicking
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        hook(info);
+    }));
+
+    Ok(Terminal::new(CrosstermBackend::new(io::stdout()))?)
+}
+
+fn restore_terminal() -> Result<()> {
+    terminal::disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    Ok(())
+}
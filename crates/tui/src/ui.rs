@@ -0,0 +1,208 @@
+use planpoker_common::{CardValue, RoomInfo, Vote};
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::app::App;
+
+pub fn draw(f: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(f.size());
+
+    draw_header(f, app, rows[0]);
+
+    match app.room.as_ref() {
+        Some(room) => {
+            draw_deck(f, app, room, rows[1]);
+
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(rows[2]);
+
+            draw_users(f, app, room, columns[0]);
+            draw_results(f, room, columns[1]);
+        }
+        None => {
+            let text = Paragraph::new(format!("Joining room {}...", app.room_id));
+            f.render_widget(text, rows[2]);
+        }
+    }
+
+    let status = Paragraph::new(app.status.clone().unwrap_or_default())
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(status, rows[3]);
+
+    f.render_widget(Paragraph::new(help(app)).style(dim()), rows[4]);
+}
+
+fn draw_header(f: &mut Frame, app: &App, area: Rect) {
+    let mut spans = vec![Span::styled(
+        format!("Room {}", app.room_id),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+
+    if let Some(room) = app.room.as_ref() {
+        spans.push(Span::raw(format!("  {:?}", room.state)));
+
+        if let Some(story) = room.current_story() {
+            spans.push(Span::raw(format!("  {}", story.title)));
+        }
+    }
+
+    if let Some(remaining) = app.timer_remaining() {
+        let secs = remaining.as_secs();
+        spans.push(Span::raw(format!("  {}:{:02} left", secs / 60, secs % 60)));
+    }
+
+    let (connection, color) = if app.connected {
+        ("connected", Color::Green)
+    } else {
+        ("reconnecting", Color::Red)
+    };
+    spans.push(Span::styled(
+        format!("  {}", connection),
+        Style::default().fg(color),
+    ));
+
+    let header = Paragraph::new(Line::from(spans)).block(block("Planning poker"));
+    f.render_widget(header, area);
+}
+
+fn draw_deck(f: &mut Frame, app: &App, room: &RoomInfo, area: Rect) {
+    let mut spans = Vec::new();
+
+    for (i, card) in room.cards.iter().enumerate() {
+        let mut style = Style::default();
+        if app.vote == Some(i as u32) {
+            style = style.fg(Color::Green).add_modifier(Modifier::BOLD);
+        }
+        if app.selected == i {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+
+        spans.push(Span::styled(format!(" {} ", card.as_str()), style));
+        spans.push(Span::raw(" "));
+    }
+
+    let deck = Paragraph::new(Line::from(spans)).block(block("Deck"));
+    f.render_widget(deck, area);
+}
+
+fn draw_users(f: &mut Frame, app: &App, room: &RoomInfo, area: Rect) {
+    let items = room
+        .users
+        .iter()
+        .map(|user| {
+            let name = app.user_name(user.user_id);
+            let admin = if user.user_id == room.admin { " *" } else { "" };
+            let (vote, style) = match user.vote {
+                Vote::None => ("not voted".to_owned(), dim()),
+                Vote::Revealed(card) => (card_label(room, card), Style::default()),
+                // Hidden votes of others, or revealed ones in anonymous mode
+                Vote::Unknown | Vote::Hidden(_) => ("voted".to_owned(), Style::default()),
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{}{}: ", name, admin)),
+                Span::styled(vote, style),
+            ]))
+        })
+        .collect::<Vec<_>>();
+
+    f.render_widget(List::new(items).block(block("Participants")), area);
+}
+
+fn draw_results(f: &mut Frame, room: &RoomInfo, area: Rect) {
+    let mut lines = Vec::new();
+
+    if room.state.is_revealed() {
+        let mut counts = room.cards.iter().map(|_| 0).collect::<Vec<usize>>();
+        for card in room.revealed_cards() {
+            if let Some(count) = counts.get_mut(card as usize) {
+                *count += 1;
+            }
+        }
+
+        for (card, count) in room.cards.iter().zip(counts) {
+            if count > 0 {
+                lines.push(Line::from(format!(
+                    "{:>4} {}",
+                    card.as_str(),
+                    "#".repeat(count)
+                )));
+            }
+        }
+
+        let values = room
+            .revealed_cards()
+            .into_iter()
+            .filter_map(|card| room.cards.get(card as usize)?.value())
+            .map(CardValue::as_f64)
+            .collect::<Vec<_>>();
+
+        lines.push(Line::from(""));
+        if !values.is_empty() {
+            let avg = values.iter().sum::<f64>() / values.len() as f64;
+            lines.push(Line::from(format!("Avg: {:.1}", avg)));
+        }
+        if let Some(suggested) = room.suggested_estimate() {
+            lines.push(Line::from(format!("Suggested: {}", suggested)));
+        }
+        if let Some(estimate) = room.current_story().and_then(|story| story.estimate) {
+            lines.push(Line::from(format!("Estimate: {}", estimate)));
+        }
+    } else {
+        let voted = room
+            .users
+            .iter()
+            .filter(|user| user.vote != Vote::None)
+            .count();
+        lines.push(Line::from(format!(
+            "{} of {} voted",
+            voted,
+            room.users.len()
+        )));
+    }
+
+    let results = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(block("Results"));
+    f.render_widget(results, area);
+}
+
+fn help(app: &App) -> String {
+    let mut help = "←/→ select  enter vote".to_owned();
+    if app.is_admin() {
+        help.push_str("  r reveal  x reset  v revote  e accept suggestion  n nudge");
+    }
+    help.push_str("  q quit");
+    help
+}
+
+fn card_label(room: &RoomInfo, card: u32) -> String {
+    room.cards
+        .get(card as usize)
+        .map_or("?", |c| c.as_str())
+        .to_owned()
+}
+
+fn block(title: &str) -> Block<'_> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+fn dim() -> Style {
+    Style::default().fg(Color::DarkGray)
+}